@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+use stdout_channel::{FileSink, MmapFileSink, StdoutChannel};
+
+const LINES: usize = 10_000;
+
+fn file_sinks(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = std::env::temp_dir().join(format!("file-sinks-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let line = "2024-01-01T00:00:00Z INFO request handled in 1.234ms status=200";
+
+    let mut group = c.benchmark_group("file_sinks");
+    group.throughput(Throughput::Elements(LINES as u64));
+    group.bench_function("buffered", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let stdout = FileSink::open(dir.join("buffered.out")).await.unwrap();
+                let stderr = FileSink::open(dir.join("buffered.err")).await.unwrap();
+                let chan = StdoutChannel::<&str>::with_sinks(stdout, stderr);
+                for _ in 0..LINES {
+                    chan.send(line);
+                }
+                chan.close().await.unwrap();
+            });
+        });
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let stdout = MmapFileSink::create(dir.join("mmap.out")).unwrap();
+                let stderr = MmapFileSink::create(dir.join("mmap.err")).unwrap();
+                let chan = StdoutChannel::<&str>::with_sinks(stdout, stderr);
+                for _ in 0..LINES {
+                    chan.send(line);
+                }
+                chan.close().await.unwrap();
+            });
+        });
+    });
+    group.finish();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, file_sinks);
+criterion_main!(benches);
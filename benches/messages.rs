@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use stack_string::StackString;
+use std::fmt::Display;
+use tokio::runtime::Runtime;
+
+use stdout_channel::{CowStr, OutputLine, OutputSink, SinkFuture, StdoutChannel};
+
+const LINES: usize = 10_000;
+
+struct NullSink;
+
+impl<T> OutputSink<T> for NullSink {
+    fn write<'a>(&'a mut self, _line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+fn send_literals<T>(rt: &Runtime)
+where
+    T: Display + Send + From<&'static str> + 'static,
+{
+    rt.block_on(async {
+        let chan = StdoutChannel::<T>::with_sinks(NullSink, NullSink);
+        for _ in 0..LINES {
+            chan.send("processing item, nothing to report");
+        }
+        chan.close().await.unwrap();
+    });
+}
+
+fn messages(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("literal_messages");
+    group.throughput(Throughput::Elements(LINES as u64));
+    group.bench_function("String", |b| b.iter(|| send_literals::<String>(&rt)));
+    group.bench_function("StackString", |b| {
+        b.iter(|| send_literals::<StackString>(&rt));
+    });
+    group.bench_function("Cow", |b| b.iter(|| send_literals::<CowStr>(&rt)));
+    group.finish();
+}
+
+/// Create, clone and close channels, which goes through the task handle and
+/// close report locks
+fn lifecycle(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    c.bench_function("channel_lifecycle", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let chan = StdoutChannel::<String>::with_sinks(NullSink, NullSink);
+                let cloned = chan.clone();
+                cloned.add_close_report(|| "done".into());
+                cloned.send("line");
+                chan.close().await.unwrap();
+            });
+        });
+    });
+}
+
+criterion_group!(benches, messages, lifecycle);
+criterion_main!(benches);
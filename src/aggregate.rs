@@ -0,0 +1,155 @@
+use std::{fmt::Display, sync::Arc, time::Duration};
+use tokio::{task::JoinHandle, time::interval};
+
+use crate::{sink::Stream, sync::Mutex, StdoutChannel};
+
+type GroupFormat<T> = Box<dyn Fn(&str, u64, Duration) -> T + Send + Sync>;
+
+struct Groups<T> {
+    chan: StdoutChannel<T>,
+    period: Duration,
+    format: GroupFormat<T>,
+    counts: Mutex<Vec<(String, u64)>>,
+}
+
+impl<T> Groups<T>
+where
+    T: Display + Send + 'static,
+{
+    fn flush(&self) {
+        let counts = std::mem::take(&mut *self.counts.lock());
+        for (key, count) in counts {
+            let line = (self.format)(&key, count, self.period);
+            self.chan.send_quiet(Stream::Stdout, line);
+        }
+    }
+}
+
+/// Counts messages by group key and sends one summary line per key every
+/// period instead of a line per item, e.g. `processed records: 4312 in last
+/// 10s`. Keys without new messages are not repeated.
+pub struct Aggregator<T>
+where
+    T: Display + Send + 'static,
+{
+    groups: Arc<Groups<T>>,
+    task: JoinHandle<()>,
+}
+
+impl<T> Aggregator<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>, period: Duration) -> Self {
+        Self::with_format(chan, period, |key, count, period| {
+            format!("{key}: {count} in last {}s", period.as_secs_f64()).into()
+        })
+    }
+}
+
+impl<T> Aggregator<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Render each summary line with `format(key, count, period)`
+    #[must_use]
+    pub fn with_format(
+        chan: &StdoutChannel<T>,
+        period: Duration,
+        format: impl Fn(&str, u64, Duration) -> T + Send + Sync + 'static,
+    ) -> Self {
+        let groups = Arc::new(Groups {
+            chan: chan.clone(),
+            period,
+            format: Box::new(format),
+            counts: Mutex::new(Vec::new()),
+        });
+        let task = tokio::spawn({
+            let groups = Arc::clone(&groups);
+            async move {
+                let mut ticks = interval(period);
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    groups.flush();
+                }
+            }
+        });
+        Self { groups, task }
+    }
+
+    /// Count one message for `key`
+    pub fn record(&self, key: &str) {
+        self.add(key, 1);
+    }
+
+    /// Count `n` messages for `key`
+    pub fn add(&self, key: &str, n: u64) {
+        let mut counts = self.groups.counts.lock();
+        match counts.iter_mut().find(|(k, _)| k == key) {
+            Some((_, count)) => *count += n,
+            None => counts.push((key.to_string(), n)),
+        }
+    }
+
+    /// Send the summary lines now instead of waiting for the next period
+    pub fn flush(&self) {
+        self.groups.flush();
+    }
+}
+
+/// Stops the periodic task and sends what was counted since the last flush
+impl<T> Drop for Aggregator<T>
+where
+    T: Display + Send + 'static,
+{
+    fn drop(&mut self) {
+        self.task.abort();
+        self.groups.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::Aggregator;
+
+    #[tokio::test]
+    async fn test_aggregator() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+
+        let agg = Aggregator::new(&chan, Duration::from_millis(50));
+        for _ in 0..4312 {
+            agg.record("processed records");
+        }
+        agg.add("skipped", 3);
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        agg.record("processed records");
+        drop(agg);
+
+        let agg = Aggregator::with_format(&chan, Duration::from_secs(3600), |key, n, _| {
+            format!("{n} {key}")
+        });
+        agg.add("retries", 2);
+        agg.flush();
+        agg.flush();
+        drop(agg);
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [
+                "processed records: 4312 in last 0.05s",
+                "skipped: 3 in last 0.05s",
+                "processed records: 1 in last 0.05s",
+                "2 retries",
+            ]
+        );
+        Ok(())
+    }
+}
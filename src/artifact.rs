@@ -0,0 +1,149 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{Display, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::fs;
+
+use crate::{StdoutChannel, StdoutChannelError};
+
+/// Makes the temporary file names of concurrent writes unique in this process
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps large payloads out of the console stream: artifacts are written to
+/// a side directory, named by the SHA-256 of their content, and only a short
+/// reference line goes through the channel.
+pub struct ArtifactStore<T> {
+    chan: StdoutChannel<T>,
+    dir: PathBuf,
+}
+
+impl<T> ArtifactStore<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>, dir: impl AsRef<Path>) -> Self {
+        Self {
+            chan: chan.clone(),
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store `bytes` and send an `artifact <name>: <path> (<len> bytes,
+    /// sha256:<hash>)` line, returning the path of the stored file. The
+    /// extension of `name` is kept, identical content is only written once.
+    /// Every write goes to its own temporary file which is renamed into
+    /// place, so concurrent stores of the same content (from this or another
+    /// process) never see a partial file.
+    /// # Errors
+    ///
+    /// Will error if the directory or file can't be written
+    pub async fn send_artifact(
+        &self,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<PathBuf, StdoutChannelError> {
+        let hash = Sha256::digest(bytes)
+            .iter()
+            .fold(String::with_capacity(64), |mut s, b| {
+                let _ = write!(s, "{b:02x}");
+                s
+            });
+        let mut path = self.dir.join(&hash);
+        if let Some(extension) = Path::new(name).extension() {
+            path.set_extension(extension);
+        }
+        if fs::metadata(&path).await.is_err() {
+            fs::create_dir_all(&self.dir).await?;
+            let tmp = self.dir.join(format!(
+                ".{hash}.{}.{}.tmp",
+                process::id(),
+                TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::write(&tmp, bytes).await?;
+            if let Err(e) = fs::rename(&tmp, &path).await {
+                let _ = fs::remove_file(&tmp).await;
+                // someone else stored the same content first
+                if fs::metadata(&path).await.is_err() {
+                    return Err(e.into());
+                }
+            }
+        }
+        self.chan.send(format!(
+            "artifact {name}: {} ({} bytes, sha256:{hash})",
+            path.display(),
+            bytes.len()
+        ));
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::ArtifactStore;
+
+    #[tokio::test]
+    async fn test_send_artifact() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let store = ArtifactStore::new(&chan, dir);
+        let first = store.send_artifact("report.html", b"hello").await?;
+        let second = store.send_artifact("copy.html", b"hello").await?;
+        chan.close().await?;
+
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(first, dir.join(format!("{hash}.html")));
+        assert_eq!(first, second);
+        assert_eq!(tokio::fs::read(&first).await?, b"hello");
+        assert_eq!(
+            stdout.lock().await[0],
+            format!(
+                "artifact report.html: {} (5 bytes, sha256:{hash})",
+                first.display()
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_artifacts() -> Result<(), StdoutChannelError> {
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::<String>::new(), MockStdout::new());
+
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let store = ArtifactStore::new(&chan, &dir);
+                tokio::spawn(async move {
+                    store
+                        .send_artifact(&format!("report-{i}.txt"), &[b'x'; 100_000])
+                        .await
+                })
+            })
+            .collect();
+        let mut paths = Vec::new();
+        for task in tasks {
+            paths.push(task.await??);
+        }
+        chan.close().await?;
+
+        assert!(paths.windows(2).all(|w| w[0] == w[1]));
+        assert_eq!(tokio::fs::read(&paths[0]).await?.len(), 100_000);
+        // no temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::{Display, Write};
+
+use crate::{OutputFormat, StdoutChannel};
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Announce the start of `app`.
+    ///
+    /// Prints `app version (build_info)` to stderr, or nothing when the
+    /// verbosity is 0 or the format is `Quiet`. In JSON mode a
+    /// `{"type":"banner","app":...,"version":...,"build":...}` record is sent
+    /// to stdout instead. An empty `build_info` is left out.
+    pub fn banner(&self, app_name: &str, version: &str, build_info: &str) {
+        let config = self.output_config();
+        if config.format.is_json() {
+            let mut record = format!(
+                r#"{{"type":"banner","app":{},"version":{}"#,
+                json_string(app_name),
+                json_string(version)
+            );
+            if !build_info.is_empty() {
+                write!(record, r#","build":{}"#, json_string(build_info)).ok();
+            }
+            record.push('}');
+            self.send(record);
+        } else if config.verbosity > 0 && config.format != OutputFormat::Quiet {
+            if build_info.is_empty() {
+                self.send_err(format!("{app_name} {version}"));
+            } else {
+                self.send_err(format!("{app_name} {version} ({build_info})"));
+            }
+        }
+    }
+}
+
+/// `s` as a quoted JSON string
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                write!(escaped, "\\u{:04x}", u32::from(c)).ok();
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, OutputConfig, OutputFormat, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_banner() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        chan.banner("svc", "1.2.3", "abc123 2026-10-01");
+        chan.banner("svc", "1.2.3", "");
+        let json = chan.clone().with_output_config(OutputConfig {
+            format: OutputFormat::Json,
+            ..OutputConfig::default()
+        });
+        json.banner("svc", "1.2.3", "built \"today\"\n");
+        let quiet = chan.clone().with_output_config(OutputConfig {
+            verbosity: 0,
+            ..OutputConfig::default()
+        });
+        quiet.banner("svc", "1.2.3", "");
+        chan.close().await?;
+
+        assert_eq!(
+            stderr.snapshot(),
+            ["svc 1.2.3 (abc123 2026-10-01)", "svc 1.2.3"]
+        );
+        assert_eq!(
+            stdout.snapshot(),
+            [r#"{"type":"banner","app":"svc","version":"1.2.3","build":"built \"today\"\n"}"#]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    child::PendingGuard, sink::Stream, SendStatus, StdoutChannel, StdoutMessage, StdoutQueue,
+};
+
+/// Guards a queued line holds until the writer task has written it: a slot
+/// of a bounded stream and the pending count of the child channel that sent
+/// it
+#[derive(Default)]
+pub(crate) struct Slot {
+    _permit: Option<OwnedSemaphorePermit>,
+    _pending: Option<PendingGuard>,
+}
+
+impl Slot {
+    pub(crate) fn pending(pending: PendingGuard) -> Self {
+        Self {
+            _permit: None,
+            _pending: Some(pending),
+        }
+    }
+}
+
+/// What `send_bounded` and `try_send` do with a line sent to a full stream
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// `send_bounded` waits for a free slot, `try_send` discards the line
+    #[default]
+    Block,
+    /// Discard the line being sent
+    DropNewest,
+    /// Discard the oldest queued line to make room
+    DropOldest,
+    /// Discard the line being sent, and queue a `N lines suppressed` line
+    /// ahead of the next one accepted
+    DropAndCount,
+}
+
+type SuppressedNote<T> = dyn Fn(u64) -> T + Send + Sync;
+
+pub(crate) struct Bounds<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    note: Option<Box<SuppressedNote<T>>>,
+    stdout: Arc<Semaphore>,
+    stderr: Arc<Semaphore>,
+    dropped: [AtomicU64; 2],
+    suppressed: [AtomicU64; 2],
+}
+
+impl<T> Bounds<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            policy: OverflowPolicy::Block,
+            note: None,
+            stdout: Arc::new(Semaphore::new(capacity)),
+            stderr: Arc::new(Semaphore::new(capacity)),
+            dropped: Default::default(),
+            suppressed: Default::default(),
+        }
+    }
+
+    fn slots(&self, stream: Stream) -> &Arc<Semaphore> {
+        match stream {
+            Stream::Stdout => &self.stdout,
+            Stream::Stderr => &self.stderr,
+        }
+    }
+
+    fn drop_line(&self, stream: Stream) {
+        self.dropped[stream as usize].fetch_add(1, Ordering::Relaxed);
+        if self.policy == OverflowPolicy::DropAndCount {
+            self.suppressed[stream as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Limit each stream to `capacity` queued lines for `send_bounded` and
+    /// `try_send`. `send`, `send_err` and the paced sends don't wait for a
+    /// slot, so lines sent with them aren't limited. A `capacity` of zero is
+    /// treated as one.
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        let mut bounds = Bounds::new(capacity.max(1));
+        if let Some(old) = self.bounds.take().and_then(|b| Arc::try_unwrap(b).ok()) {
+            bounds.policy = old.policy;
+            bounds.note = old.note;
+        }
+        self.bounds = Some(Arc::new(bounds));
+        self
+    }
+
+    /// Capacity set with `with_capacity`
+    #[must_use]
+    pub fn capacity(&self) -> Option<usize> {
+        self.bounds.as_ref().map(|b| b.capacity)
+    }
+
+    /// Lines discarded because `stream` was full
+    #[must_use]
+    pub fn dropped(&self, stream: Stream) -> u64 {
+        self.bounds
+            .as_ref()
+            .map_or(0, |b| b.dropped[stream as usize].load(Ordering::Relaxed))
+    }
+
+    fn queue(&self, stream: Stream) -> &StdoutQueue<T> {
+        match stream {
+            Stream::Stdout => &self.stdout_queue,
+            Stream::Stderr => &self.stderr_queue,
+        }
+    }
+
+    pub(crate) fn push_slot(&self, stream: Stream, item: T, slot: Slot) {
+        if self.rejects_send() || self.discards(stream) {
+            return;
+        }
+        self.start_tasks();
+        match stream {
+            Stream::Stdout => self.stats.sent_stdout(),
+            Stream::Stderr => self.stats.sent_stderr(),
+        }
+        self.queue(stream)
+            .push(StdoutMessage::Mesg(item, stream, slot));
+    }
+
+    /// Take a slot of `stream` without waiting, evicting queued lines if the
+    /// policy is `DropOldest`
+    fn try_slot(&self, bounds: &Bounds<T>, stream: Stream) -> Option<OwnedSemaphorePermit> {
+        loop {
+            if let Ok(permit) = Arc::clone(bounds.slots(stream)).try_acquire_owned() {
+                return Some(permit);
+            }
+            if bounds.policy != OverflowPolicy::DropOldest {
+                return None;
+            }
+            // lines sent with `send` hold no slot, evicting them frees
+            // nothing but they are older than the line being sent
+            match self.queue(stream).try_pop() {
+                Some(StdoutMessage::Mesg(..)) => bounds.drop_line(stream),
+                // a barrier or close can't be evicted, put it back behind
+                // the queued lines and drop the new one instead
+                Some(message) => {
+                    self.queue(stream).push(message);
+                    return None;
+                }
+                // every slot is held by a line being written
+                None => return None,
+            }
+        }
+    }
+
+    fn push_permit(
+        &self,
+        bounds: &Bounds<T>,
+        stream: Stream,
+        item: T,
+        permit: OwnedSemaphorePermit,
+    ) {
+        if let Some(note) = &bounds.note {
+            let suppressed = bounds.suppressed[stream as usize].swap(0, Ordering::Relaxed);
+            if suppressed > 0 {
+                self.push_slot(stream, note(suppressed), Slot::default());
+            }
+        }
+        let slot = Slot {
+            _permit: Some(permit),
+            _pending: None,
+        };
+        self.push_slot(stream, item, slot);
+    }
+
+    async fn push_bounded(&self, stream: Stream, item: T) {
+        let Some(bounds) = &self.bounds else {
+            self.push_slot(stream, item, Slot::default());
+            return;
+        };
+        let permit = match self.try_slot(bounds, stream) {
+            Some(permit) => permit,
+            None if bounds.policy == OverflowPolicy::Block => {
+                // the semaphores are never closed
+                let Ok(permit) = Arc::clone(bounds.slots(stream)).acquire_owned().await else {
+                    return;
+                };
+                permit
+            }
+            None => {
+                bounds.drop_line(stream);
+                return;
+            }
+        };
+        self.push_permit(bounds, stream, item, permit);
+    }
+
+    fn try_push(&self, stream: Stream, item: T) -> SendStatus {
+        if self.is_closed() {
+            return SendStatus::Closed;
+        }
+        let Some(bounds) = &self.bounds else {
+            self.push_slot(stream, item, Slot::default());
+            return SendStatus::Accepted;
+        };
+        let Some(permit) = self.try_slot(bounds, stream) else {
+            bounds.drop_line(stream);
+            return SendStatus::Full;
+        };
+        self.push_permit(bounds, stream, item, permit);
+        SendStatus::Accepted
+    }
+
+    /// Send to stdout, waiting while `capacity` lines are already queued
+    /// unless the overflow policy discards a line
+    pub async fn send_bounded(&self, item: impl Into<T>) {
+        self.push_bounded(Stream::Stdout, item.into()).await;
+    }
+
+    /// Send to stderr, waiting while `capacity` lines are already queued
+    /// unless the overflow policy discards a line
+    pub async fn send_err_bounded(&self, item: impl Into<T>) {
+        self.push_bounded(Stream::Stderr, item.into()).await;
+    }
+
+    /// Send to stdout without waiting, returns `SendStatus::Full` if the
+    /// queue is full and the item was discarded, or `SendStatus::Closed` if
+    /// the channel is closed
+    pub fn try_send(&self, item: impl Into<T>) -> SendStatus {
+        self.try_push(Stream::Stdout, item.into())
+    }
+
+    /// Send to stderr without waiting, returns `SendStatus::Full` if the
+    /// queue is full and the item was discarded, or `SendStatus::Closed` if
+    /// the channel is closed
+    pub fn try_send_err(&self, item: impl Into<T>) -> SendStatus {
+        self.try_push(Stream::Stderr, item.into())
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: From<String> + 'static,
+{
+    /// Choose what happens to lines sent to a full stream, call after
+    /// `with_capacity`: unbounded channels are never full
+    #[must_use]
+    pub fn with_overflow(mut self, policy: OverflowPolicy) -> Self {
+        let Some(capacity) = self.capacity() else {
+            return self;
+        };
+        let note: Option<Box<SuppressedNote<T>>> = match policy {
+            OverflowPolicy::DropAndCount => Some(Box::new(|n| {
+                let lines = if n == 1 { "line" } else { "lines" };
+                T::from(format!("{n} {lines} suppressed"))
+            })),
+            _ => None,
+        };
+        let mut bounds = self
+            .bounds
+            .take()
+            .and_then(|b| Arc::try_unwrap(b).ok())
+            .unwrap_or_else(|| Bounds::new(capacity));
+        bounds.policy = policy;
+        bounds.note = note;
+        self.bounds = Some(Arc::new(bounds));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{sink::Stream, MockStdout, SendStatus, StdoutChannel, StdoutChannelError};
+
+    use super::OverflowPolicy;
+
+    #[tokio::test]
+    async fn test_bounded() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan =
+            StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new()).with_capacity(2);
+        assert_eq!(chan.capacity(), Some(2));
+
+        // hold the mock so the writer task can't drain the queue
+        let guard = stdout.lock().await;
+        assert_eq!(chan.try_send("a"), SendStatus::Accepted);
+        tokio::task::yield_now().await;
+        assert_eq!(chan.try_send("b"), SendStatus::Accepted);
+        assert_eq!(chan.try_send("c"), SendStatus::Full);
+        assert_eq!(chan.try_send_err("e"), SendStatus::Accepted);
+
+        let waiting = tokio::spawn({
+            let chan = chan.clone();
+            async move { chan.send_bounded("f").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+        drop(guard);
+        waiting.await?;
+        chan.close().await?;
+        assert_eq!(stdout.snapshot(), ["a", "b", "f"]);
+        assert_eq!(chan.dropped(Stream::Stdout), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overflow_policy() -> Result<(), StdoutChannelError> {
+        for (policy, expected) in [
+            (OverflowPolicy::DropNewest, &["a", "b", "c", "f"][..]),
+            (OverflowPolicy::DropOldest, &["a", "d", "e", "f"]),
+            (
+                OverflowPolicy::DropAndCount,
+                &["a", "b", "c", "2 lines suppressed", "f"],
+            ),
+        ] {
+            let stdout = MockStdout::<String>::new();
+            let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new())
+                .with_capacity(3)
+                .with_overflow(policy);
+            let guard = stdout.lock().await;
+            chan.send_bounded("a").await;
+            // wait for the writer task to take "a" and block on the mock
+            while chan.describe().stdout.queued > 0 {
+                tokio::task::yield_now().await;
+            }
+            for line in ["b", "c", "d", "e"] {
+                chan.send_bounded(line).await;
+            }
+            assert_eq!(chan.dropped(Stream::Stdout), 2);
+            drop(guard);
+            while chan.describe().stdout.queued > 0 {
+                tokio::task::yield_now().await;
+            }
+            tokio::task::yield_now().await;
+            chan.send_bounded("f").await;
+            chan.close().await?;
+            assert_eq!(stdout.snapshot(), expected, "{policy:?}");
+        }
+        Ok(())
+    }
+}
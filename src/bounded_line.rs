@@ -0,0 +1,135 @@
+use std::{convert::TryFrom, fmt, ops::Deref};
+
+/// Error creating a `BoundedLine` from a line that is too long
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+#[error("line of {len} bytes exceeds the limit of {max} bytes")]
+pub struct LineTooLong {
+    pub len: usize,
+    pub max: usize,
+}
+
+/// A line of at most `N` bytes, checked when it is created rather than when
+/// it is written, for protocols with strict line lengths, e.g.
+/// `StdoutChannel<BoundedLine<1024>>` for syslog. It converts into a
+/// `String` for channels of `String`s.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedLine<const N: usize>(String);
+
+impl<const N: usize> BoundedLine<N> {
+    pub const MAX: usize = N;
+
+    /// # Errors
+    ///
+    /// Returns `LineTooLong` if `line` is longer than `N` bytes
+    pub fn new(line: impl Into<String>) -> Result<Self, LineTooLong> {
+        let line = line.into();
+        if line.len() > N {
+            return Err(LineTooLong {
+                len: line.len(),
+                max: N,
+            });
+        }
+        Ok(Self(line))
+    }
+
+    /// Cut `line` to the longest prefix of at most `N` bytes that ends on a
+    /// character boundary
+    #[must_use]
+    pub fn truncated(line: impl Into<String>) -> Self {
+        let mut line = line.into();
+        if line.len() > N {
+            let end = (0..=N)
+                .rev()
+                .find(|&i| line.is_char_boundary(i))
+                .unwrap_or(0);
+            line.truncate(end);
+        }
+        Self(line)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl<const N: usize> Deref for BoundedLine<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> AsRef<str> for BoundedLine<N> {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> fmt::Display for BoundedLine<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedLine<N> {
+    type Error = LineTooLong;
+
+    fn try_from(line: String) -> Result<Self, Self::Error> {
+        Self::new(line)
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for BoundedLine<N> {
+    type Error = LineTooLong;
+
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        Self::new(line)
+    }
+}
+
+impl<const N: usize> From<BoundedLine<N>> for String {
+    fn from(line: BoundedLine<N>) -> Self {
+        line.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{BoundedLine, LineTooLong};
+
+    type SerialLine = BoundedLine<8>;
+
+    #[tokio::test]
+    async fn test_bounded_line() -> Result<(), StdoutChannelError> {
+        assert_eq!(
+            SerialLine::new("123456789"),
+            Err(LineTooLong { len: 9, max: 8 })
+        );
+        assert_eq!(SerialLine::truncated("1234567é").as_str(), "1234567");
+        assert_eq!(SerialLine::MAX, 8);
+
+        let stdout = MockStdout::<SerialLine>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send(SerialLine::try_from("ok").unwrap());
+        chan.close().await?;
+        assert_eq!(stdout.snapshot(), [SerialLine::new("ok").unwrap()]);
+
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send(SerialLine::truncated("as string"));
+        chan.close().await?;
+        assert_eq!(stdout.snapshot(), ["as strin"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,698 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+
+#[cfg(feature = "rotation")]
+use crate::sink::{partitioned::Partition, retention::RetentionPolicy, rotating::RotatingFileSink};
+use crate::{
+    clock::{Clock, TimeZone, TimestampFormat},
+    config::{ColorMode, EnvConfig, OutputConfig},
+    format::{JsonFields, OutputFormat},
+    sink::{
+        file::FileSinkOptions,
+        framed::FramedSink,
+        stdio::{StderrSink, StdoutSink},
+        tee::TeeSink,
+        OutputSink, Stream,
+    },
+    RateLimiter, StdoutChannel, StdoutChannelError,
+};
+
+/// A single problem found by `StdoutChannelBuilder::validate`
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigProblem {
+    #[error("more than one sink configured for {0}")]
+    ConflictingSinks(Stream),
+    #[error("stdout and stderr both write to {}", .0.display())]
+    SameFile(PathBuf),
+    #[error("empty file path for {0}")]
+    EmptyPath(Stream),
+    #[error("directory {} does not exist", .0.display())]
+    MissingDirectory(PathBuf),
+    #[error("rate limit threshold must be greater than zero")]
+    ZeroThreshold,
+    #[error("queue capacity must be greater than zero")]
+    ZeroCapacity,
+    #[error("flush interval must be greater than zero")]
+    ZeroFlushInterval,
+    #[error("invalid value {value:?} for {name}")]
+    InvalidEnv { name: &'static str, value: String },
+}
+
+/// Every problem with a `StdoutChannelBuilder` configuration
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    problems: Vec<ConfigProblem>,
+}
+
+impl ConfigError {
+    #[must_use]
+    pub fn problems(&self) -> &[ConfigProblem] {
+        &self.problems
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid channel configuration")?;
+        for (i, problem) in self.problems.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{sep}{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+enum Target<T> {
+    File(PathBuf),
+    Sink(Box<dyn OutputSink<T>>),
+}
+
+/// Wraps the target of a stream in a `TeeSink`
+type Mirror<T> = Box<dyn FnOnce(Box<dyn OutputSink<T>>) -> Box<dyn OutputSink<T>> + Send>;
+
+/// Collects the configuration of a `StdoutChannel` and checks all of it at
+/// once in `validate` / `build`.
+///
+/// Streams without a configured target write to the process stdout and
+/// stderr.
+pub struct StdoutChannelBuilder<T> {
+    stdout: Vec<Target<T>>,
+    stderr: Vec<Target<T>>,
+    mirrors: Vec<(Stream, Mirror<T>)>,
+    file_options: FileSinkOptions,
+    #[cfg(feature = "rotation")]
+    rotation: Option<FileRotation>,
+    rate_limit: Option<(RateLimiter, usize)>,
+    rng: Option<u64>,
+    capacity: Option<usize>,
+    ordered: bool,
+    buffer_size: Option<usize>,
+    flush_interval: Option<Duration>,
+    framing: Framing,
+    clock: Clock,
+    config: OutputConfig,
+    env_problems: Vec<ConfigProblem>,
+}
+
+impl<T> Default for StdoutChannelBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> StdoutChannelBuilder<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            mirrors: Vec::new(),
+            file_options: FileSinkOptions::new(),
+            #[cfg(feature = "rotation")]
+            rotation: None,
+            rate_limit: None,
+            rng: None,
+            capacity: None,
+            ordered: false,
+            buffer_size: None,
+            flush_interval: None,
+            framing: Framing::default(),
+            clock: Clock::default(),
+            config: OutputConfig::default(),
+            env_problems: Vec::new(),
+        }
+    }
+
+    /// Apply the `STDOUT_CHANNEL_*` environment variables:
+    /// `STDOUT_CHANNEL_COLOR` (`auto`, `always`, `never`),
+    /// `STDOUT_CHANNEL_FORMAT` (an `OutputFormat`), `STDOUT_CHANNEL_JSON`
+    /// (`1`/`0`, `true`/`false`, ... same as `STDOUT_CHANNEL_FORMAT=json`),
+    /// `STDOUT_CHANNEL_VERBOSITY` (a number), `STDOUT_CHANNEL_ORDERED`
+    /// (`1`/`0`, ... see `ordered`) and `STDOUT_CHANNEL_LOG_FILE` (write
+    /// stderr to this file). Settings whose variable is unset or invalid
+    /// keep their value, invalid values are reported by `validate`.
+    #[must_use]
+    pub fn from_env(self) -> Self {
+        self.env(EnvConfig::from_env())
+    }
+
+    fn env(mut self, env: EnvConfig) -> Self {
+        env.apply(&mut self.config);
+        self.env_problems = env.problems;
+        self.ordered = env.ordered.unwrap_or(self.ordered);
+        match env.log_file {
+            Some(path) => self.file(Stream::Stderr, path),
+            None => self,
+        }
+    }
+
+    fn targets(&mut self, stream: Stream) -> &mut Vec<Target<T>> {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    /// Write `stream` to a `FileSink` at `path`
+    #[must_use]
+    pub fn file(mut self, stream: Stream, path: impl AsRef<Path>) -> Self {
+        self.targets(stream)
+            .push(Target::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Write `stream` to a custom sink
+    #[must_use]
+    pub fn sink(mut self, stream: Stream, sink: impl OutputSink<T> + 'static) -> Self {
+        self.targets(stream).push(Target::Sink(Box::new(sink)));
+        self
+    }
+
+    /// Also write `stream` to `sink`, like `tee`, e.g. to keep a transcript
+    /// of what the terminal shows. Mirrors get lines framed the same way as
+    /// the target, see `TeeSink`.
+    #[must_use]
+    pub fn mirror(mut self, stream: Stream, sink: impl OutputSink<T> + 'static) -> Self
+    where
+        T: Clone + Send + 'static,
+    {
+        self.mirrors.push((
+            stream,
+            Box::new(move |target| Box::new(TeeSink::new(target, sink))),
+        ));
+        self
+    }
+
+    /// Options used to open the files given to `file`
+    #[must_use]
+    pub fn file_options(mut self, options: FileSinkOptions) -> Self {
+        self.file_options = options;
+        self
+    }
+
+    /// Write the files given to `file` with a `RotatingFileSink`, rotating
+    /// once a file would grow past `max_bytes` and/or when the hour or day
+    /// changes, and deleting rotated files outside `retention`
+    #[cfg(feature = "rotation")]
+    #[must_use]
+    pub fn rotate(
+        mut self,
+        max_bytes: Option<u64>,
+        every: Option<Partition>,
+        retention: Option<RetentionPolicy>,
+    ) -> Self {
+        self.rotation = Some(FileRotation {
+            max_bytes,
+            every,
+            retention,
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.config.color = color;
+        self
+    }
+
+    #[must_use]
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.config.format = format;
+        self
+    }
+
+    #[must_use]
+    pub fn verbosity(mut self, verbosity: u8) -> Self {
+        self.config.verbosity = verbosity;
+        self
+    }
+
+    /// Fields added to the lines of a `JsonChannel`
+    #[must_use]
+    pub fn json_fields(mut self, fields: JsonFields) -> Self {
+        self.config.json_fields = fields;
+        self
+    }
+
+    /// Same as `StdoutChannel::with_rate_limit`
+    #[must_use]
+    pub fn rate_limit(mut self, rate_limiter: RateLimiter, threshold: usize) -> Self {
+        self.rate_limit = Some((rate_limiter, threshold));
+        self
+    }
+
+    /// Seed the randomness of the channel so runs are reproducible, it
+    /// applies to the jitter of the rate limiter given to `rate_limit`, see
+    /// `RateLimiter::with_rng`. Sinks given to `sink` are seeded where
+    /// they are created, e.g. with `GelfSink::with_rng` or
+    /// `RandomFaults::with_rng`.
+    #[must_use]
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.rng = Some(seed);
+        self
+    }
+
+    /// Same as `StdoutChannel::with_capacity`, the queues stay unbounded
+    /// unless this is set
+    #[must_use]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Write both streams from one queue in the order lines were sent, see
+    /// `StdoutChannel::ordered`
+    #[must_use]
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Buffer size of the files given to `file`, overrides the one of
+    /// `file_options`, see `FileSinkOptions::buffer_size`
+    #[must_use]
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Same as `StdoutChannel::with_flush_interval`
+    #[must_use]
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// End lines with `terminator` instead of `\n`, see `FramedSink`
+    #[must_use]
+    pub fn line_terminator(mut self, terminator: &str) -> Self {
+        self.framing.terminator = Some(terminator.into());
+        self
+    }
+
+    /// Prefix the lines of both streams with a fixed tag like
+    /// `[worker-3] `, added by the writer tasks so the sent values stay
+    /// clean, see `FramedSink`
+    #[must_use]
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.framing.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Prefix lines with the time they are written, see `FramedSink`
+    #[must_use]
+    pub fn timestamps(mut self, timestamps: bool) -> Self {
+        self.framing.timestamps = timestamps;
+        self
+    }
+
+    /// Prefix lines with timestamps written in `format`
+    #[must_use]
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.framing.timestamps = true;
+        self.framing.format = format;
+        self
+    }
+
+    /// Zone of the timestamps added by `timestamps` and of `JsonChannel`
+    /// lines
+    #[must_use]
+    pub fn time_zone(mut self, zone: TimeZone) -> Self {
+        self.config.time_zone = zone;
+        self
+    }
+
+    /// Clock of the channel and of the timestamps added by `timestamps`,
+    /// see `StdoutChannel::with_clock`
+    #[must_use]
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Check the whole configuration without opening anything
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` listing every problem found
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = self.env_problems.clone();
+        let mut files = Vec::new();
+        for (stream, targets) in [
+            (Stream::Stdout, &self.stdout),
+            (Stream::Stderr, &self.stderr),
+        ] {
+            if targets.len() > 1 {
+                problems.push(ConfigProblem::ConflictingSinks(stream));
+            }
+            for target in targets {
+                let Target::File(path) = target else {
+                    continue;
+                };
+                if path.as_os_str().is_empty() {
+                    problems.push(ConfigProblem::EmptyPath(stream));
+                    continue;
+                }
+                match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => {
+                        problems.push(ConfigProblem::MissingDirectory(dir.to_path_buf()));
+                    }
+                    _ => files.push((stream, path)),
+                }
+            }
+        }
+        for (i, (stream, path)) in files.iter().enumerate() {
+            if files[..i].iter().any(|(s, p)| s != stream && p == path) {
+                problems.push(ConfigProblem::SameFile((*path).clone()));
+            }
+        }
+        if matches!(self.rate_limit, Some((_, 0))) {
+            problems.push(ConfigProblem::ZeroThreshold);
+        }
+        if self.capacity == Some(0) {
+            problems.push(ConfigProblem::ZeroCapacity);
+        }
+        if self.flush_interval == Some(Duration::ZERO) {
+            problems.push(ConfigProblem::ZeroFlushInterval);
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+}
+
+impl<T> StdoutChannelBuilder<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Validate the configuration, open any files and create the channel
+    /// # Errors
+    ///
+    /// Will error with `StdoutChannelError::ConfigError` if `validate` fails,
+    /// or if a file can't be opened
+    pub async fn build(self) -> Result<StdoutChannel<T>, StdoutChannelError> {
+        self.validate()?;
+        let mut file_options = self.file_options;
+        if let Some(buffer_size) = self.buffer_size {
+            file_options = file_options.buffer_size(buffer_size);
+        }
+        let files = FileTargets {
+            options: file_options,
+            #[cfg(feature = "rotation")]
+            rotation: self.rotation,
+        };
+        let stdout_sink = files.open(self.stdout).await?;
+        let stderr_sink = files.open(self.stderr).await?;
+        let framed = self.framing.is_enabled();
+        let plain = !framed && self.mirrors.is_empty();
+        let chan = match (stdout_sink, stderr_sink, self.ordered) {
+            (None, None, false) if plain => StdoutChannel::new(),
+            (None, None, true) if plain => StdoutChannel::ordered(),
+            (o, e, ordered) => {
+                let mut o = o.unwrap_or_else(|| Box::new(StdoutSink::new()));
+                let mut e = e.unwrap_or_else(|| Box::new(StderrSink::new()));
+                for (stream, mirror) in self.mirrors {
+                    match stream {
+                        Stream::Stdout => o = mirror(o),
+                        Stream::Stderr => e = mirror(e),
+                    }
+                }
+                if framed {
+                    o = self.framing.wrap(o, &self.clock, self.config.time_zone);
+                    e = self.framing.wrap(e, &self.clock, self.config.time_zone);
+                }
+                if ordered {
+                    StdoutChannel::ordered_with_sinks(o, e)
+                } else {
+                    StdoutChannel::with_sinks(o, e)
+                }
+            }
+        };
+        let mut chan = chan.with_output_config(self.config).with_clock(self.clock);
+        if let Some(capacity) = self.capacity {
+            chan = chan.with_capacity(capacity);
+        }
+        if let Some((mut rate_limiter, threshold)) = self.rate_limit {
+            if let Some(seed) = self.rng {
+                rate_limiter = rate_limiter.with_rng(seed);
+            }
+            chan = chan.with_rate_limit(rate_limiter, threshold);
+        }
+        if let Some(interval) = self.flush_interval {
+            chan = chan.with_flush_interval(interval);
+        }
+        Ok(chan)
+    }
+}
+
+/// How the `FramedSink` around each stream's sink frames lines
+#[derive(Default)]
+struct Framing {
+    terminator: Option<String>,
+    prefix: Option<String>,
+    timestamps: bool,
+    format: TimestampFormat,
+}
+
+impl Framing {
+    fn is_enabled(&self) -> bool {
+        self.terminator.is_some() || self.prefix.is_some() || self.timestamps
+    }
+
+    fn wrap<T>(
+        &self,
+        sink: Box<dyn OutputSink<T>>,
+        clock: &Clock,
+        time_zone: TimeZone,
+    ) -> Box<dyn OutputSink<T>>
+    where
+        T: 'static,
+    {
+        let framed = FramedSink::new(sink)
+            .with_terminator(self.terminator.as_deref().unwrap_or("\n"))
+            .with_prefix(self.prefix.as_deref().unwrap_or_default())
+            .with_timestamps(self.timestamps)
+            .with_timestamp_format(self.format.clone())
+            .with_clock(clock.clone())
+            .with_time_zone(time_zone);
+        Box::new(framed)
+    }
+}
+
+/// Rotation of the files given to `file`, see `StdoutChannelBuilder::rotate`
+#[cfg(feature = "rotation")]
+#[derive(Clone, Copy)]
+struct FileRotation {
+    max_bytes: Option<u64>,
+    every: Option<Partition>,
+    retention: Option<RetentionPolicy>,
+}
+
+/// How the files given to `file` are opened
+struct FileTargets {
+    options: FileSinkOptions,
+    #[cfg(feature = "rotation")]
+    rotation: Option<FileRotation>,
+}
+
+impl FileTargets {
+    async fn open<T>(
+        &self,
+        targets: Vec<Target<T>>,
+    ) -> Result<Option<Box<dyn OutputSink<T>>>, StdoutChannelError> {
+        let path = match targets.into_iter().next() {
+            None => return Ok(None),
+            Some(Target::Sink(sink)) => return Ok(Some(sink)),
+            Some(Target::File(path)) => path,
+        };
+        #[cfg(feature = "rotation")]
+        if let Some(rotation) = self.rotation {
+            let mut sink = RotatingFileSink::new(path).with_options(self.options);
+            if let Some(max_bytes) = rotation.max_bytes {
+                sink = sink.max_bytes(max_bytes);
+            }
+            if let Some(every) = rotation.every {
+                sink = sink.every(every);
+            }
+            if let Some(retention) = rotation.retention {
+                sink = sink.with_retention(retention);
+            }
+            return Ok(Some(Box::new(sink)));
+        }
+        Ok(Some(Box::new(self.options.open(path).await?)))
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    #[must_use]
+    pub fn builder() -> StdoutChannelBuilder<T> {
+        StdoutChannelBuilder::new()
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Build a channel configured by the `STDOUT_CHANNEL_*` environment
+    /// variables, see `StdoutChannelBuilder::from_env`
+    /// # Errors
+    ///
+    /// Will error if a variable has an invalid value or the log file can't be
+    /// opened
+    pub async fn from_env() -> Result<Self, StdoutChannelError> {
+        StdoutChannelBuilder::new().from_env().build().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        config::{EnvConfig, ENV_COLOR},
+        sink::Stream,
+        Clock, ColorMode, JsonFields, ManualClock, MockStdout, OutputConfig, OutputFormat,
+        RateLimiter, StdoutChannel, StdoutChannelError, TimeZone, TimestampFormat,
+    };
+
+    use super::{ConfigProblem, StdoutChannelBuilder};
+
+    #[cfg(feature = "rotation")]
+    #[tokio::test]
+    async fn test_rotate() -> Result<(), StdoutChannelError> {
+        use crate::RetentionPolicy;
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("rotated.log");
+        let chan = StdoutChannel::<String>::builder()
+            .file(Stream::Stdout, &path)
+            .rotate(Some(4), None, Some(RetentionPolicy::new().max_files(1)))
+            .build()
+            .await?;
+        for line in ["one", "two", "three"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+        assert_eq!(tokio::fs::read_to_string(&path).await?, "three\n");
+        let rotated = tmp.path().join("rotated.log.2");
+        assert_eq!(tokio::fs::read_to_string(rotated).await?, "two\n");
+        assert!(!tokio::fs::try_exists(tmp.path().join("rotated.log.1")).await?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_keeps_builder_settings() {
+        let builder = StdoutChannelBuilder::<String>::new()
+            .color(ColorMode::Always)
+            .format(OutputFormat::Logfmt)
+            .verbosity(2)
+            .time_zone(TimeZone::Fixed(3600));
+        let expected = builder.config;
+        // none of the variables set
+        let builder = builder.env(EnvConfig::from_lookup(|_| None));
+        assert_eq!(builder.config, expected);
+
+        let builder = builder.env(EnvConfig::from_lookup(|name| {
+            (name == ENV_COLOR).then(|| "never".into())
+        }));
+        assert_eq!(
+            builder.config,
+            OutputConfig {
+                color: ColorMode::Never,
+                format: OutputFormat::Logfmt,
+                verbosity: 2,
+                json_fields: JsonFields::default(),
+                time_zone: TimeZone::Fixed(3600),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let missing = dir.join("no-such-dir").join("out.log");
+        let shared = dir.join("builder.log");
+        let builder = StdoutChannelBuilder::<String>::new()
+            .file(Stream::Stdout, &shared)
+            .file(Stream::Stderr, &shared)
+            .file(Stream::Stderr, &missing)
+            .file(Stream::Stderr, "")
+            .rate_limit(RateLimiter::new(10, 100), 0)
+            .capacity(0)
+            .flush_interval(Duration::ZERO);
+        let err = builder.validate().unwrap_err();
+        assert_eq!(
+            err.problems(),
+            [
+                ConfigProblem::ConflictingSinks(Stream::Stderr),
+                ConfigProblem::MissingDirectory(missing.parent().unwrap().to_path_buf()),
+                ConfigProblem::EmptyPath(Stream::Stderr),
+                ConfigProblem::SameFile(shared.clone()),
+                ConfigProblem::ZeroThreshold,
+                ConfigProblem::ZeroCapacity,
+                ConfigProblem::ZeroFlushInterval,
+            ]
+        );
+        assert!(err.to_string().starts_with(
+            "invalid channel configuration: more than one sink configured for stderr; "
+        ));
+        let result = builder.build().await;
+        assert!(matches!(result, Err(StdoutChannelError::ConfigError(e)) if e == err));
+
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::builder()
+            .file(Stream::Stdout, &shared)
+            .sink(Stream::Stderr, stderr.clone())
+            .capacity(16)
+            .ordered(true)
+            .build()
+            .await?;
+        assert_eq!(chan.capacity(), Some(16));
+        assert!(chan.is_ordered());
+        chan.send("to file");
+        chan.send_err("to mock");
+        chan.close().await?;
+        assert_eq!(tokio::fs::read_to_string(&shared).await?, "to file\n");
+        assert_eq!(stderr.snapshot(), ["to mock"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_options() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("builder-opts.log");
+        let transcript = MockStdout::<String>::new();
+        let chan = StdoutChannel::<String>::builder()
+            .file(Stream::Stdout, &path)
+            .mirror(Stream::Stdout, transcript.clone())
+            .buffer_size(1024)
+            .flush_interval(Duration::from_millis(10))
+            .line_terminator("\r\n")
+            .prefix("[w3] ")
+            .timestamp_format(TimestampFormat::Pattern("%H:%M:%S".into()))
+            .clock(Clock::Manual(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(1_704_164_645),
+            )))
+            .build()
+            .await?;
+        chan.send("a".to_string());
+        chan.send("b".to_string());
+        // written by the periodic flush, not by close
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            tokio::fs::read_to_string(&path).await?,
+            "03:04:05 [w3] a\r\n03:04:05 [w3] b\r\n"
+        );
+        chan.close().await?;
+        assert_eq!(transcript.snapshot(), ["a", "b"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{sync::Mutex, StdoutChannel};
+
+/// Lines sent with `send_at!` and `send_err_at!` by call site, shared by all
+/// clones of a channel
+#[derive(Default)]
+pub(crate) struct CallSites(Mutex<HashMap<&'static str, u64>>);
+
+impl CallSites {
+    fn record(&self, location: &'static str) {
+        *self.0.lock().entry(location).or_insert(0) += 1;
+    }
+}
+
+/// Lines sent from one place in the code, see `StdoutChannel::top_senders`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    /// `file:line` of the `send_at!` or `send_err_at!` call
+    pub location: &'static str,
+    pub lines: u64,
+}
+
+/// Send a line to stdout like `StdoutChannel::send`, counted for the
+/// `file:line` of the call in `StdoutChannel::top_senders`
+#[macro_export]
+macro_rules! send_at {
+    ($chan:expr, $item:expr) => {
+        $chan.send_from(concat!(file!(), ":", line!()), $item)
+    };
+}
+
+/// Send a line to stderr like `StdoutChannel::send_err`, counted for the
+/// `file:line` of the call in `StdoutChannel::top_senders`
+#[macro_export]
+macro_rules! send_err_at {
+    ($chan:expr, $item:expr) => {
+        $chan.send_err_from(concat!(file!(), ":", line!()), $item)
+    };
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Send to stdout, counting the line for `location`, usually called
+    /// through `send_at!`
+    pub fn send_from(&self, location: &'static str, item: impl Into<T>) {
+        self.stats.call_sites.record(location);
+        self.send(item);
+    }
+
+    /// Send to stderr, counting the line for `location`, usually called
+    /// through `send_err_at!`
+    pub fn send_err_from(&self, location: &'static str, item: impl Into<T>) {
+        self.stats.call_sites.record(location);
+        self.send_err(item);
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// The `n` call sites that sent the most lines with `send_at!` and
+    /// `send_err_at!`, most first. Lines sent with `send` aren't counted.
+    #[must_use]
+    pub fn top_senders(&self, n: usize) -> Vec<CallSite> {
+        let mut sites: Vec<_> = self
+            .stats
+            .call_sites
+            .0
+            .lock()
+            .iter()
+            .map(|(&location, &lines)| CallSite { location, lines })
+            .collect();
+        sites.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.location.cmp(b.location)));
+        sites.truncate(n);
+        sites
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_top_senders() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        for i in 0..3 {
+            send_at!(chan, format!("line {i}"));
+        }
+        send_err_at!(chan, "failed");
+        chan.send("not counted");
+
+        let top = chan.top_senders(10);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].lines, 3);
+        assert!(top[0].location.starts_with("src/call_site.rs:"));
+        assert_eq!(top[1].lines, 1);
+        assert_eq!(chan.clone().top_senders(1), top[..1]);
+        chan.close().await?;
+        assert_eq!(stdout.snapshot().len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fmt::{Display, Write},
+    io::IsTerminal,
+};
+
+use crate::{config::ColorMode, sink::Stream, StdoutChannel};
+
+const BAR_WIDTH: usize = 20;
+const BAR_EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn bar(count: u64, max: u64) -> String {
+    if max == 0 || count == 0 {
+        return String::new();
+    }
+    let eighths = (count as f64 / max as f64 * (BAR_WIDTH * 8) as f64).round() as usize;
+    let eighths = eighths.max(1);
+    let (full, rest) = (eighths / 8, eighths % 8);
+    let mut bar: String = std::iter::repeat_n('█', full).collect();
+    if rest > 0 {
+        bar.push(BAR_EIGHTHS[rest - 1]);
+    }
+    bar
+}
+
+/// Smallest and largest finite value
+fn range(series: &[f64]) -> Option<(f64, f64)> {
+    let finite = series.iter().copied().filter(|v| v.is_finite());
+    let min = finite.clone().fold(f64::INFINITY, f64::min);
+    let max = finite.fold(f64::NEG_INFINITY, f64::max);
+    (min <= max).then_some((min, max))
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn sparkline(series: &[f64]) -> String {
+    let (min, max) = range(series).unwrap_or((0.0, 0.0));
+    series
+        .iter()
+        .map(|&v| {
+            if !v.is_finite() {
+                ' '
+            } else if max > min {
+                SPARKS[((v - min) / (max - min) * 7.0).round() as usize]
+            } else {
+                SPARKS[3]
+            }
+        })
+        .collect()
+}
+
+impl<T> StdoutChannel<T> {
+    /// Whether stdout can show Unicode charts: the format is `Human` and
+    /// either `ColorMode::Always` is set or the channel writes to the process
+    /// stdout and that is a terminal
+    fn charts_enabled(&self) -> bool {
+        let config = self.output_config();
+        if !config.format.is_human() {
+            return false;
+        }
+        config.color == ColorMode::Always
+            || (self.stats.is_process_stream(Stream::Stdout) && std::io::stdout().is_terminal())
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Send `label` followed by a line with a bar for each bucket, e.g. a
+    /// latency distribution. Without a terminal a single `label: bucket=count
+    /// ...` line is sent instead.
+    pub fn send_histogram(&self, label: &str, buckets: &[(impl Display, u64)]) {
+        if !self.charts_enabled() {
+            let mut line = format!("{label}:");
+            for (bucket, count) in buckets {
+                write!(line, " {bucket}={count}").ok();
+            }
+            self.send(line);
+            return;
+        }
+        let names: Vec<_> = buckets.iter().map(|(b, _)| b.to_string()).collect();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+        let max = buckets.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        self.send(label.to_string());
+        for (name, (_, count)) in names.iter().zip(buckets) {
+            self.send(format!(
+                "  {name:<width$} {:<BAR_WIDTH$} {count}",
+                bar(*count, max)
+            ));
+        }
+    }
+
+    /// Send `label ▁▃▅█ (min .. max)`, or `label: v1 v2 ...` without a
+    /// terminal
+    pub fn send_sparkline(&self, label: &str, series: &[f64]) {
+        if !self.charts_enabled() {
+            let mut line = format!("{label}:");
+            for v in series {
+                write!(line, " {v}").ok();
+            }
+            self.send(line);
+            return;
+        }
+        match range(series) {
+            Some((min, max)) => {
+                self.send(format!("{label} {} ({min} .. {max})", sparkline(series)));
+            }
+            None => self.send(format!("{label} {}", sparkline(series))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColorMode, MockStdout, OutputConfig, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_charts() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let buckets = [("<1ms", 120), ("<10ms", 45), (">=10ms", 0)];
+        chan.send_histogram("latency", &buckets);
+        chan.send_sparkline("rps", &[1.0, 5.0, 9.0]);
+
+        let tty = chan.clone().with_output_config(OutputConfig {
+            color: ColorMode::Always,
+            ..OutputConfig::default()
+        });
+        tty.send_histogram("latency", &buckets);
+        tty.send_sparkline("rps", &[1.0, 5.0, 9.0, f64::NAN, 3.0]);
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [
+                "latency: <1ms=120 <10ms=45 >=10ms=0",
+                "rps: 1 5 9",
+                "latency",
+                "  <1ms   ████████████████████ 120",
+                "  <10ms  ███████▌             45",
+                "  >=10ms                      0",
+                "rps ▁▅█ ▃ (1 .. 9)",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Notify;
+
+use crate::{bounded::Slot, config::OutputConfig, sink::Stream, StdoutChannel};
+
+/// Number of lines a child channel has queued that haven't been written yet
+#[derive(Default)]
+struct Pending {
+    count: AtomicUsize,
+    written: Notify,
+}
+
+/// Held by a queued line until it has been written
+pub(crate) struct PendingGuard(Arc<Pending>);
+
+impl PendingGuard {
+    fn new(pending: &Arc<Pending>) -> Self {
+        pending.count.fetch_add(1, Ordering::AcqRel);
+        Self(Arc::clone(pending))
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.written.notify_waiters();
+        }
+    }
+}
+
+#[derive(Default)]
+struct ChildStats {
+    stdout_sent: AtomicU64,
+    stderr_sent: AtomicU64,
+    filtered: AtomicU64,
+}
+
+type LineFilter = dyn Fn(Stream, &str) -> bool + Send + Sync;
+
+/// A channel for one subcommand of a multi-command binary, created with
+/// `StdoutChannel::child`.
+///
+/// Lines go through the sinks of the parent, prefixed with `[name] ` by
+/// default. The child keeps its own filter and counts, and `close` only
+/// waits for the lines the child itself sent, the parent stays open.
+pub struct ChildChannel<T> {
+    parent: StdoutChannel<T>,
+    name: Arc<str>,
+    prefix: Arc<str>,
+    filter: Option<Arc<LineFilter>>,
+    stats: Arc<ChildStats>,
+    pending: Arc<Pending>,
+}
+
+impl<T> Clone for ChildChannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            parent: self.parent.clone(),
+            name: Arc::clone(&self.name),
+            prefix: Arc::clone(&self.prefix),
+            filter: self.filter.clone(),
+            stats: Arc::clone(&self.stats),
+            pending: Arc::clone(&self.pending),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Create a child channel sharing this channel's sinks and config
+    #[must_use]
+    pub fn child(&self, name: &str) -> ChildChannel<T> {
+        ChildChannel {
+            parent: self.clone(),
+            name: name.into(),
+            prefix: format!("[{name}] ").into(),
+            filter: None,
+            stats: Arc::default(),
+            pending: Arc::default(),
+        }
+    }
+}
+
+impl<T> ChildChannel<T> {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Replace the default `[name] ` prefix, an empty prefix sends lines
+    /// unchanged
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Only send lines for which `filter` returns true, it is given the line
+    /// without the prefix
+    #[must_use]
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(Stream, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Config of the parent channel
+    #[must_use]
+    pub fn output_config(&self) -> OutputConfig {
+        self.parent.output_config()
+    }
+
+    /// Lines this child sent to `stream`
+    #[must_use]
+    pub fn sent(&self, stream: Stream) -> u64 {
+        match stream {
+            Stream::Stdout => self.stats.stdout_sent.load(Ordering::Relaxed),
+            Stream::Stderr => self.stats.stderr_sent.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Lines dropped by the filter
+    #[must_use]
+    pub fn filtered(&self) -> u64 {
+        self.stats.filtered.load(Ordering::Relaxed)
+    }
+
+    /// Lines this child sent that haven't been written yet
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.count.load(Ordering::Acquire)
+    }
+
+    /// Wait until every line this child sent has been written, or the
+    /// parent channel has been closed
+    pub async fn close(&self) {
+        loop {
+            let written = self.pending.written.notified();
+            if self.pending() == 0 || self.parent.is_closed() {
+                return;
+            }
+            written.await;
+        }
+    }
+}
+
+impl<T> ChildChannel<T>
+where
+    T: From<String>,
+{
+    pub fn send(&self, item: impl Display) {
+        self.push(Stream::Stdout, item);
+    }
+
+    pub fn send_err(&self, item: impl Display) {
+        self.push(Stream::Stderr, item);
+    }
+
+    fn push(&self, stream: Stream, item: impl Display) {
+        let line = item.to_string();
+        if let Some(filter) = &self.filter {
+            if !filter(stream, &line) {
+                self.stats.filtered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        let line = if self.prefix.is_empty() {
+            line
+        } else {
+            format!("{}{line}", self.prefix)
+        };
+        match stream {
+            Stream::Stdout => self.stats.stdout_sent.fetch_add(1, Ordering::Relaxed),
+            Stream::Stderr => self.stats.stderr_sent.fetch_add(1, Ordering::Relaxed),
+        };
+        let slot = Slot::pending(PendingGuard::new(&self.pending));
+        self.parent.push_slot(stream, line.into(), slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sink::Stream, MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_child() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let build = chan.child("build");
+        let test = chan
+            .child("test")
+            .with_filter(|stream, line| stream == Stream::Stderr || !line.starts_with("ok"));
+
+        let guard = stdout.lock().await;
+        build.send("compiling");
+        test.send("ok 1");
+        test.send("not ok 2");
+        test.send_err("1 failure");
+        assert_eq!(build.pending(), 1);
+        let closing = tokio::spawn({
+            let build = build.clone();
+            async move { build.close().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!closing.is_finished());
+        drop(guard);
+        closing.await?;
+        assert_eq!(build.pending(), 0);
+
+        assert_eq!(test.sent(Stream::Stdout), 1);
+        assert_eq!(test.sent(Stream::Stderr), 1);
+        assert_eq!(test.filtered(), 1);
+        chan.send("done");
+        chan.close().await?;
+        test.close().await;
+        assert_eq!(
+            stdout.snapshot(),
+            ["[build] compiling", "[test] not ok 2", "done"]
+        );
+        assert_eq!(stderr.snapshot(), ["[test] 1 failure"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,584 @@
+use std::{
+    env,
+    fmt::Display,
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{sink::Stream, StdoutChannel};
+
+/// Severity of a `send_annotation` message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Error,
+    Warning,
+    Notice,
+}
+
+impl AnnotationLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Notice => "notice",
+        }
+    }
+}
+
+/// Renders the CI-specific lines used by `StdoutChannel::group` and
+/// `StdoutChannel::send_annotation`.
+///
+/// `CiEnvironment` implements this for the CI systems detected out of the
+/// box, implement it for your own type to support another one.
+pub trait CiAnnotator: Send + Sync {
+    /// Line opening a collapsible group
+    fn group_start(&self, title: &str) -> String;
+
+    /// Line closing the group opened with `title`, if the CI system needs one
+    fn group_end(&self, _title: &str) -> Option<String> {
+        None
+    }
+
+    /// Line reporting a problem at `file:line`
+    fn annotation(&self, level: AnnotationLevel, file: &str, line: usize, msg: &str) -> String;
+
+    /// Whether annotations are written to stderr instead of stdout
+    fn annotation_to_stderr(&self) -> bool {
+        false
+    }
+
+    /// Command creating the annotation out of band, for CI systems where a
+    /// log line isn't enough, and the body to feed to its stdin. It runs on
+    /// a background thread in addition to the `annotation` line.
+    fn annotation_command(
+        &self,
+        _level: AnnotationLevel,
+        _file: &str,
+        _line: usize,
+        _msg: &str,
+    ) -> Option<(Command, String)> {
+        None
+    }
+}
+
+/// The CI system the process is running under, used to pick the right
+/// folding markers and annotation syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CiEnvironment {
+    GithubActions,
+    GitLab,
+    AzurePipelines,
+    TeamCity,
+    /// Groups are `---` log sections. `send_annotation` appends to the build
+    /// annotation of its level with `buildkite-agent annotate` and also
+    /// writes a `file:line: level: msg` log line, errors expanding the group
+    /// they're in.
+    Buildkite,
+    Local,
+}
+
+impl CiEnvironment {
+    /// Detect the CI system from the environment variables each one sets
+    #[must_use]
+    pub fn detect() -> Self {
+        Self::detect_from(|key| env::var(key).ok())
+    }
+
+    fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("GITHUB_ACTIONS").as_deref() == Some("true") {
+            Self::GithubActions
+        } else if var("GITLAB_CI").is_some() {
+            Self::GitLab
+        } else if var("TF_BUILD").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+            Self::AzurePipelines
+        } else if var("TEAMCITY_VERSION").is_some() {
+            Self::TeamCity
+        } else if var("BUILDKITE").as_deref() == Some("true") {
+            Self::Buildkite
+        } else {
+            Self::Local
+        }
+    }
+}
+
+impl CiAnnotator for CiEnvironment {
+    fn group_start(&self, title: &str) -> String {
+        match self {
+            Self::GithubActions => format!("::group::{title}"),
+            Self::GitLab => format!(
+                "\x1b[0Ksection_start:{}:{}[collapsed=true]\r\x1b[0K{title}",
+                unix_time(),
+                section_name(title)
+            ),
+            Self::AzurePipelines => format!("##[group]{title}"),
+            Self::TeamCity => format!("##teamcity[blockOpened name='{}']", escape_teamcity(title)),
+            Self::Buildkite => format!("--- {title}"),
+            Self::Local => format!("==> {title}"),
+        }
+    }
+
+    fn group_end(&self, title: &str) -> Option<String> {
+        match self {
+            Self::GithubActions => Some("::endgroup::".into()),
+            Self::GitLab => Some(format!(
+                "\x1b[0Ksection_end:{}:{}\r\x1b[0K",
+                unix_time(),
+                section_name(title)
+            )),
+            Self::AzurePipelines => Some("##[endgroup]".into()),
+            Self::TeamCity => Some(format!(
+                "##teamcity[blockClosed name='{}']",
+                escape_teamcity(title)
+            )),
+            Self::Buildkite | Self::Local => None,
+        }
+    }
+
+    fn annotation(&self, level: AnnotationLevel, file: &str, line: usize, msg: &str) -> String {
+        match self {
+            Self::GithubActions => format!(
+                "::{} file={},line={line}::{}",
+                level.as_str(),
+                escape_property(file),
+                escape_data(msg)
+            ),
+            Self::TeamCity => {
+                let status = match level {
+                    AnnotationLevel::Error => "ERROR",
+                    AnnotationLevel::Warning => "WARNING",
+                    AnnotationLevel::Notice => "NORMAL",
+                };
+                format!(
+                    "##teamcity[message text='{}' status='{status}']",
+                    escape_teamcity(&format!("{file}:{line}: {msg}"))
+                )
+            }
+            // `^^^ +++` expands the enclosing group so errors aren't hidden
+            Self::Buildkite if level == AnnotationLevel::Error => {
+                format!("^^^ +++\n{file}:{line}: error: {msg}")
+            }
+            _ => format!("{file}:{line}: {}: {msg}", level.as_str()),
+        }
+    }
+
+    fn annotation_to_stderr(&self) -> bool {
+        matches!(self, Self::GitLab | Self::AzurePipelines | Self::Local)
+    }
+
+    fn annotation_command(
+        &self,
+        level: AnnotationLevel,
+        file: &str,
+        line: usize,
+        msg: &str,
+    ) -> Option<(Command, String)> {
+        if *self != Self::Buildkite {
+            return None;
+        }
+        let style = match level {
+            AnnotationLevel::Error => "error",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "info",
+        };
+        let mut command = Command::new("buildkite-agent");
+        command.args([
+            "annotate",
+            "--style",
+            style,
+            "--context",
+            &format!("stdout-channel-{}", level.as_str()),
+            "--append",
+        ]);
+        Some((command, format!("* `{file}:{line}`: {msg}\n")))
+    }
+}
+
+/// Run an `annotation_command`, feeding it `body`
+fn run_annotation_command(mut command: Command, body: &str) -> io::Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(status.to_string()))
+    }
+}
+
+/// Escape the message part of a workflow command
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a `key=value` property of a workflow command
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Escape a value of a `TeamCity` service message
+fn escape_teamcity(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// GitLab section names may only contain `[a-zA-Z0-9_.-]`
+fn section_name(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Scope guard returned by `StdoutChannel::group`, closes the group when
+/// dropped.
+pub struct GroupGuard<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    chan: StdoutChannel<T>,
+    annotator: Box<dyn CiAnnotator>,
+    title: String,
+}
+
+impl<T> Drop for GroupGuard<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(end) = self.annotator.group_end(&self.title) {
+            self.chan.send_quiet(Stream::Stdout, end);
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Start a collapsible output group using the markers of the detected CI
+    /// system (a plain `==> title` header when not running in CI). The group
+    /// ends when the returned guard is dropped.
+    #[must_use = "the group ends as soon as the guard is dropped"]
+    pub fn group(&self, title: &str) -> GroupGuard<T> {
+        self.group_with(CiEnvironment::detect(), title)
+    }
+
+    /// Start an output group using the markers of a specific CI system
+    #[must_use = "the group ends as soon as the guard is dropped"]
+    pub fn group_with(&self, annotator: impl CiAnnotator + 'static, title: &str) -> GroupGuard<T> {
+        self.send(annotator.group_start(title));
+        GroupGuard {
+            chan: self.clone(),
+            annotator: Box::new(annotator),
+            title: title.into(),
+        }
+    }
+
+    /// Report a problem at `file:line`. On GitHub Actions this emits an
+    /// `::error`/`::warning`/`::notice` workflow command so it shows up as an
+    /// inline annotation, `TeamCity` gets a service message, Buildkite a
+    /// build annotation created with `buildkite-agent annotate`, and a
+    /// `file:line: level: msg` line is written, to stdout on Buildkite and
+    /// to stderr on the others.
+    pub fn send_annotation(&self, level: AnnotationLevel, file: &str, line: usize, msg: &str) {
+        self.send_annotation_with(&CiEnvironment::detect(), level, file, line, msg);
+    }
+
+    /// Report a problem at `file:line` for a specific CI system
+    pub fn send_annotation_with(
+        &self,
+        annotator: &(impl CiAnnotator + ?Sized),
+        level: AnnotationLevel,
+        file: &str,
+        line: usize,
+        msg: &str,
+    ) {
+        let annotation = annotator.annotation(level, file, line, msg);
+        if annotator.annotation_to_stderr() {
+            self.send_err(annotation);
+        } else {
+            self.send(annotation);
+        }
+        if let Some((command, body)) = annotator.annotation_command(level, file, line, msg) {
+            let program = command.get_program().to_string_lossy().into_owned();
+            let chan = self.clone();
+            thread::spawn(move || {
+                if let Err(e) = run_annotation_command(command, &body) {
+                    chan.send_err(format!("{program} failed: {e}"));
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, process::Command, time::Duration};
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{AnnotationLevel, CiAnnotator, CiEnvironment};
+
+    #[test]
+    fn test_detect() {
+        let detect = |vars: &[(&str, &str)]| {
+            CiEnvironment::detect_from(|key| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| (*v).to_string())
+            })
+        };
+        assert_eq!(
+            detect(&[("GITHUB_ACTIONS", "true")]),
+            CiEnvironment::GithubActions
+        );
+        assert_eq!(detect(&[("GITLAB_CI", "true")]), CiEnvironment::GitLab);
+        assert_eq!(
+            detect(&[("TF_BUILD", "True")]),
+            CiEnvironment::AzurePipelines
+        );
+        assert_eq!(
+            detect(&[("TEAMCITY_VERSION", "2023.05")]),
+            CiEnvironment::TeamCity
+        );
+        assert_eq!(detect(&[("BUILDKITE", "true")]), CiEnvironment::Buildkite);
+        assert_eq!(detect(&[]), CiEnvironment::Local);
+    }
+
+    #[tokio::test]
+    async fn test_group() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+
+        {
+            let _group = chan.group_with(CiEnvironment::GithubActions, "Building crate X");
+            chan.send("compiling");
+        }
+        {
+            let _group = chan.group_with(CiEnvironment::Local, "Testing");
+            chan.send("running");
+        }
+        let gitlab = chan.group_with(CiEnvironment::GitLab, "Building crate X");
+        drop(gitlab);
+        chan.close().await?;
+
+        let stdout = stdout.lock().await;
+        assert_eq!(
+            &stdout[..5],
+            [
+                "::group::Building crate X",
+                "compiling",
+                "::endgroup::",
+                "==> Testing",
+                "running"
+            ]
+        );
+        assert!(stdout[5].contains("section_start:"));
+        assert!(stdout[5].contains(":building_crate_x[collapsed=true]\r\x1b[0KBuilding crate X"));
+        assert!(stdout[6].contains(":building_crate_x\r"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_annotation() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+
+        chan.send_annotation_with(
+            &CiEnvironment::GithubActions,
+            AnnotationLevel::Error,
+            "src/a,b.rs",
+            12,
+            "100% broken\nreally",
+        );
+        chan.send_annotation_with(
+            &CiEnvironment::Local,
+            AnnotationLevel::Warning,
+            "src/lib.rs",
+            3,
+            "unused variable",
+        );
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.lock().await.as_slice(),
+            ["::error file=src/a%2Cb.rs,line=12::100%25 broken%0Areally"]
+        );
+        assert_eq!(
+            stderr.lock().await.as_slice(),
+            ["src/lib.rs:3: warning: unused variable"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_teamcity_buildkite() {
+        assert_eq!(
+            CiEnvironment::TeamCity.annotation(AnnotationLevel::Error, "a.rs", 1, "it's [bad]"),
+            "##teamcity[message text='a.rs:1: it|'s |[bad|]' status='ERROR']"
+        );
+        assert_eq!(
+            CiEnvironment::TeamCity.group_end("Build").as_deref(),
+            Some("##teamcity[blockClosed name='Build']")
+        );
+        assert_eq!(CiEnvironment::Buildkite.group_start("Build"), "--- Build");
+        assert_eq!(
+            CiEnvironment::Buildkite.annotation(AnnotationLevel::Error, "a.rs", 1, "bad"),
+            "^^^ +++\na.rs:1: error: bad"
+        );
+
+        let (command, body) = CiEnvironment::Buildkite
+            .annotation_command(AnnotationLevel::Notice, "a.rs", 1, "fyi")
+            .unwrap();
+        assert_eq!(command.get_program(), "buildkite-agent");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "annotate",
+                "--style",
+                "info",
+                "--context",
+                "stdout-channel-notice",
+                "--append"
+            ]
+        );
+        assert_eq!(body, "* `a.rs:1`: fyi\n");
+        assert!(CiEnvironment::GithubActions
+            .annotation_command(AnnotationLevel::Error, "a.rs", 1, "bad")
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_annotation_command() -> Result<(), StdoutChannelError> {
+        struct Recorder(PathBuf);
+
+        impl CiAnnotator for Recorder {
+            fn group_start(&self, title: &str) -> String {
+                title.into()
+            }
+
+            fn annotation(
+                &self,
+                level: AnnotationLevel,
+                file: &str,
+                line: usize,
+                msg: &str,
+            ) -> String {
+                CiEnvironment::Buildkite.annotation(level, file, line, msg)
+            }
+
+            fn annotation_command(
+                &self,
+                level: AnnotationLevel,
+                file: &str,
+                line: usize,
+                msg: &str,
+            ) -> Option<(Command, String)> {
+                let (_, body) =
+                    CiEnvironment::Buildkite.annotation_command(level, file, line, msg)?;
+                let mut command = Command::new("sh");
+                command.arg("-c").arg("cat > \"$0\"").arg(&self.0);
+                Some((command, body))
+            }
+        }
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("annotation.md");
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send_annotation_with(
+            &Recorder(path.clone()),
+            AnnotationLevel::Warning,
+            "a.rs",
+            7,
+            "careful",
+        );
+        let mut body = String::new();
+        for _ in 0..100 {
+            body = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+            if !body.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        chan.close().await?;
+
+        assert_eq!(body, "* `a.rs:7`: careful\n");
+        assert_eq!(stdout.lock().await.as_slice(), ["a.rs:7: warning: careful"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_custom_annotator() -> Result<(), StdoutChannelError> {
+        struct Custom;
+
+        impl CiAnnotator for Custom {
+            fn group_start(&self, title: &str) -> String {
+                format!("[start {title}]")
+            }
+
+            fn group_end(&self, title: &str) -> Option<String> {
+                Some(format!("[end {title}]"))
+            }
+
+            fn annotation(
+                &self,
+                level: AnnotationLevel,
+                file: &str,
+                line: usize,
+                msg: &str,
+            ) -> String {
+                format!("[{level:?} {file}#{line}] {msg}")
+            }
+        }
+
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        {
+            let _group = chan.group_with(Custom, "lint");
+            chan.send_annotation_with(&Custom, AnnotationLevel::Notice, "x.rs", 2, "hi");
+        }
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.lock().await.as_slice(),
+            ["[start lint]", "[Notice x.rs#2] hi", "[end lint]"]
+        );
+        Ok(())
+    }
+}
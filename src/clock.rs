@@ -0,0 +1,386 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{sink::partitioned::utc_fields, StdoutChannel};
+
+const RFC3339: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+/// A point in time as read from a `Clock`. Wall-clock times come from
+/// `SystemTime`, which has no leap seconds, so they never show second 60.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timestamp {
+    /// Written in UTC like `2024-01-02T03:04:05Z`
+    Utc(SystemTime),
+    /// Written with an offset of that many seconds east of UTC like
+    /// `2024-01-02T05:04:05+02:00`
+    Offset(SystemTime, i32),
+    /// Written in seconds like `12.000345s`
+    Elapsed(Duration),
+    /// A counter without a unit, written as is
+    Ticks(u64),
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utc(_) | Self::Offset(..) => self.write_pattern(f, RFC3339),
+            Self::Elapsed(d) => write!(f, "{}.{:06}s", d.as_secs(), d.subsec_micros()),
+            Self::Ticks(ticks) => write!(f, "{ticks}"),
+        }
+    }
+}
+
+impl Timestamp {
+    /// Write a wall-clock time with a `TimestampFormat::Pattern`, other
+    /// timestamps as `Display` writes them
+    fn write_pattern(self, f: &mut impl fmt::Write, pattern: &str) -> fmt::Result {
+        let (time, offset) = match self {
+            Self::Utc(time) => (time, None),
+            Self::Offset(time, offset) => (time, Some(offset)),
+            timestamp => return write!(f, "{timestamp}"),
+        };
+        let shift = Duration::from_secs(u64::from(offset.unwrap_or(0).unsigned_abs()));
+        let local = if offset.unwrap_or(0) < 0 {
+            time.checked_sub(shift)
+        } else {
+            time.checked_add(shift)
+        };
+        let [year, month, day, hour, minute, second] = utc_fields(local.unwrap_or(time));
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                f.write_char(c)?;
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => write!(f, "{year:04}")?,
+                Some('m') => write!(f, "{month:02}")?,
+                Some('d') => write!(f, "{day:02}")?,
+                Some('H') => write!(f, "{hour:02}")?,
+                Some('M') => write!(f, "{minute:02}")?,
+                Some('S') => write!(f, "{second:02}")?,
+                Some('f') => write!(f, "{:06}", since_epoch.subsec_micros())?,
+                Some('s') => write!(f, "{}", since_epoch.as_secs())?,
+                Some('z') => match offset {
+                    None => f.write_char('Z')?,
+                    Some(offset) => {
+                        let sign = if offset < 0 { '-' } else { '+' };
+                        let minutes = offset.unsigned_abs() / 60;
+                        write!(f, "{sign}{:02}:{:02}", minutes / 60, minutes % 60)?;
+                    }
+                },
+                Some('%') | None => f.write_char('%')?,
+                Some(other) => write!(f, "%{other}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The same wall-clock time written in `zone`, other timestamps are
+    /// returned as they are
+    #[must_use]
+    pub fn in_zone(self, zone: TimeZone) -> Self {
+        match self {
+            Self::Utc(time) | Self::Offset(time, _) => match zone.offset_at(time) {
+                0 => Self::Utc(time),
+                offset => Self::Offset(time, offset),
+            },
+            timestamp => timestamp,
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    fn from(time: time::OffsetDateTime) -> Self {
+        Self::Offset(time.into(), time.offset().whole_seconds())
+    }
+}
+
+/// How timestamps are written
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimestampFormat {
+    /// RFC 3339 like `2024-01-02T03:04:05Z`, the `Display` of `Timestamp`
+    #[default]
+    Rfc3339,
+    /// A `strftime`-like pattern of `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`,
+    /// `%f` (microseconds), `%z` (`Z` or an offset like `+02:00`), `%s`
+    /// (Unix seconds) and `%%`, e.g. `"%H:%M:%S.%f"`. Only applies to
+    /// wall-clock timestamps, elapsed times and ticks are written as
+    /// `Display` writes them.
+    Pattern(String),
+}
+
+impl TimestampFormat {
+    #[must_use]
+    pub fn format(&self, timestamp: Timestamp) -> String {
+        match self {
+            Self::Rfc3339 => timestamp.to_string(),
+            Self::Pattern(pattern) => {
+                let mut out = String::new();
+                timestamp.write_pattern(&mut out, pattern).ok();
+                out
+            }
+        }
+    }
+}
+
+/// Time zone wall-clock timestamps are written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimeZone {
+    #[default]
+    Utc,
+    /// That many seconds east of UTC
+    Fixed(i32),
+    /// The zone of the system, looked up for each timestamp so daylight
+    /// saving changes are followed. UTC where it can't be looked up, which
+    /// is every platform without `tm_gmtoff`: Windows and the unixes other
+    /// than Linux, Android, Apple's and the BSDs.
+    ///
+    /// The lookup calls `localtime_r`, which reads the `TZ` variable:
+    /// changing the environment while another thread writes timestamps is a
+    /// data race in most C libraries, so set `TZ` before spawning threads.
+    System,
+}
+
+impl TimeZone {
+    /// Seconds east of UTC at `time`
+    #[must_use]
+    pub fn offset_at(self, time: SystemTime) -> i32 {
+        match self {
+            Self::Utc => 0,
+            Self::Fixed(offset) => offset,
+            Self::System => system_offset(time).unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::UtcOffset> for TimeZone {
+    fn from(offset: time::UtcOffset) -> Self {
+        Self::Fixed(offset.whole_seconds())
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+))]
+fn system_offset(time: SystemTime) -> Option<i32> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let secs = libc::time_t::try_from(secs).ok()?;
+    // SAFETY: `tm` is plain old data for which all-zero is valid
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid, `localtime_r` only writes to `tm`
+    if unsafe { libc::localtime_r(std::ptr::addr_of!(secs), std::ptr::addr_of_mut!(tm)) }.is_null()
+    {
+        return None;
+    }
+    i32::try_from(tm.tm_gmtoff).ok()
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+)))]
+fn system_offset(_time: SystemTime) -> Option<i32> {
+    None
+}
+
+/// Where the timestamps of `FramedSink` and `JsonChannel` lines come from
+#[derive(Clone, Default)]
+pub enum Clock {
+    /// The system's wall clock
+    #[default]
+    System,
+    /// Time elapsed since the given instant, for hosts without a real-time
+    /// clock
+    Monotonic(Instant),
+    /// A clock set by hand, e.g. for golden-file tests
+    Manual(ManualClock),
+    /// Any other source, e.g. a hardware tick counter
+    Custom(Arc<dyn Fn() -> Timestamp + Send + Sync>),
+}
+
+impl Clock {
+    /// Time elapsed from now on
+    #[must_use]
+    pub fn monotonic() -> Self {
+        Self::Monotonic(Instant::now())
+    }
+
+    pub fn custom(now: impl Fn() -> Timestamp + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(now))
+    }
+
+    #[must_use]
+    pub fn now(&self) -> Timestamp {
+        match self {
+            Self::System => Timestamp::Utc(SystemTime::now()),
+            Self::Monotonic(start) => Timestamp::Elapsed(start.elapsed()),
+            Self::Manual(clock) => Timestamp::Utc(clock.now()),
+            Self::Custom(now) => now(),
+        }
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::System => f.write_str("System"),
+            Self::Monotonic(start) => f.debug_tuple("Monotonic").field(start).finish(),
+            Self::Manual(clock) => f.debug_tuple("Manual").field(&clock.now()).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// A wall clock that only moves when told to, clones share the time
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    micros: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    #[must_use]
+    pub fn new(start: SystemTime) -> Self {
+        let clock = Self {
+            micros: Arc::default(),
+        };
+        clock.set(start);
+        clock
+    }
+
+    #[must_use]
+    pub fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+
+    /// Set the time, times before the Unix epoch are set to the epoch
+    pub fn set(&self, time: SystemTime) {
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros();
+        self.micros
+            .store(u64::try_from(micros).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let micros = u64::try_from(by.as_micros()).unwrap_or(u64::MAX);
+        self.micros.fetch_add(micros, Ordering::Relaxed);
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    #[must_use]
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    /// Read the timestamps of lines sent from this channel, and clones made
+    /// from it from now on, from `clock`. Sinks keep their own clock, see
+    /// `FramedSink::with_clock`.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant, UNIX_EPOCH},
+    };
+
+    use crate::{
+        sink::{file::FileSink, framed::FramedSink},
+        MockStdout, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::{Clock, ManualClock, TimeZone, Timestamp, TimestampFormat};
+
+    #[tokio::test]
+    async fn test_clock() -> Result<(), StdoutChannelError> {
+        let manual = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_704_164_645));
+        let clock = Clock::Manual(manual.clone());
+        assert_eq!(clock.now().to_string(), "2024-01-02T03:04:05Z");
+        manual.advance(Duration::from_secs(60));
+        assert_eq!(clock.now().to_string(), "2024-01-02T03:05:05Z");
+        assert_eq!(
+            clock
+                .now()
+                .in_zone(TimeZone::Fixed(-5 * 3600 - 1800))
+                .to_string(),
+            "2024-01-01T21:35:05-05:30"
+        );
+        assert_eq!(
+            clock.now().in_zone(TimeZone::Fixed(0)),
+            Timestamp::Utc(manual.now())
+        );
+        assert_eq!(
+            TimestampFormat::Pattern("[%d/%m %H:%M:%S.%f %z %s %%%q]".into())
+                .format(Timestamp::Offset(manual.now(), 3600)),
+            "[02/01 04:05:05.000000 +01:00 1704164705 %%q]"
+        );
+        #[cfg(feature = "time")]
+        assert_eq!(
+            Timestamp::from(
+                time::OffsetDateTime::from(manual.now())
+                    .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap())
+            )
+            .to_string(),
+            "2024-01-02T05:05:05+02:00"
+        );
+
+        let start = Instant::now();
+        let elapsed = Clock::Monotonic(start - Duration::from_millis(1500));
+        assert!(elapsed.now().to_string().starts_with("1.5"));
+        assert_eq!(
+            Timestamp::Elapsed(Duration::from_micros(12_000_345)).to_string(),
+            "12.000345s"
+        );
+
+        let counter = Arc::new(AtomicU64::new(7));
+        let ticks = {
+            let counter = Arc::clone(&counter);
+            Clock::custom(move || Timestamp::Ticks(counter.fetch_add(1, Ordering::Relaxed)))
+        };
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("clock.log");
+        let stdout = FramedSink::new(FileSink::open(&path).await?)
+            .with_timestamps(true)
+            .with_clock(ticks);
+        let chan = StdoutChannel::<&str>::with_sinks(stdout, MockStdout::new());
+        chan.send("a");
+        chan.send("b");
+        chan.close().await?;
+        assert_eq!(tokio::fs::read_to_string(&path).await?, "7 a\n8 b\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use std::{ffi::OsString, io::IsTerminal, path::PathBuf, str::FromStr, sync::Arc};
+
+use crate::{
+    builder::ConfigProblem,
+    clock::TimeZone,
+    format::{JsonFields, OutputFormat},
+    sink::Stream,
+    sync::Mutex,
+    StdoutChannel,
+};
+
+pub const ENV_COLOR: &str = "STDOUT_CHANNEL_COLOR";
+pub const ENV_FORMAT: &str = "STDOUT_CHANNEL_FORMAT";
+pub const ENV_JSON: &str = "STDOUT_CHANNEL_JSON";
+pub const ENV_LOG_FILE: &str = "STDOUT_CHANNEL_LOG_FILE";
+pub const ENV_ORDERED: &str = "STDOUT_CHANNEL_ORDERED";
+pub const ENV_VERBOSITY: &str = "STDOUT_CHANNEL_VERBOSITY";
+
+/// Whether output should be colored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color when writing to a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" | "on" => Ok(Self::Always),
+            "never" | "off" => Ok(Self::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Presentation settings applications can query from a channel, set with
+/// the builder or from `STDOUT_CHANNEL_*` variables
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputConfig {
+    pub color: ColorMode,
+    pub format: OutputFormat,
+    /// 0 is quiet, 1 normal, higher is more verbose
+    pub verbosity: u8,
+    /// Fields added to the lines of a `JsonChannel`
+    pub json_fields: JsonFields,
+    /// Zone the timestamps of those lines are written in
+    pub time_zone: TimeZone,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            color: ColorMode::Auto,
+            format: OutputFormat::Human,
+            verbosity: 1,
+            json_fields: JsonFields::default(),
+            time_zone: TimeZone::Utc,
+        }
+    }
+}
+
+impl OutputConfig {
+    /// Whether lines written to `stream` should be colored
+    #[must_use]
+    pub fn use_color(&self, stream: Stream) -> bool {
+        match self.color {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && match stream {
+                        Stream::Stdout => std::io::stdout().is_terminal(),
+                        Stream::Stderr => std::io::stderr().is_terminal(),
+                    }
+            }
+        }
+    }
+}
+
+/// Settings read from the `STDOUT_CHANNEL_*` variables, unset ones keep
+/// the builder's choice
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct EnvConfig {
+    pub(crate) color: Option<ColorMode>,
+    pub(crate) format: Option<OutputFormat>,
+    pub(crate) verbosity: Option<u8>,
+    pub(crate) log_file: Option<PathBuf>,
+    /// Whether to write both streams from one queue
+    pub(crate) ordered: Option<bool>,
+    pub(crate) problems: Vec<ConfigProblem>,
+}
+
+impl EnvConfig {
+    /// Override the settings of `config` whose variables are set
+    pub(crate) fn apply(&self, config: &mut OutputConfig) {
+        config.color = self.color.unwrap_or(config.color);
+        config.format = self.format.unwrap_or(config.format);
+        config.verbosity = self.verbosity.unwrap_or(config.verbosity);
+    }
+
+    pub(crate) fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var_os(name))
+    }
+
+    pub(crate) fn from_lookup(lookup: impl Fn(&str) -> Option<OsString>) -> Self {
+        let mut config = Self::default();
+        let mut get = |name: &'static str| {
+            let value = lookup(name)?;
+            match value.into_string() {
+                Ok(value) if !value.is_empty() => Some((name, value)),
+                Ok(_) => None,
+                Err(value) => {
+                    config
+                        .problems
+                        .push(invalid(name, &value.to_string_lossy()));
+                    None
+                }
+            }
+        };
+        let color = get(ENV_COLOR);
+        let format = get(ENV_FORMAT);
+        let json = get(ENV_JSON);
+        let verbosity = get(ENV_VERBOSITY);
+        let ordered = get(ENV_ORDERED);
+        config.log_file = lookup(ENV_LOG_FILE)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        if let Some((name, value)) = color {
+            match value.parse() {
+                Ok(color) => config.color = Some(color),
+                Err(()) => config.problems.push(invalid(name, &value)),
+            }
+        }
+        if let Some((name, value)) = format {
+            match value.parse() {
+                Ok(format) => config.format = Some(format),
+                Err(_) => config.problems.push(invalid(name, &value)),
+            }
+        }
+        if let Some((name, value)) = json {
+            match parse_bool(&value) {
+                Some(true) => config.format = Some(OutputFormat::Json),
+                Some(false) => {}
+                None => config.problems.push(invalid(name, &value)),
+            }
+        }
+        if let Some((name, value)) = verbosity {
+            match value.parse() {
+                Ok(verbosity) => config.verbosity = Some(verbosity),
+                Err(_) => config.problems.push(invalid(name, &value)),
+            }
+        }
+        if let Some((name, value)) = ordered {
+            config.ordered = parse_bool(&value);
+            if config.ordered.is_none() {
+                config.problems.push(invalid(name, &value));
+            }
+        }
+        config
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn invalid(name: &'static str, value: &str) -> ConfigProblem {
+    ConfigProblem::InvalidEnv {
+        name,
+        value: value.into(),
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    #[must_use]
+    pub fn output_config(&self) -> OutputConfig {
+        *self.config.lock()
+    }
+
+    #[must_use]
+    pub fn verbosity(&self) -> u8 {
+        self.config.lock().verbosity
+    }
+
+    /// Set the verbosity of this channel and every clone of it, e.g. from
+    /// `-q`/`-v` flags, see `send_level`
+    pub fn set_verbosity(&self, verbosity: u8) {
+        self.config.lock().verbosity = verbosity;
+    }
+
+    /// Use `config` for this channel and clones made from it from now on,
+    /// channels this one was cloned from keep theirs
+    #[must_use]
+    pub fn with_output_config(mut self, config: OutputConfig) -> Self {
+        self.config = Arc::new(Mutex::new(config));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+    use crate::{builder::ConfigProblem, sink::Stream};
+
+    use super::{ColorMode, EnvConfig, OutputConfig, OutputFormat};
+
+    #[test]
+    fn test_env_config() {
+        let env: HashMap<_, _> = [
+            ("STDOUT_CHANNEL_COLOR", "never"),
+            ("STDOUT_CHANNEL_JSON", "yes"),
+            ("STDOUT_CHANNEL_VERBOSITY", "3"),
+            ("STDOUT_CHANNEL_LOG_FILE", "/var/log/app.log"),
+            ("STDOUT_CHANNEL_ORDERED", "on"),
+        ]
+        .into();
+        let config = EnvConfig::from_lookup(|name| env.get(name).map(OsString::from));
+        assert_eq!(config.color, Some(ColorMode::Never));
+        assert_eq!(config.format, Some(OutputFormat::Json));
+        assert_eq!(config.verbosity, Some(3));
+        let mut output = OutputConfig::default();
+        config.apply(&mut output);
+        assert!(!output.use_color(Stream::Stdout));
+        assert_eq!(config.log_file, Some(PathBuf::from("/var/log/app.log")));
+        assert_eq!(config.ordered, Some(true));
+        assert!(config.problems.is_empty());
+
+        let env: HashMap<_, _> = [
+            ("STDOUT_CHANNEL_COLOR", "rainbow"),
+            ("STDOUT_CHANNEL_VERBOSITY", "loud"),
+            ("STDOUT_CHANNEL_ORDERED", "sometimes"),
+        ]
+        .into();
+        let config = EnvConfig::from_lookup(|name| env.get(name).map(OsString::from));
+        let mut output = OutputConfig {
+            verbosity: 2,
+            ..OutputConfig::default()
+        };
+        config.apply(&mut output);
+        assert_eq!(output.verbosity, 2);
+        assert_eq!(output.color, ColorMode::Auto);
+        assert_eq!(
+            config.problems,
+            [
+                ConfigProblem::InvalidEnv {
+                    name: "STDOUT_CHANNEL_COLOR",
+                    value: "rainbow".into()
+                },
+                ConfigProblem::InvalidEnv {
+                    name: "STDOUT_CHANNEL_VERBOSITY",
+                    value: "loud".into()
+                },
+                ConfigProblem::InvalidEnv {
+                    name: "STDOUT_CHANNEL_ORDERED",
+                    value: "sometimes".into()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    convert::TryFrom,
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::Poll,
+    time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+
+use crate::StdoutChannel;
+
+/// Settings the channel changes on its running writer tasks, shared by all
+/// clones of the channel
+#[derive(Default)]
+pub(crate) struct TaskControl {
+    cancelled: AtomicBool,
+    notify: Notify,
+    coalesce_micros: AtomicU64,
+}
+
+impl TaskControl {
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        self.notify.notify_waiters();
+    }
+
+    /// Run `fut` to completion, or return `None` once writes are cancelled
+    pub(crate) async fn run<F: Future>(&self, fut: F) -> Option<F::Output> {
+        let mut cancelled = pin!(self.notify.notified());
+        // register before checking, a cancel in between isn't missed
+        cancelled.as_mut().enable();
+        if self.cancelled.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut fut = pin!(fut);
+        poll_fn(|cx| {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            fut.as_mut().poll(cx).map(Some)
+        })
+        .await
+    }
+
+    pub(crate) fn set_coalesce_window(&self, window: Duration) {
+        let micros = u64::try_from(window.as_micros()).unwrap_or(u64::MAX);
+        self.coalesce_micros.store(micros, Ordering::Relaxed);
+    }
+
+    /// When a batch started now may wait for more lines until, `None` if
+    /// lines are written as soon as the queue is empty
+    pub(crate) fn coalesce_deadline(&self) -> Option<Instant> {
+        match self.coalesce_micros.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Instant::now() + Duration::from_micros(micros)),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Cancel the write each writer task is in, e.g. one stuck on a pipe
+    /// nobody reads, and every later one. The tasks stop with
+    /// `StdoutChannelError::WriteCancelled`, which `close` returns, and
+    /// lines still queued are never written.
+    ///
+    /// `close_with_timeout` does this once the timeout expires.
+    pub fn cancel_writes(&self) {
+        self.control.cancel();
+    }
+
+    /// Once the queue is empty, wait up to `window` (e.g. 1-5 ms) for
+    /// more lines before writing a batch to the process stdout or stderr,
+    /// trading that much latency for fewer syscalls under heavy logging.
+    /// Lines are batched without a window too, but only those already
+    /// queued. Sinks buffer writes themselves and aren't affected.
+    #[must_use]
+    pub fn with_coalesce_window(self, window: Duration) -> Self {
+        self.control.set_coalesce_window(window);
+        self
+    }
+}
@@ -0,0 +1,34 @@
+use std::borrow::Cow;
+
+use crate::StdoutChannel;
+
+/// Message type that keeps static literals borrowed while still accepting
+/// owned `String`s, so `send("literal")` doesn't allocate
+pub type CowStr = Cow<'static, str>;
+
+pub type CowChannel = StdoutChannel<CowStr>;
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError, TapWriter};
+
+    use super::CowStr;
+
+    #[tokio::test]
+    async fn test_cow_channel() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<CowStr>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send("literal");
+        chan.send(format!("formatted {}", 1));
+        TapWriter::new(&chan).finish();
+        chan.close().await?;
+
+        let lines = stdout.lock().await;
+        assert!(matches!(lines[0], Cow::Borrowed("literal")));
+        assert!(matches!(&lines[1], Cow::Owned(s) if s == "formatted 1"));
+        assert_eq!(lines[2..], ["TAP version 14", "1..0"]);
+        Ok(())
+    }
+}
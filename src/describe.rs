@@ -0,0 +1,340 @@
+use std::{
+    any::type_name,
+    fmt::{self, Display},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    call_site::CallSites,
+    sink::{OutputSink, Stream},
+    StdoutChannel, StdoutChannelError, WriterTask,
+};
+
+/// Which sinks a channel was created with and how many lines went through
+/// each stream, shared by all clones of the channel
+pub(crate) struct ChannelStats {
+    stdout_sink: &'static str,
+    stderr_sink: &'static str,
+    /// Whether each stream is written to the process stdout or stderr,
+    /// indexed by stream
+    process_streams: [bool; 2],
+    stdout_sent: AtomicU64,
+    stderr_sent: AtomicU64,
+    /// Lines and bytes the writer tasks have written, indexed by stream
+    lines_written: [AtomicU64; 2],
+    bytes_written: [AtomicU64; 2],
+    /// Lines `send_level` didn't send because of the verbosity
+    lines_filtered: [AtomicU64; 2],
+    pub(crate) call_sites: CallSites,
+}
+
+impl ChannelStats {
+    pub(crate) fn new(stdout_sink: &'static str, stderr_sink: &'static str) -> Self {
+        Self {
+            stdout_sink,
+            stderr_sink,
+            process_streams: [false; 2],
+            stdout_sent: AtomicU64::new(0),
+            stderr_sent: AtomicU64::new(0),
+            lines_written: [AtomicU64::new(0), AtomicU64::new(0)],
+            bytes_written: [AtomicU64::new(0), AtomicU64::new(0)],
+            lines_filtered: [AtomicU64::new(0), AtomicU64::new(0)],
+            call_sites: CallSites::default(),
+        }
+    }
+
+    pub(crate) fn with_types<O, E>() -> Self {
+        Self::new(type_name::<O>(), type_name::<E>())
+    }
+
+    /// Stats of a channel writing to `stdout_sink` and `stderr_sink`
+    pub(crate) fn for_sinks<T, O, E>(stdout_sink: &O, stderr_sink: &E) -> Self
+    where
+        O: OutputSink<T>,
+        E: OutputSink<T>,
+    {
+        Self::with_types::<O, E>().with_process_streams([
+            stdout_sink.is_process_stream(),
+            stderr_sink.is_process_stream(),
+        ])
+    }
+
+    /// Mark which streams are written to the process stdout and stderr
+    pub(crate) fn with_process_streams(mut self, process_streams: [bool; 2]) -> Self {
+        self.process_streams = process_streams;
+        self
+    }
+
+    /// Whether `stream` is written to the process stdout or stderr rather
+    /// than a sink or mock
+    pub(crate) fn is_process_stream(&self, stream: Stream) -> bool {
+        self.process_streams[stream as usize]
+    }
+
+    pub(crate) fn sent_stdout(&self) {
+        self.stdout_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sent_stderr(&self) {
+        self.stderr_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Lines sent to both streams
+    pub(crate) fn sent(&self) -> u64 {
+        self.stdout_sent.load(Ordering::Relaxed) + self.stderr_sent.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn written(&self, stream: Stream, lines: usize, bytes: usize) {
+        let i = stream as usize;
+        self.lines_written[i].fetch_add(lines as u64, Ordering::Relaxed);
+        self.bytes_written[i].fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn filtered(&self, stream: Stream) {
+        self.lines_filtered[stream as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// State of one stream of a channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StreamDescription {
+    /// `stdout`, `stderr` or the type name of the sink or mock store
+    pub sink: &'static str,
+    pub sent: u64,
+    pub queued: usize,
+    /// Lines discarded by the overflow policy of a bounded channel
+    pub dropped: u64,
+    pub closed: bool,
+}
+
+/// Configuration and live stats of a `StdoutChannel`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChannelDescription {
+    pub stdout: StreamDescription,
+    pub stderr: StreamDescription,
+    /// Backpressure threshold set with `with_rate_limit`
+    pub pacing_threshold: Option<usize>,
+    pub close_reports: usize,
+}
+
+/// What one stream of a channel wrote before it was closed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StreamStats {
+    pub lines_written: u64,
+    /// Rendered bytes including newlines, always 0 for mock stores which
+    /// are given the items unrendered
+    pub bytes_written: u64,
+    /// Lines discarded by the overflow policy of a bounded channel
+    pub lines_dropped: u64,
+    /// Lines `send_level` left out because of the verbosity
+    pub lines_filtered: u64,
+}
+
+/// Returned by `StdoutChannel::close_with_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CloseStats {
+    pub stdout: StreamStats,
+    pub stderr: StreamStats,
+}
+
+impl fmt::Display for StreamStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lines, {} bytes written",
+            self.lines_written, self.bytes_written
+        )?;
+        if self.lines_dropped > 0 {
+            write!(f, ", {} dropped", self.lines_dropped)?;
+        }
+        if self.lines_filtered > 0 {
+            write!(f, ", {} filtered", self.lines_filtered)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CloseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stdout: {}, stderr: {}", self.stdout, self.stderr)
+    }
+}
+
+impl fmt::Display for StreamDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} sent, {} queued",
+            self.sink, self.sent, self.queued
+        )?;
+        if self.dropped > 0 {
+            write!(f, ", {} dropped", self.dropped)?;
+        }
+        f.write_str(if self.closed { ", closed)" } else { ")" })
+    }
+}
+
+impl fmt::Display for ChannelDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stdout: {}, stderr: {}", self.stdout, self.stderr)?;
+        if let Some(threshold) = self.pacing_threshold {
+            write!(f, ", paced above {threshold} queued")?;
+        }
+        if self.close_reports > 0 {
+            write!(f, ", {} close reports", self.close_reports)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    #[must_use]
+    pub fn describe(&self) -> ChannelDescription {
+        // ordered channels share one task between both streams
+        let stdout_closed = matches!(*self.stdout_task.lock(), WriterTask::Taken);
+        let stderr_closed = matches!(*self.stderr_task.lock(), WriterTask::Taken);
+        ChannelDescription {
+            stdout: StreamDescription {
+                sink: self.stats.stdout_sink,
+                sent: self.stats.stdout_sent.load(Ordering::Relaxed),
+                queued: self.stdout_queue.len(),
+                dropped: self.dropped(Stream::Stdout),
+                closed: stdout_closed,
+            },
+            stderr: StreamDescription {
+                sink: self.stats.stderr_sink,
+                sent: self.stats.stderr_sent.load(Ordering::Relaxed),
+                queued: self.stderr_queue.len(),
+                dropped: self.dropped(Stream::Stderr),
+                closed: stderr_closed,
+            },
+            pacing_threshold: self.pacing.as_ref().map(|p| p.threshold),
+            close_reports: self.close_reports.lock().len(),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Same as `close`, returning what each stream wrote. Lines are counted
+    /// once the writer or sink accepted them, quarantined lines aren't.
+    ///
+    /// # Errors
+    /// Returns the same errors as `close`
+    pub async fn close_with_stats(&self) -> Result<CloseStats, StdoutChannelError> {
+        self.close().await?;
+        Ok(CloseStats {
+            stdout: self.stream_stats(Stream::Stdout),
+            stderr: self.stream_stats(Stream::Stderr),
+        })
+    }
+
+    fn stream_stats(&self, stream: Stream) -> StreamStats {
+        let i = stream as usize;
+        StreamStats {
+            lines_written: self.stats.lines_written[i].load(Ordering::Relaxed),
+            bytes_written: self.stats.bytes_written[i].load(Ordering::Relaxed),
+            lines_dropped: self.dropped(stream),
+            lines_filtered: self.stats.lines_filtered[i].load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T> fmt::Debug for StdoutChannel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = self.describe();
+        f.debug_struct("StdoutChannel")
+            .field("stdout", &description.stdout)
+            .field("stderr", &description.stderr)
+            .field("pacing_threshold", &description.pacing_threshold)
+            .field("close_reports", &description.close_reports)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FileStore, MockStdout, OverflowPolicy, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_describe() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("describe.log");
+        let chan = StdoutChannel::<String>::with_mock_stdout(
+            MockStdout::new(),
+            MockStdout::with_store(FileStore::create(&path)?),
+        );
+        chan.send("a");
+        chan.send("b");
+        chan.send_err("c");
+        chan.add_close_report(|| "done".into());
+
+        let description = chan.describe();
+        assert_eq!(
+            description.stdout.sink,
+            "alloc::vec::Vec<alloc::string::String>"
+        );
+        assert_eq!(description.stdout.sent, 2);
+        assert_eq!(description.stderr.sink, "stdout_channel::mock::FileStore");
+        assert_eq!(description.close_reports, 1);
+        assert!(format!("{chan:?}").contains("sent: 2"));
+
+        chan.close().await?;
+        let description = chan.describe();
+        assert_eq!(description.stderr.sent, 2);
+        assert_eq!(description.stdout.queued, 0);
+        assert!(description.stdout.closed);
+        assert!(description
+            .to_string()
+            .ends_with("2 sent, 0 queued, closed)"));
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_value(&description).unwrap()["stdout"]["sent"],
+            2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_with_stats() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_sinks(stdout.clone(), MockStdout::new())
+            .with_capacity(2)
+            .with_overflow(OverflowPolicy::DropNewest);
+        let guard = stdout.lock().await;
+        for line in ["a", "bc", "def", "ghij"] {
+            chan.try_send(line);
+        }
+        chan.send_err("e");
+        drop(guard);
+
+        let stats = chan.close_with_stats().await?;
+        assert_eq!(stats.stdout.lines_dropped, 2);
+        assert_eq!(stats.stdout.lines_written, 2);
+        assert_eq!(stats.stdout.bytes_written, 5);
+        assert_eq!(stats.stderr.lines_written, 1);
+        assert_eq!(
+            stats.to_string(),
+            "stdout: 2 lines, 5 bytes written, 2 dropped, stderr: 1 lines, 2 bytes written"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_process_stream() {
+        use crate::{sink::Stream, FramedSink, StdoutSink};
+
+        let framed = FramedSink::new(Box::new(StdoutSink::new()));
+        let chan = StdoutChannel::<String>::with_sinks(framed, MockStdout::new());
+        assert!(chan.stats.is_process_stream(Stream::Stdout));
+        assert!(!chan.stats.is_process_stream(Stream::Stderr));
+        let chan = StdoutChannel::<String>::with_mock_stdout(MockStdout::new(), MockStdout::new());
+        assert!(!chan.stats.is_process_stream(Stream::Stdout));
+    }
+}
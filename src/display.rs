@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use crate::StdoutChannel;
+
+/// A type erased message, formatted only once the writer task gets to it
+pub type DisplayBox = Box<dyn Display + Send>;
+
+/// A channel accepting any mix of `Display` types
+pub type DisplayChannel = StdoutChannel<DisplayBox>;
+
+impl StdoutChannel<DisplayBox> {
+    pub fn send_display(&self, item: impl Display + Send + 'static) {
+        self.send(Box::new(item) as DisplayBox);
+    }
+
+    pub fn send_err_display(&self, item: impl Display + Send + 'static) {
+        self.send_err(Box::new(item) as DisplayBox);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt, path::PathBuf};
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::DisplayBox;
+
+    struct Progress(usize, usize);
+
+    impl fmt::Display for Progress {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "[{}/{}]", self.0, self.1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_display() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<DisplayBox>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        chan.send_display("static str");
+        chan.send_display(Progress(3, 10));
+        chan.send_display(42_u64);
+        chan.send_err_display(PathBuf::from("/tmp/missing").display().to_string());
+        chan.close().await?;
+
+        let lines: Vec<_> = stdout
+            .lock()
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(lines, ["static str", "[3/10]", "42"]);
+        assert_eq!(stderr.lock().await[0].to_string(), "/tmp/missing");
+        Ok(())
+    }
+}
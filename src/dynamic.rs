@@ -0,0 +1,93 @@
+use std::{borrow::Cow, fmt};
+
+use crate::StdoutChannel;
+
+/// Message of a `DynStdoutChannel`, either text or raw bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DynMessage {
+    Text(Cow<'static, str>),
+    /// Written as UTF-8, invalid sequences are replaced by `U+FFFD`
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for DynMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(s) => f.write_str(s),
+            Self::Bytes(b) => f.write_str(&String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+impl From<&'static str> for DynMessage {
+    fn from(s: &'static str) -> Self {
+        Self::Text(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for DynMessage {
+    fn from(s: String) -> Self {
+        Self::Text(Cow::Owned(s))
+    }
+}
+
+impl From<Cow<'static, str>> for DynMessage {
+    fn from(s: Cow<'static, str>) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<Vec<u8>> for DynMessage {
+    fn from(b: Vec<u8>) -> Self {
+        Self::Bytes(b)
+    }
+}
+
+/// Channel type without a message type parameter, for storing in
+/// non-generic structs and trait objects
+pub type DynStdoutChannel = StdoutChannel<DynMessage>;
+
+impl StdoutChannel<DynMessage> {
+    pub fn send_bytes(&self, bytes: impl Into<Vec<u8>>) {
+        self.send(DynMessage::Bytes(bytes.into()));
+    }
+
+    pub fn send_err_bytes(&self, bytes: impl Into<Vec<u8>>) {
+        self.send_err(DynMessage::Bytes(bytes.into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{DynMessage, DynStdoutChannel};
+
+    struct App {
+        chan: DynStdoutChannel,
+    }
+
+    #[tokio::test]
+    async fn test_dyn_channel() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<DynMessage>::new();
+        let stderr = MockStdout::new();
+        let app = App {
+            chan: StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone()),
+        };
+        app.chan.send("literal");
+        app.chan.send(format!("count {}", 2));
+        app.chan.send_bytes(&b"raw \xff"[..]);
+        app.chan.send_err_bytes("oops");
+        app.chan.close().await?;
+
+        let lines: Vec<_> = stdout
+            .lock()
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(lines, ["literal", "count 2", "raw \u{fffd}"]);
+        assert_eq!(stderr.lock().await[0], DynMessage::Bytes(b"oops".to_vec()));
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    fmt::{self, Display, Write},
+    time::Duration,
+};
+
+use crate::{banner::json_string, OutputFormat, StdoutChannel};
+
+/// Value of an `Event` field
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl FieldValue {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Self::Str(s) => out.push_str(&json_string(s)),
+            Self::Float(f) if !f.is_finite() => out.push_str("null"),
+            value => {
+                write!(out, "{value}").ok();
+            }
+        }
+    }
+
+    fn write_text(&self, out: &mut String) {
+        match self {
+            Self::Str(s) if s.is_empty() || s.contains([' ', '=', '"', '\n']) => {
+                out.push_str(&json_string(s));
+            }
+            value => {
+                write!(out, "{value}").ok();
+            }
+        }
+    }
+
+    fn write_logfmt(&self, out: &mut String) {
+        match self {
+            Self::Str(s)
+                if s.is_empty()
+                    || s.contains(|c: char| {
+                        matches!(c, ' ' | '=' | '"' | '\\') || c.is_control()
+                    }) =>
+            {
+                out.push_str(&json_string(s));
+            }
+            value => {
+                write!(out, "{value}").ok();
+            }
+        }
+    }
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(s) => f.write_str(s),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::UInt(u) => write!(f, "{u}"),
+            Self::Float(x) => write!(f, "{x}"),
+            Self::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+macro_rules! field_value_from {
+    ($variant:ident, $as:ty, $($t:ty),*) => {
+        $(
+            impl From<$t> for FieldValue {
+                fn from(v: $t) -> Self {
+                    Self::$variant(<$as>::from(v))
+                }
+            }
+        )*
+    };
+}
+
+field_value_from!(Int, i64, i8, i16, i32, i64);
+field_value_from!(UInt, u64, u8, u16, u32, u64);
+field_value_from!(Float, f64, f32, f64);
+field_value_from!(Bool, bool, bool);
+field_value_from!(Str, String, &str, String);
+
+impl From<usize> for FieldValue {
+    fn from(v: usize) -> Self {
+        Self::UInt(v as u64)
+    }
+}
+
+/// `key` with the characters logfmt can't have in a key replaced by `_`
+fn logfmt_key(key: &str) -> String {
+    if key.is_empty() {
+        return "_".into();
+    }
+    key.chars()
+        .map(|c| {
+            if matches!(c, ' ' | '=' | '"') || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// One line describing a whole operation ("canonical log line"), started
+/// with `StdoutChannel::event` and sent with `emit`.
+///
+/// Written as `name key=value ...`, as `{"event":name,"key":value,...}`
+/// with a JSON format, as `event=name key=value ...` with
+/// `OutputFormat::Logfmt` or as a `name,value,...` row with
+/// `OutputFormat::Csv`.
+#[must_use = "an event is only sent by `emit`"]
+pub struct Event<'a, T> {
+    pub(crate) chan: &'a StdoutChannel<T>,
+    pub(crate) name: String,
+    pub(crate) fields: Vec<(String, FieldValue)>,
+}
+
+impl<T> Event<'_, T> {
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add a `duration_ms` field
+    pub fn duration(self, duration: Duration) -> Self {
+        self.field("duration_ms", duration.as_secs_f64() * 1000.0)
+    }
+
+    fn render(&self) -> String {
+        let mut line = String::new();
+        let format = self.chan.output_config().format;
+        if format == OutputFormat::Csv {
+            line.push_str(&csv_field(&self.name));
+            for (_, value) in &self.fields {
+                line.push(',');
+                line.push_str(&csv_field(&value.to_string()));
+            }
+        } else if format == OutputFormat::Logfmt {
+            line.push_str("event=");
+            FieldValue::Str(self.name.clone()).write_logfmt(&mut line);
+            for (key, value) in &self.fields {
+                write!(line, " {}=", logfmt_key(key)).ok();
+                value.write_logfmt(&mut line);
+            }
+        } else if format.is_json() {
+            line.push_str(r#"{"event":"#);
+            line.push_str(&json_string(&self.name));
+            for (key, value) in &self.fields {
+                write!(line, ",{}:", json_string(key)).ok();
+                value.write_json(&mut line);
+            }
+            line.push('}');
+        } else {
+            line.push_str(&self.name);
+            for (key, value) in &self.fields {
+                write!(line, " {key}=").ok();
+                value.write_text(&mut line);
+            }
+        }
+        line
+    }
+}
+
+impl<T> Event<'_, T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Send the event to stdout
+    pub fn emit(self) {
+        self.chan.send(self.render());
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    pub fn event(&self, name: impl Into<String>) -> Event<'_, T> {
+        Event {
+            chan: self,
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{MockStdout, OutputConfig, OutputFormat, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_event() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.event("sync")
+            .field("files", 12_usize)
+            .field("dest", "s3://bucket/my dir")
+            .field("ok", true)
+            .duration(Duration::from_millis(1500))
+            .emit();
+        let json = chan.clone().with_output_config(OutputConfig {
+            format: OutputFormat::Json,
+            ..OutputConfig::default()
+        });
+        json.event("sync")
+            .field("files", 12_usize)
+            .field("delta", -3)
+            .field("dest", "a\"b")
+            .field("rate", f64::NAN)
+            .emit();
+        json.set_format(OutputFormat::Csv);
+        json.event("sync")
+            .field("files", 1)
+            .field("dest", "a,b")
+            .emit();
+        json.set_format(OutputFormat::Logfmt);
+        json.event("sync")
+            .field("dest", "C:\\tmp")
+            .field("msg", "line\nbreak")
+            .field("bad key=", "")
+            .field("ok", true)
+            .emit();
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [
+                r#"sync files=12 dest="s3://bucket/my dir" ok=true duration_ms=1500"#,
+                r#"{"event":"sync","files":12,"delta":-3,"dest":"a\"b","rate":null}"#,
+                r#"sync,1,"a,b""#,
+                r#"event=sync dest="C:\\tmp" msg="line\nbreak" bad_key_="" ok=true"#,
+            ]
+        );
+        Ok(())
+    }
+}
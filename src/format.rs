@@ -0,0 +1,165 @@
+use std::{fmt, str::FromStr};
+
+use crate::StdoutChannel;
+
+/// Output format selected with e.g. an `--output-format` flag, consulted by
+/// the format-aware helpers (`banner`, `event`, `MetricsRecorder`, ...)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+    /// One JSON document per line
+    Ndjson,
+    Csv,
+    /// `key=value` pairs, one record per line
+    Logfmt,
+    /// Only what was explicitly asked for, no banners or progress
+    Quiet,
+}
+
+impl OutputFormat {
+    pub const ALL: [Self; 6] = [
+        Self::Human,
+        Self::Json,
+        Self::Ndjson,
+        Self::Csv,
+        Self::Logfmt,
+        Self::Quiet,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+            Self::Logfmt => "logfmt",
+            Self::Quiet => "quiet",
+        }
+    }
+
+    /// Json or Ndjson, every helper writes a single JSON document per line
+    /// for both
+    #[must_use]
+    pub fn is_json(self) -> bool {
+        matches!(self, Self::Json | Self::Ndjson)
+    }
+
+    /// Whether decorations meant for people (banners, charts) are shown
+    #[must_use]
+    pub fn is_human(self) -> bool {
+        self == Self::Human
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Fields a `JsonChannel` adds to each line next to those of the message
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct JsonFields {
+    /// `"timestamp"`, the time the line was sent, see `StdoutChannel::with_clock`
+    pub timestamp: bool,
+    /// `"stream"`, `stdout` or `stderr`
+    pub stream: bool,
+    /// `"level"`, for lines sent with `log_json`
+    pub level: bool,
+}
+
+/// Error parsing an `OutputFormat`
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+#[error("unknown output format {0:?}, expected one of human, json, ndjson, csv, logfmt, quiet")]
+pub struct ParseFormatError(String);
+
+impl FromStr for OutputFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "text" => Ok(Self::Human),
+            "jsonl" => Ok(Self::Ndjson),
+            _ => Self::ALL
+                .iter()
+                .copied()
+                .find(|f| f.as_str() == lower)
+                .ok_or_else(|| ParseFormatError(s.into())),
+        }
+    }
+}
+
+#[cfg(feature = "clap")]
+impl clap::ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Self::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        let value = clap::builder::PossibleValue::new(self.as_str());
+        Some(match self {
+            Self::Human => value.alias("text"),
+            Self::Ndjson => value.alias("jsonl"),
+            _ => value,
+        })
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Switch the output format of this channel and every clone of it
+    pub fn set_format(&self, format: OutputFormat) {
+        self.config.lock().format = format;
+    }
+
+    #[must_use]
+    pub fn format(&self) -> OutputFormat {
+        self.config.lock().format
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::OutputFormat;
+
+    #[tokio::test]
+    async fn test_set_format() -> Result<(), StdoutChannelError> {
+        assert_eq!("NDJSON".parse(), Ok(OutputFormat::Ndjson));
+        assert_eq!("text".parse(), Ok(OutputFormat::Human));
+        assert!("yaml".parse::<OutputFormat>().is_err());
+
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let cloned = chan.clone();
+        cloned.set_format("json".parse().unwrap());
+        assert_eq!(chan.format(), OutputFormat::Json);
+        chan.banner("svc", "1.0", "");
+        chan.set_format(OutputFormat::Quiet);
+        chan.banner("svc", "1.0", "");
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [r#"{"type":"banner","app":"svc","version":"1.0"}"#]
+        );
+        assert!(stderr.snapshot().is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn test_value_enum() {
+        use clap::ValueEnum;
+
+        assert_eq!(
+            OutputFormat::from_str("jsonl", false),
+            Ok(OutputFormat::Ndjson)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::{
+    fmt::Display,
+    future::poll_fn,
+    pin::{pin, Pin},
+    task::{Context, Poll},
+};
+
+use crate::{sink::Stream as OutputStream, StdoutChannel, StdoutChannelError};
+
+/// Items are sent to stdout, so a stream can be forwarded into the channel
+/// with `StreamExt::forward`. The queue is unbounded, the sink is always
+/// ready until the channel is closed and flushing it doesn't wait for
+/// lines to be written, `close` on the channel does. After `close` every
+/// call fails with `StdoutChannelError::Closed`.
+impl<T> Sink<T> for StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    type Error = StdoutChannelError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.open_or_closed())
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        if self.send_quiet(OutputStream::Stdout, item) {
+            Ok(())
+        } else {
+            Err(StdoutChannelError::Closed)
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.open_or_closed())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    fn open_or_closed(&self) -> Result<(), StdoutChannelError> {
+        if self.is_closed() {
+            Err(StdoutChannelError::Closed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Send every item of `stream` to stdout, returns the number of lines
+    /// sent once the stream ends or the channel is closed
+    pub async fn send_all_from_stream<S>(&self, stream: S) -> usize
+    where
+        S: Stream,
+        S::Item: Into<T>,
+    {
+        let mut stream = pin!(stream);
+        let mut lines = 0;
+        while let Some(item) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            if !self.send_quiet(OutputStream::Stdout, item) {
+                break;
+            }
+            lines += 1;
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{stream, SinkExt, StreamExt};
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_forward_stream() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let lines = chan
+            .send_all_from_stream(stream::iter(["first", "second"]))
+            .await;
+        assert_eq!(lines, 2);
+        stream::iter(3..5)
+            .map(|i| Ok(format!("line {i}")))
+            .forward(chan.clone())
+            .await?;
+        chan.close().await?;
+        let mut closed = chan.clone();
+        let late = SinkExt::send(&mut closed, "late".to_string()).await;
+        assert!(matches!(late, Err(StdoutChannelError::Closed)));
+
+        assert_eq!(stdout.snapshot(), ["first", "second", "line 3", "line 4"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::{PoisonError, RwLock};
+
+use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+static GLOBAL: RwLock<Option<StdoutChannel<String>>> = RwLock::new(None);
+
+/// The process wide default channel, created writing to the real stdout and
+/// stderr on first use. It can be created outside of a tokio runtime, lines
+/// stay queued until one is sent inside a runtime.
+#[must_use]
+pub fn global() -> StdoutChannel<String> {
+    if let Some(chan) = GLOBAL
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        return chan.clone();
+    }
+    GLOBAL
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(StdoutChannel::new)
+        .clone()
+}
+
+/// Replace the default channel, returning the previous one
+pub fn set_global(chan: StdoutChannel<String>) -> Option<StdoutChannel<String>> {
+    GLOBAL
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .replace(chan)
+}
+
+/// Redirect the default channel to a `MockStdout` until the returned guard
+/// is dropped or finished, e.g. to check what an example prints.
+///
+/// The redirection is process wide, tests capturing concurrently will see
+/// each other's output.
+#[must_use]
+pub fn capture_global() -> CaptureGuard {
+    let stdout = MockStdout::new();
+    let stderr = MockStdout::new();
+    let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+    let previous = set_global(chan.clone());
+    CaptureGuard {
+        chan,
+        stdout,
+        stderr,
+        previous,
+        restored: false,
+    }
+}
+
+/// Restores the previous default channel on drop, see `capture_global`
+pub struct CaptureGuard {
+    chan: StdoutChannel<String>,
+    stdout: MockStdout<String>,
+    stderr: MockStdout<String>,
+    previous: Option<StdoutChannel<String>>,
+    restored: bool,
+}
+
+impl CaptureGuard {
+    /// Restore the previous channel and return everything captured from
+    /// stdout and stderr
+    /// # Errors
+    ///
+    /// Will error if the capturing channel's tasks failed
+    pub async fn finish(mut self) -> Result<(Vec<String>, Vec<String>), StdoutChannelError> {
+        self.restore();
+        self.chan.close().await?;
+        let stdout = std::mem::take(&mut *self.stdout.lock().await);
+        let stderr = std::mem::take(&mut *self.stderr.lock().await);
+        Ok((stdout, stderr))
+    }
+
+    fn restore(&mut self) {
+        if !self.restored {
+            self.restored = true;
+            *GLOBAL.write().unwrap_or_else(PoisonError::into_inner) = self.previous.take();
+        }
+    }
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StdoutChannelError;
+
+    use super::{capture_global, global};
+
+    fn greet(name: &str) {
+        global().send(format!("hello {name}"));
+        global().send_err("greeted".to_string());
+    }
+
+    #[tokio::test]
+    async fn test_capture_global() -> Result<(), StdoutChannelError> {
+        let capture = capture_global();
+        greet("world");
+        let (stdout, stderr) = capture.finish().await?;
+        assert_eq!(stdout, ["hello world"]);
+        assert_eq!(stderr, ["greeted"]);
+
+        {
+            let _capture = capture_global();
+            greet("again");
+        }
+        Ok(())
+    }
+}
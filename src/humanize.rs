@@ -0,0 +1,131 @@
+use std::{fmt, time::Duration};
+
+const BYTE_UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Sizes and durations written the same way by every CLI on the channel,
+/// e.g. `format!("{} in {}", Humanize::bytes(n), Humanize::duration(d))`
+/// gives `1.2 MiB in 3.4s`.
+///
+/// The wrappers are right-aligned with a width like numbers are, so
+/// `{:>10}` and `{:10}` both line up columns, and a precision sets the
+/// number of decimals, 1 by default.
+pub struct Humanize;
+
+impl Humanize {
+    #[must_use]
+    pub fn bytes(bytes: u64) -> HumanBytes {
+        HumanBytes(bytes)
+    }
+
+    #[must_use]
+    pub fn duration(duration: Duration) -> HumanDuration {
+        HumanDuration(duration)
+    }
+}
+
+/// A size in binary units, `512 B`, `1.2 MiB`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanBytes(pub u64);
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(1);
+        if self.0 < 1024 {
+            return pad(f, &format!("{} B", self.0));
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        // compare what is written, 1023.96 KiB is written as 1.0 MiB
+        while unit + 1 < BYTE_UNITS.len()
+            && format!("{value:.decimals$}")
+                .parse::<f64>()
+                .is_ok_and(|written| written >= 1024.0)
+        {
+            value /= 1024.0;
+            unit += 1;
+        }
+        pad(f, &format!("{value:.decimals$} {}", BYTE_UNITS[unit]))
+    }
+}
+
+/// A duration in the largest unit that keeps it short, `850µs`, `12ms`,
+/// `3.4s`, `2m05s`, `1h02m`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.0;
+        let secs = d.as_secs();
+        let s = if d < Duration::from_millis(1) {
+            format!("{}µs", d.as_micros())
+        } else if d < Duration::from_secs(1) {
+            format!("{}ms", d.as_millis())
+        } else if secs < 60 {
+            let decimals = f.precision().unwrap_or(1);
+            format!("{:.decimals$}s", d.as_secs_f64())
+        } else if secs < 3600 {
+            format!("{}m{:02}s", secs / 60, secs % 60)
+        } else {
+            format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+        };
+        pad(f, &s)
+    }
+}
+
+/// Pad `s` to the width of `f`, right-aligned unless asked otherwise.
+/// `Formatter::pad` would cut `s` to the precision, used for decimals here.
+fn pad(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    let padding = f.width().unwrap_or(0).saturating_sub(s.chars().count());
+    let (before, after) = match f.align() {
+        Some(fmt::Alignment::Left) => (0, padding),
+        Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+        Some(fmt::Alignment::Right) | None => (padding, 0),
+    };
+    let fill = f.fill();
+    for _ in 0..before {
+        fmt::Write::write_char(f, fill)?;
+    }
+    f.write_str(s)?;
+    for _ in 0..after {
+        fmt::Write::write_char(f, fill)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Humanize;
+
+    #[test]
+    fn test_humanize() {
+        assert_eq!(Humanize::bytes(512).to_string(), "512 B");
+        assert_eq!(Humanize::bytes(1_258_291).to_string(), "1.2 MiB");
+        assert_eq!(Humanize::bytes(1_048_575).to_string(), "1.0 MiB");
+        assert_eq!(format!("{:.2}", Humanize::bytes(1536)), "1.50 KiB");
+        assert_eq!(Humanize::bytes(u64::MAX).to_string(), "16.0 EiB");
+
+        let d = Duration::from_millis;
+        assert_eq!(
+            Humanize::duration(Duration::from_micros(850)).to_string(),
+            "850µs"
+        );
+        assert_eq!(Humanize::duration(d(12)).to_string(), "12ms");
+        assert_eq!(Humanize::duration(d(3400)).to_string(), "3.4s");
+        assert_eq!(Humanize::duration(d(125_000)).to_string(), "2m05s");
+        assert_eq!(Humanize::duration(d(3_720_000)).to_string(), "1h02m");
+
+        assert_eq!(
+            format!(
+                "[{:>9}] [{:<9}] [{:*^8}]",
+                Humanize::bytes(1_258_291),
+                Humanize::duration(d(3400)),
+                Humanize::bytes(7)
+            ),
+            "[  1.2 MiB] [3.4s     ] [**7 B***]"
+        );
+    }
+}
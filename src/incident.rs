@@ -0,0 +1,142 @@
+use std::{
+    any::Any,
+    future::{poll_fn, Future},
+    panic::AssertUnwindSafe,
+    pin::pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::Poll,
+};
+
+use crate::{
+    quarantine::Quarantined, sink::Stream, sync::Mutex, StdoutChannel, StdoutChannelError,
+};
+
+type ErrorHook = dyn Fn(&StdoutChannelError) + Send + Sync;
+
+/// Panics caught in the writer tasks and lines they quarantined, shared by
+/// all clones of a channel
+pub(crate) struct Incidents<T> {
+    panics: AtomicU64,
+    hook: Mutex<Option<Arc<ErrorHook>>>,
+    pub(crate) quarantine: Mutex<Vec<Quarantined<T>>>,
+}
+
+impl<T> Default for Incidents<T> {
+    fn default() -> Self {
+        Self {
+            panics: AtomicU64::new(0),
+            hook: Mutex::default(),
+            quarantine: Mutex::default(),
+        }
+    }
+}
+
+impl<T> Incidents<T> {
+    pub(crate) fn record(&self, stream: Stream, panic: &(dyn Any + Send)) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+        let message = panic_message(panic);
+        self.report(&StdoutChannelError::TaskPanic { stream, message });
+    }
+
+    pub(crate) fn quarantine(&self, stream: Stream, item: T, reason: String) {
+        self.report(&StdoutChannelError::Quarantined {
+            stream,
+            reason: reason.clone(),
+        });
+        self.quarantine.lock().push(Quarantined {
+            stream,
+            item,
+            reason,
+        });
+    }
+
+    pub(crate) fn report(&self, error: &StdoutChannelError) {
+        let hook = self.hook.lock().clone();
+        if let Some(hook) = hook {
+            hook(error);
+        }
+    }
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".into())
+}
+
+/// Run `fut`, returning the payload if it panics
+pub(crate) async fn catch_unwind<F: Future>(fut: F) -> Result<F::Output, Box<dyn Any + Send>> {
+    let mut fut = pin!(fut);
+    poll_fn(
+        |cx| match std::panic::catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(panic) => Poll::Ready(Err(panic)),
+        },
+    )
+    .await
+}
+
+impl<T> StdoutChannel<T> {
+    /// Call `hook` with errors the writer tasks recover from: a
+    /// `StdoutChannelError::TaskPanic` for every caught panic and a
+    /// `StdoutChannelError::Quarantined` for every quarantined line. Set for
+    /// all clones of the channel, replacing any previous hook.
+    pub fn set_error_hook(&self, hook: impl Fn(&StdoutChannelError) + Send + Sync + 'static) {
+        *self.incidents.hook.lock() = Some(Arc::new(hook));
+    }
+
+    /// Number of panics caught in the writer tasks, e.g. in a sink or mock
+    /// store. The line being written is skipped and the task carries on with
+    /// the next one. Lines whose `Display` impl panics are quarantined
+    /// instead.
+    #[must_use]
+    pub fn panics(&self) -> u64 {
+        self.incidents.panics.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{sink::Stream, MockStdout, MockStore, StdoutChannel, StdoutChannelError};
+
+    /// Panics when asked to store `boom`
+    #[derive(Clone, Default)]
+    struct Fragile(Vec<String>);
+
+    impl MockStore<String> for Fragile {
+        fn push(&mut self, item: String) -> Result<(), StdoutChannelError> {
+            assert_ne!(item, "boom", "can't store {item}");
+            self.0.push(item);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panic_recovery() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::with_store(Fragile::default());
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        chan.set_error_hook({
+            let errors = Arc::clone(&errors);
+            move |e| errors.lock().unwrap().push(e.to_string())
+        });
+        for line in ["a", "boom", "b"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+        assert_eq!(stdout.snapshot().0, ["a", "b"]);
+        assert_eq!(chan.panics(), 1);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(&format!("{} writer task panicked", Stream::Stdout)));
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt::Display, mem, sync::Arc};
+
+use crate::{sink::Stream, sync::Mutex, StdoutChannel};
+
+/// How a `JobMux` combines the output of concurrent jobs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MuxMode {
+    /// Forward every line immediately, tagged with `[job] `
+    Interleaved,
+    /// Hold each job's output and write it as one block, under a
+    /// `---- job ----` header, once the job finishes
+    Grouped,
+}
+
+/// Hands out per-job handles that share one `StdoutChannel`, similar to the
+/// output modes of `cargo test` or GNU parallel.
+pub struct JobMux<T> {
+    chan: StdoutChannel<T>,
+    mode: MuxMode,
+    block_lock: Arc<Mutex<()>>,
+}
+
+impl<T> JobMux<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>, mode: MuxMode) -> Self {
+        Self {
+            chan: chan.clone(),
+            mode,
+            block_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> MuxMode {
+        self.mode
+    }
+
+    /// Create the handle for a new job
+    #[must_use]
+    pub fn job(&self, name: impl Into<String>) -> JobHandle<T> {
+        JobHandle {
+            name: name.into(),
+            chan: self.chan.clone(),
+            mode: self.mode,
+            block_lock: Arc::clone(&self.block_lock),
+            pending: Vec::new(),
+        }
+    }
+}
+
+enum JobLine<T> {
+    Stdout(T),
+    Stderr(T),
+}
+
+/// Output handle for a single job of a `JobMux`.
+///
+/// In `MuxMode::Grouped` the buffered output is written when the handle is
+/// finished or dropped, so blocks appear in completion order.
+pub struct JobHandle<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    name: String,
+    chan: StdoutChannel<T>,
+    mode: MuxMode,
+    block_lock: Arc<Mutex<()>>,
+    pending: Vec<JobLine<T>>,
+}
+
+impl<T> JobHandle<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn send(&mut self, item: impl Into<T>) {
+        match self.mode {
+            MuxMode::Interleaved => self.chan.send(self.tag(&item.into())),
+            MuxMode::Grouped => self.pending.push(JobLine::Stdout(item.into())),
+        }
+    }
+
+    pub fn send_err(&mut self, item: impl Into<T>) {
+        match self.mode {
+            MuxMode::Interleaved => self.chan.send_err(self.tag(&item.into())),
+            MuxMode::Grouped => self.pending.push(JobLine::Stderr(item.into())),
+        }
+    }
+
+    /// Mark the job as complete, writing out any grouped output
+    pub fn finish(self) {}
+
+    fn tag(&self, item: &T) -> T {
+        format!("[{}] {item}", self.name).into()
+    }
+
+    fn write_block(&mut self) {
+        let pending = mem::take(&mut self.pending);
+        let _guard = self.block_lock.lock();
+        // also run by `drop`, which may come after `close`
+        let header = format!("---- {} ----", self.name);
+        self.chan.send_quiet(Stream::Stdout, header);
+        for line in pending {
+            let (stream, item) = match line {
+                JobLine::Stdout(item) => (Stream::Stdout, item),
+                JobLine::Stderr(item) => (Stream::Stderr, item),
+            };
+            self.chan.send_quiet(stream, item);
+        }
+    }
+}
+
+impl<T> Drop for JobHandle<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn drop(&mut self) {
+        if self.mode == MuxMode::Grouped {
+            self.write_block();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{JobMux, MuxMode};
+
+    #[tokio::test]
+    async fn test_interleaved() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<StackString>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let mux = JobMux::new(&chan, MuxMode::Interleaved);
+
+        let mut a = mux.job("a");
+        let mut b = mux.job("b");
+        a.send("one");
+        b.send("two");
+        a.send_err("three");
+        a.finish();
+        b.finish();
+        chan.close().await?;
+
+        assert_eq!(stdout.lock().await.as_slice(), ["[a] one", "[b] two"]);
+        assert_eq!(stderr.lock().await.as_slice(), ["[a] three"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grouped() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<StackString>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let mux = JobMux::new(&chan, MuxMode::Grouped);
+
+        let mut a = mux.job("a");
+        let mut b = mux.job("b");
+        a.send("a1");
+        b.send("b1");
+        a.send("a2");
+        b.send_err("b failed");
+        b.finish();
+        a.finish();
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.lock().await.as_slice(),
+            ["---- b ----", "b1", "---- a ----", "a1", "a2"]
+        );
+        assert_eq!(stderr.lock().await.as_slice(), ["b failed"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+use crate::{
+    banner::json_string, Level, OutputConfig, OutputFormat, StdoutChannel, Stream, Timestamp,
+};
+
+/// A value written as one JSON object per line. The fields of an object
+/// are written after the `JsonFields` of the channel, any other value is
+/// written as the `"message"` field when there are any.
+///
+/// Values that fail to serialize are quarantined.
+pub struct JsonLine<T> {
+    value: T,
+    timestamp: Option<Timestamp>,
+    stream: Option<Stream>,
+    level: Option<Level>,
+}
+
+impl<T> JsonLine<T> {
+    /// A line without any extra fields
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            timestamp: None,
+            stream: None,
+            level: None,
+        }
+    }
+
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    #[must_use]
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> From<T> for JsonLine<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Serialize> fmt::Display for JsonLine<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = serde_json::to_value(&self.value).map_err(|_| fmt::Error)?;
+        let mut fields = Vec::new();
+        if let Some(timestamp) = self.timestamp {
+            fields.push(("timestamp", json_string(&timestamp.to_string())));
+        }
+        if let Some(stream) = self.stream {
+            fields.push(("stream", json_string(&stream.to_string())));
+        }
+        if let Some(level) = self.level {
+            fields.push(("level", json_string(level.as_str())));
+        }
+        if fields.is_empty() {
+            return write!(f, "{value}");
+        }
+        f.write_str("{")?;
+        for (i, (key, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "\"{key}\":{value}")?;
+        }
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    write!(f, ",{}:{value}", json_string(&key))?;
+                }
+            }
+            value => write!(f, ",\"message\":{value}")?,
+        }
+        f.write_str("}")
+    }
+}
+
+/// A channel writing `Serialize` values as JSON Lines
+pub type JsonChannel<T> = StdoutChannel<JsonLine<T>>;
+
+impl<T> StdoutChannel<JsonLine<T>>
+where
+    T: Serialize + Send + 'static,
+{
+    /// A channel to the process stdout and stderr with the `Ndjson` format,
+    /// set `OutputConfig::json_fields` with `with_output_config` or the
+    /// builder's `json_fields` to add fields
+    #[must_use]
+    pub fn new_json() -> Self {
+        Self::new().with_output_config(OutputConfig {
+            format: OutputFormat::Ndjson,
+            ..OutputConfig::default()
+        })
+    }
+
+    pub fn send_json(&self, value: T) {
+        self.send(self.json_line(value, Stream::Stdout, None));
+    }
+
+    pub fn send_err_json(&self, value: T) {
+        self.send_err(self.json_line(value, Stream::Stderr, None));
+    }
+
+    /// Send `value` with a `"level"` field, errors and warnings to stderr
+    pub fn log_json(&self, level: Level, value: T) {
+        match level {
+            Level::Error | Level::Warning => {
+                self.send_err(self.json_line(value, Stream::Stderr, Some(level)));
+            }
+            _ => self.send(self.json_line(value, Stream::Stdout, Some(level))),
+        }
+    }
+
+    fn json_line(&self, value: T, stream: Stream, level: Option<Level>) -> JsonLine<T> {
+        let config = self.output_config();
+        let fields = config.json_fields;
+        JsonLine {
+            value,
+            timestamp: fields
+                .timestamp
+                .then(|| self.clock.now().in_zone(config.time_zone)),
+            stream: fields.stream.then_some(stream),
+            level: level.filter(|_| fields.level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        JsonFields, Level, MockStdout, OutputConfig, StdoutChannel, StdoutChannelError, Stream,
+        Timestamp,
+    };
+
+    use super::JsonLine;
+
+    #[derive(Serialize)]
+    struct Request {
+        path: &'static str,
+        status: u16,
+    }
+
+    #[tokio::test]
+    async fn test_json_lines() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<JsonLine<Request>>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone())
+            .with_output_config(OutputConfig {
+                json_fields: JsonFields {
+                    stream: true,
+                    level: true,
+                    ..JsonFields::default()
+                },
+                ..OutputConfig::default()
+            });
+        chan.send(Request {
+            path: "/",
+            status: 200,
+        });
+        chan.send_json(Request {
+            path: "/a\"b",
+            status: 404,
+        });
+        chan.log_json(
+            Level::Error,
+            Request {
+                path: "/c",
+                status: 500,
+            },
+        );
+        chan.close().await?;
+        let lines: Vec<_> = stdout
+            .lock()
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"path":"/","status":200}"#,
+                r#"{"stream":"stdout","path":"/a\"b","status":404}"#,
+            ]
+        );
+        assert_eq!(
+            stderr.lock().await[0].to_string(),
+            r#"{"stream":"stderr","level":"error","path":"/c","status":500}"#
+        );
+
+        let line = JsonLine {
+            value: "started",
+            timestamp: Some(Timestamp::Utc(
+                UNIX_EPOCH + Duration::from_secs(1_704_164_645),
+            )),
+            stream: Some(Stream::Stdout),
+            level: None,
+        };
+        assert_eq!(
+            line.to_string(),
+            r#"{"timestamp":"2024-01-02T03:04:05Z","stream":"stdout","message":"started"}"#
+        );
+        Ok(())
+    }
+}
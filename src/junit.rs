@@ -0,0 +1,311 @@
+use std::{
+    fmt::{Display, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{sync::Mutex, StdoutChannel, StdoutChannelError};
+
+/// Result of a single test case
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    Failed { message: String, details: String },
+    Errored { message: String, details: String },
+    Skipped { message: String },
+}
+
+/// A single test result recorded into a `JUnitReport`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestCase {
+    pub classname: String,
+    pub name: String,
+    pub duration: Duration,
+    pub outcome: TestOutcome,
+    pub output: Option<String>,
+}
+
+impl TestCase {
+    #[must_use]
+    pub fn new(classname: impl Into<String>, name: impl Into<String>, duration: Duration) -> Self {
+        Self {
+            classname: classname.into(),
+            name: name.into(),
+            duration,
+            outcome: TestOutcome::Passed,
+            output: None,
+        }
+    }
+
+    #[must_use]
+    pub fn failed(mut self, message: impl Into<String>, details: impl Into<String>) -> Self {
+        self.outcome = TestOutcome::Failed {
+            message: message.into(),
+            details: details.into(),
+        };
+        self
+    }
+
+    #[must_use]
+    pub fn errored(mut self, message: impl Into<String>, details: impl Into<String>) -> Self {
+        self.outcome = TestOutcome::Errored {
+            message: message.into(),
+            details: details.into(),
+        };
+        self
+    }
+
+    #[must_use]
+    pub fn skipped(mut self, message: impl Into<String>) -> Self {
+        self.outcome = TestOutcome::Skipped {
+            message: message.into(),
+        };
+        self
+    }
+
+    /// Captured output, written as `<system-out>`
+    #[must_use]
+    pub fn with_output(mut self, output: impl Into<String>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+}
+
+/// Prints test results through a `StdoutChannel` as they are recorded and
+/// writes them all as a `JUnit` XML file.
+///
+/// The file is written when the channel is closed, with every case recorded
+/// until then. Call `close` to write it earlier, e.g. when the channel stays
+/// open until the process exits.
+pub struct JUnitReport<T> {
+    chan: StdoutChannel<T>,
+    suite: Arc<str>,
+    path: PathBuf,
+    cases: Arc<Mutex<Vec<TestCase>>>,
+}
+
+impl<T> JUnitReport<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// A report written to `path` when `chan` is closed
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>, suite: impl Into<String>, path: impl AsRef<Path>) -> Self {
+        let report = Self {
+            chan: chan.clone(),
+            suite: suite.into().into(),
+            path: path.as_ref().to_path_buf(),
+            cases: Arc::default(),
+        };
+        let (suite, path, cases) = (
+            Arc::clone(&report.suite),
+            report.path.clone(),
+            Arc::clone(&report.cases),
+        );
+        chan.add_close_hook(move || async move {
+            let xml = to_xml(&suite, &cases.lock());
+            tokio::fs::write(&path, xml).await?;
+            Ok(())
+        });
+        report
+    }
+
+    /// Print a console line for the test and keep it for the XML report
+    pub fn record(&self, case: TestCase) {
+        let name = format!("{}::{}", case.classname, case.name);
+        let secs = case.duration.as_secs_f64();
+        match &case.outcome {
+            TestOutcome::Passed => self.chan.send(format!("test {name} ... ok ({secs:.3}s)")),
+            TestOutcome::Skipped { message } => {
+                self.chan
+                    .send(format!("test {name} ... skipped: {message}"));
+            }
+            TestOutcome::Failed { message, .. } => {
+                self.chan
+                    .send(format!("test {name} ... FAILED ({secs:.3}s)"));
+                self.chan.send_err(format!("{name}: {message}"));
+            }
+            TestOutcome::Errored { message, .. } => {
+                self.chan
+                    .send(format!("test {name} ... ERROR ({secs:.3}s)"));
+                self.chan.send_err(format!("{name}: {message}"));
+            }
+        }
+        self.cases.lock().push(case);
+    }
+
+    /// Render the recorded results as a `JUnit` XML document
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        to_xml(&self.suite, &self.cases.lock())
+    }
+
+    /// Write the XML report to the configured path now, it's written again
+    /// when the channel is closed
+    /// # Errors
+    ///
+    /// Will error if the report file can't be written
+    pub async fn close(&self) -> Result<(), StdoutChannelError> {
+        tokio::fs::write(&self.path, self.to_xml()).await?;
+        Ok(())
+    }
+}
+
+/// The `JUnit` XML document of `cases`
+fn to_xml(suite: &str, cases: &[TestCase]) -> String {
+    let count = |f: fn(&TestOutcome) -> bool| cases.iter().filter(|c| f(&c.outcome)).count();
+    let failures = count(|o| matches!(o, TestOutcome::Failed { .. }));
+    let errors = count(|o| matches!(o, TestOutcome::Errored { .. }));
+    let skipped = count(|o| matches!(o, TestOutcome::Skipped { .. }));
+    let time: f64 = cases.iter().map(|c| c.duration.as_secs_f64()).sum();
+    let counts = format!(
+        r#"tests="{}" failures="{failures}" errors="{errors}" skipped="{skipped}" time="{time:.3}""#,
+        cases.len()
+    );
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(xml, "<testsuites {counts}>");
+    let _ = writeln!(xml, r#"  <testsuite name="{}" {counts}>"#, escape(suite));
+    for case in cases {
+        let _ = write!(
+            xml,
+            r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+            escape(&case.classname),
+            escape(&case.name),
+            case.duration.as_secs_f64()
+        );
+        if case.outcome == TestOutcome::Passed && case.output.is_none() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        match &case.outcome {
+            TestOutcome::Passed => {}
+            TestOutcome::Failed { message, details } => {
+                let _ = writeln!(
+                    xml,
+                    r#"      <failure message="{}">{}</failure>"#,
+                    escape(message),
+                    escape(details)
+                );
+            }
+            TestOutcome::Errored { message, details } => {
+                let _ = writeln!(
+                    xml,
+                    r#"      <error message="{}">{}</error>"#,
+                    escape(message),
+                    escape(details)
+                );
+            }
+            TestOutcome::Skipped { message } => {
+                let _ = writeln!(xml, r#"      <skipped message="{}"/>"#, escape(message));
+            }
+        }
+        if let Some(output) = &case.output {
+            let _ = writeln!(xml, "      <system-out>{}</system-out>", escape(output));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// `s` escaped for XML text and attributes, characters XML 1.0 doesn't
+/// allow (control characters other than tab and line breaks, `U+FFFE` and
+/// `U+FFFF`) are replaced with `U+FFFD`
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\0'..='\x1f' | '\u{fffe}' | '\u{ffff}' => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{escape, JUnitReport, TestCase};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("\x1b[31mred\x1b[0m\tok\r\n\0\u{ffff}"),
+            "\u{fffd}[31mred\u{fffd}[0m\tok\r\n\u{fffd}\u{fffd}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_junit_report() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("junit.xml");
+        let report = JUnitReport::new(&chan, "unit", &path);
+        report.record(TestCase::new("config", "parse", Duration::from_millis(120)));
+        report.record(
+            TestCase::new("config", "merge", Duration::from_millis(5))
+                .failed("left != right", "assertion failed: 1 < 0 && \"a\""),
+        );
+        report.record(TestCase::new("net", "fetch", Duration::ZERO).skipped("offline"));
+        report.close().await?;
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.lock().await.as_slice(),
+            [
+                "test config::parse ... ok (0.120s)",
+                "test config::merge ... FAILED (0.005s)",
+                "test net::fetch ... skipped: offline",
+            ]
+        );
+        assert_eq!(
+            stderr.lock().await.as_slice(),
+            ["config::merge: left != right"]
+        );
+
+        let xml = tokio::fs::read_to_string(&path).await?;
+        assert!(xml.contains(
+            r#"<testsuite name="unit" tests="3" failures="1" errors="0" skipped="1" time="0.125">"#
+        ));
+        assert!(xml.contains(r#"<testcase classname="config" name="parse" time="0.120"/>"#));
+        assert!(xml.contains(
+            r#"<failure message="left != right">assertion failed: 1 &lt; 0 &amp;&amp; &quot;a&quot;</failure>"#
+        ));
+        assert!(xml.contains(r#"<skipped message="offline"/>"#));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_written_on_channel_close() -> Result<(), StdoutChannelError> {
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::<String>::new(), MockStdout::new());
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("junit.xml");
+        let report = JUnitReport::new(&chan, "unit", &path);
+        report.record(TestCase::new("config", "parse", Duration::from_millis(1)));
+        report.close().await?;
+        report.record(TestCase::new("config", "merge", Duration::from_millis(1)));
+        drop(report);
+        chan.close().await?;
+
+        let xml = tokio::fs::read_to_string(&path).await?;
+        assert!(xml.contains(r#"<testsuite name="unit" tests="2" "#));
+        assert!(xml.contains(r#"name="merge""#));
+        Ok(())
+    }
+}
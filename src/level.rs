@@ -0,0 +1,83 @@
+use std::fmt;
+
+#[cfg(any(
+    feature = "gelf",
+    feature = "syslog",
+    all(feature = "eventlog", windows),
+    all(feature = "oslog", target_os = "macos"),
+    all(feature = "logcat", target_os = "android")
+))]
+use crate::sink::Stream;
+
+/// Severity of a message, most severe first
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl Level {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+
+    /// Severity of the level in syslog and GELF
+    #[must_use]
+    pub fn syslog_severity(self) -> u8 {
+        match self {
+            Self::Error => 3,
+            Self::Warning => 4,
+            Self::Info => 6,
+            Self::Debug => 7,
+        }
+    }
+
+    /// Level of a line written to `stream`: the one it starts with as in
+    /// `warning: ...`, else error for stderr and info for stdout
+    #[cfg(any(
+        feature = "gelf",
+        feature = "syslog",
+        all(feature = "eventlog", windows),
+        all(feature = "oslog", target_os = "macos"),
+        all(feature = "logcat", target_os = "android")
+    ))]
+    pub(crate) fn of_line(line: &str, stream: Stream) -> Self {
+        [Self::Error, Self::Warning, Self::Info, Self::Debug]
+            .iter()
+            .copied()
+            .find(|level| {
+                line.strip_prefix(level.as_str())
+                    .is_some_and(|rest| rest.starts_with(": "))
+            })
+            .unwrap_or(match stream {
+                Stream::Stdout => Self::Info,
+                Stream::Stderr => Self::Error,
+            })
+    }
+
+    /// Lowest verbosity at which `StdoutChannel::send_level` writes this
+    /// level, errors and warnings are always written
+    #[must_use]
+    pub fn min_verbosity(self) -> u8 {
+        match self {
+            Self::Error | Self::Warning => 0,
+            Self::Info => 1,
+            Self::Debug => 2,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -1,17 +1,173 @@
+pub mod aggregate;
+#[cfg(feature = "artifacts")]
+pub mod artifact;
+mod banner;
+pub mod bounded;
+pub mod bounded_line;
+pub mod builder;
+pub mod call_site;
+mod chart;
+pub mod child;
+pub mod ci;
+pub mod clock;
+pub mod config;
+mod control;
+pub mod cow;
+pub mod describe;
+pub mod display;
+pub mod dynamic;
+pub mod event;
+pub mod format;
+#[cfg(feature = "futures")]
+mod forward;
+pub mod global;
+pub mod humanize;
+mod incident;
+pub mod job_mux;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod junit;
+pub mod level;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod mock;
+mod null;
+mod ordered;
+pub mod print;
+#[cfg(feature = "process")]
+pub mod process;
+pub mod quarantine;
 pub mod rate_limiter;
+#[cfg(feature = "metrics")]
+pub mod recorder;
+#[cfg(feature = "ssh")]
+pub mod remote;
+#[cfg(feature = "sarif")]
+pub mod sarif;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod shutdown;
+pub mod siem;
+pub mod sink;
+mod summary;
+mod sync;
+pub mod tap;
+#[cfg(feature = "gelf")]
+mod zlib;
 
-pub use rate_limiter::RateLimiter;
+pub use aggregate::Aggregator;
+#[cfg(feature = "artifacts")]
+pub use artifact::ArtifactStore;
+pub use bounded::OverflowPolicy;
+pub use bounded_line::{BoundedLine, LineTooLong};
+pub use builder::{ConfigError, ConfigProblem, StdoutChannelBuilder};
+pub use call_site::CallSite;
+pub use child::ChildChannel;
+pub use ci::{AnnotationLevel, CiAnnotator, CiEnvironment, GroupGuard};
+pub use clock::{Clock, ManualClock, TimeZone, Timestamp, TimestampFormat};
+pub use config::{ColorMode, OutputConfig};
+pub use cow::{CowChannel, CowStr};
+pub use describe::{ChannelDescription, CloseStats, StreamDescription, StreamStats};
+pub use display::{DisplayBox, DisplayChannel};
+pub use dynamic::{DynMessage, DynStdoutChannel};
+pub use event::{Event, FieldValue};
+pub use format::{JsonFields, OutputFormat, ParseFormatError};
+pub use global::{capture_global, global, set_global, CaptureGuard};
+pub use humanize::{HumanBytes, HumanDuration, Humanize};
+pub use job_mux::{JobHandle, JobMux, MuxMode};
+#[cfg(feature = "serde")]
+pub use json::{JsonChannel, JsonLine};
+pub use junit::{JUnitReport, TestCase, TestOutcome};
+pub use level::Level;
+#[cfg(feature = "log")]
+pub use logger::StdoutChannelLogger;
+pub use mock::{FileStore, MockStore, RingStore};
+pub use print::ChannelWriter;
+#[cfg(feature = "process")]
+pub use process::PipedChild;
+pub use quarantine::Quarantined;
+#[cfg(feature = "redis")]
+pub use rate_limiter::redis_backend::RedisRateLimiter;
+#[cfg(feature = "tower")]
+pub use rate_limiter::tower::{RateLimitLayer, RateLimitService};
+pub use rate_limiter::{
+    backend::{BackendFuture, RateLimitBackend},
+    quota::{Quota, Usage},
+    HierarchicalRateLimiter, RateLimiter, RateLimiterState, RatePermit,
+};
+#[cfg(feature = "metrics")]
+pub use recorder::MetricsRecorder;
+#[cfg(feature = "ssh")]
+pub use remote::RemoteHost;
+#[cfg(feature = "sarif")]
+pub use sarif::{Diagnostic, Region, SarifReport};
+pub use shutdown::ShutdownHooks;
+pub use siem::{SiemDialect, SiemFormatter};
+#[cfg(feature = "clickhouse")]
+pub use sink::clickhouse::ClickHouseSink;
+#[cfg(all(feature = "eventlog", windows))]
+pub use sink::eventlog::EventLogSink;
+#[cfg(feature = "gelf")]
+pub use sink::gelf::{GelfFormatter, GelfSink, GelfTags};
+#[cfg(all(feature = "logcat", target_os = "android"))]
+pub use sink::logcat::LogcatSink;
+#[cfg(feature = "mmap")]
+pub use sink::mmap::MmapFileSink;
+#[cfg(feature = "oslog")]
+pub use sink::oslog::OsLogSink;
+#[cfg(feature = "rotation")]
+pub use sink::rotating::{RotatedNaming, RotatingFileSink};
+#[cfg(feature = "syslog")]
+pub use sink::syslog::{Facility, SyslogFormatter, SyslogSink};
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use sink::uring::UringSink;
+pub use sink::{
+    atomic::AtomicFileSink,
+    disk_guard::{DiskGuard, DiskStatus},
+    fault::{Fault, FaultAction, FaultInjector, FaultPlan, FaultSink, FlakySink, RandomFaults},
+    file::{default_log_dir, FileSink, FileSinkOptions},
+    framed::FramedSink,
+    keyed::KeyedFileSink,
+    paced::PacedSink,
+    part::{PartFileSink, PartLimit},
+    partitioned::{Partition, TimePartitionedSink},
+    retention::RetentionPolicy,
+    stdio::{StderrSink, StdoutSink},
+    tee::TeeSink,
+    OutputLine, OutputSink, SinkFuture, Stream,
+};
+pub use tap::TapWriter;
 
 use deadqueue::unlimited::Queue;
-use std::{fmt, fmt::Display, io::Write, ops::Deref, sync::Arc};
-use tokio::{
-    io::{stderr, stdout, AsyncWriteExt},
-    sync::Mutex,
-    task::{spawn, JoinHandle},
+use std::io::Error as IoError;
+use std::{
+    fmt::Display,
+    future::Future,
+    io::{IoSlice, Write},
+    marker::PhantomData,
+    ops::Deref,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use thiserror::Error;
+
+use bounded::{Bounds, Slot};
+use control::TaskControl;
+use describe::ChannelStats;
+use incident::{catch_unwind, Incidents};
+use summary::Summary;
 use tokio::task::JoinError;
-use std::io::Error as IoError;
+use tokio::{
+    io::{stderr, stdout, AsyncWrite, AsyncWriteExt},
+    runtime::{Handle, RuntimeFlavor},
+    sync::{oneshot, Mutex, MutexGuard},
+    task::{spawn, JoinHandle},
+    time::timeout_at,
+};
 
 #[derive(Error, Debug)]
 pub enum StdoutChannelError {
@@ -19,22 +175,270 @@ pub enum StdoutChannelError {
     JoinError(#[from] JoinError),
     #[error("io error")]
     IoError(#[from] IoError),
+    #[error("{0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("{stream} writer task panicked: {message}")]
+    TaskPanic { stream: Stream, message: String },
+    #[error("{stream} line quarantined: {reason}")]
+    Quarantined { stream: Stream, reason: String },
+    #[error("writer tasks did not finish within {0:?}")]
+    CloseTimeout(Duration),
+    /// Returned by `close` after an earlier call failed
+    #[error("channel already closed: {0}")]
+    AlreadyClosed(Arc<str>),
+    /// Returned by interfaces that report send errors, like `Sink`, once
+    /// the channel is closed
+    #[error("channel is closed")]
+    Closed,
+    /// A write was cancelled by `cancel_writes` or `close_with_timeout`.
+    /// Sinks and mock stores take lines as a whole, only the process
+    /// stdout and stderr can report partially written lines.
+    #[error("{stream} write cancelled after {written} bytes of the current line")]
+    WriteCancelled { stream: Stream, written: usize },
+    #[cfg(any(feature = "sarif", feature = "schema", feature = "serde"))]
+    #[error("json error")]
+    JsonError(#[from] serde_json::Error),
+    #[cfg(feature = "redis")]
+    #[error("redis error")]
+    RedisError(#[from] redis::RedisError),
+}
+
+/// `e` followed by its sources
+pub(crate) fn error_chain(e: &StdoutChannelError) -> String {
+    let mut chain = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(e) = source {
+        chain.push_str(": ");
+        chain.push_str(&e.to_string());
+        source = e.source();
+    }
+    chain
 }
 
 enum StdoutMessage<T> {
-    Mesg(T),
+    Mesg(T, Stream, Slot),
+    /// Answered once every line queued before it has been written and the
+    /// writer or sink flushed
+    Flush(oneshot::Sender<()>),
     Close,
 }
 
 type StdoutQueue<T> = Queue<StdoutMessage<T>>;
 type StdoutTask = JoinHandle<Result<(), StdoutChannelError>>;
+type WriterFuture = Pin<Box<dyn Future<Output = Result<(), StdoutChannelError>> + Send>>;
+
+/// The writer task of a stream, spawned by the first send, flush or close
+/// made inside a runtime so channels can be created outside one
+enum WriterTask {
+    Pending(WriterFuture),
+    Running(StdoutTask),
+    /// Taken by `close` or `abort`
+    Taken,
+}
+
+impl WriterTask {
+    fn new(writer: impl Future<Output = Result<(), StdoutChannelError>> + Send + 'static) -> Self {
+        Self::Pending(Box::pin(writer))
+    }
+
+    /// Spawn the task if it's pending and there is a runtime
+    fn start(&mut self) -> Option<&StdoutTask> {
+        if matches!(self, Self::Pending(_)) && Handle::try_current().is_ok() {
+            if let Self::Pending(writer) = std::mem::replace(self, Self::Taken) {
+                *self = Self::Running(spawn(writer));
+            }
+        }
+        match self {
+            Self::Running(task) => Some(task),
+            _ => None,
+        }
+    }
+
+    /// Start and take the task, a pending one is dropped outside a runtime
+    fn take(&mut self) -> Option<StdoutTask> {
+        self.start();
+        match std::mem::replace(self, Self::Taken) {
+            Self::Running(task) => Some(task),
+            _ => None,
+        }
+    }
+}
+
+/// The task set up by `with_flush_interval`, spawned with the writer tasks
+type Flusher = Pin<Box<dyn Future<Output = ()> + Send>>;
+type CloseReport<T> = Box<dyn Fn() -> T + Send + Sync>;
+type CloseHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = HookResult> + Send>> + Send>;
+type HookResult = Result<(), StdoutChannelError>;
+type CloseResult = Result<(), Arc<str>>;
+
+/// Outcome of a paced send
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendStatus {
+    Accepted,
+    /// The queue is above the backpressure threshold, producers should back
+    /// off
+    SlowDown {
+        queued: usize,
+    },
+    /// The bounded queue was full and the item was discarded
+    Full,
+    /// The channel was closed and the item was discarded
+    Closed,
+}
+
+impl SendStatus {
+    #[must_use]
+    pub fn is_slow_down(self) -> bool {
+        matches!(self, Self::SlowDown { .. })
+    }
+}
+
+struct Pacing {
+    rate_limiter: RateLimiter,
+    threshold: usize,
+}
+
+const MAX_PACING_SHIFT: usize = 4;
+
+/// Time writer tasks get to stop once their writes are cancelled
+const CANCEL_GRACE: Duration = Duration::from_millis(100);
 
-#[derive(Clone)]
 pub struct StdoutChannel<T> {
     stdout_queue: Arc<StdoutQueue<T>>,
     stderr_queue: Arc<StdoutQueue<T>>,
-    stdout_task: Arc<Mutex<Option<StdoutTask>>>,
-    stderr_task: Arc<Mutex<Option<StdoutTask>>>,
+    stdout_task: Arc<sync::Mutex<WriterTask>>,
+    stderr_task: Arc<sync::Mutex<WriterTask>>,
+    pacing: Option<Arc<Pacing>>,
+    close_reports: Arc<sync::Mutex<Vec<CloseReport<T>>>>,
+    close_hooks: Arc<sync::Mutex<Vec<CloseHook>>>,
+    stats: Arc<ChannelStats>,
+    config: Arc<sync::Mutex<OutputConfig>>,
+    summary: Arc<Summary>,
+    bounds: Option<Arc<Bounds<T>>>,
+    incidents: Arc<Incidents<T>>,
+    closed: Arc<Mutex<Option<CloseResult>>>,
+    closing: Arc<AtomicBool>,
+    control: Arc<TaskControl>,
+    clock: Clock,
+    null: bool,
+    started: Arc<AtomicBool>,
+    flusher: Arc<sync::Mutex<Option<Flusher>>>,
+}
+
+impl<T> Clone for StdoutChannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            stdout_queue: Arc::clone(&self.stdout_queue),
+            stderr_queue: Arc::clone(&self.stderr_queue),
+            stdout_task: Arc::clone(&self.stdout_task),
+            stderr_task: Arc::clone(&self.stderr_task),
+            pacing: self.pacing.clone(),
+            close_reports: Arc::clone(&self.close_reports),
+            close_hooks: Arc::clone(&self.close_hooks),
+            stats: Arc::clone(&self.stats),
+            config: Arc::clone(&self.config),
+            summary: Arc::clone(&self.summary),
+            bounds: self.bounds.clone(),
+            incidents: Arc::clone(&self.incidents),
+            closed: Arc::clone(&self.closed),
+            closing: Arc::clone(&self.closing),
+            control: Arc::clone(&self.control),
+            clock: self.clock.clone(),
+            null: self.null,
+            started: Arc::clone(&self.started),
+            flusher: Arc::clone(&self.flusher),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    fn from_parts(
+        [stdout_queue, stderr_queue]: [Arc<StdoutQueue<T>>; 2],
+        [stdout_task, stderr_task]: [Arc<sync::Mutex<WriterTask>>; 2],
+        stats: Arc<ChannelStats>,
+        incidents: Arc<Incidents<T>>,
+        control: Arc<TaskControl>,
+    ) -> Self {
+        Self {
+            stdout_queue,
+            stderr_queue,
+            stdout_task,
+            stderr_task,
+            pacing: None,
+            close_reports: Arc::default(),
+            close_hooks: Arc::default(),
+            stats,
+            config: Arc::default(),
+            summary: Arc::default(),
+            bounds: None,
+            incidents,
+            closed: Arc::default(),
+            closing: Arc::default(),
+            control,
+            clock: Clock::default(),
+            null: false,
+            started: Arc::default(),
+            flusher: Arc::default(),
+        }
+    }
+
+    /// Whether `close` or `abort` has been called on this channel or a
+    /// clone of it, lines sent since are never written
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closing.load(Ordering::Acquire)
+    }
+
+    /// Whether a line sent now would be dropped because the channel is
+    /// closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if it would, sending after `close` is a bug
+    /// unless done with `try_send` or `send_paced` which return
+    /// `SendStatus::Closed`
+    fn rejects_send(&self) -> bool {
+        let closed = self.is_closed();
+        assert!(
+            !closed || !cfg!(debug_assertions),
+            "send on a closed StdoutChannel"
+        );
+        closed
+    }
+
+    /// Spawn the writer tasks unless they run already, lines stay queued
+    /// until a send inside a runtime does
+    fn start_tasks(&self) {
+        if self.started.load(Ordering::Acquire) {
+            return;
+        }
+        let mut pending = false;
+        for task in [&self.stdout_task, &self.stderr_task] {
+            let mut task = task.lock();
+            task.start();
+            pending |= matches!(*task, WriterTask::Pending(_));
+        }
+        let mut flusher = self.flusher.lock();
+        if Handle::try_current().is_ok() {
+            if let Some(flusher) = flusher.take() {
+                spawn(flusher);
+            }
+        }
+        pending |= flusher.is_some();
+        if !pending {
+            self.started.store(true, Ordering::Release);
+        }
+    }
+
+    /// Whether lines are dropped because this is a `null` channel, they
+    /// are counted as filtered
+    fn discards(&self, stream: Stream) -> bool {
+        if self.null {
+            self.stats.filtered(stream);
+        }
+        self.null
+    }
 }
 
 impl<T> Default for StdoutChannel<T>
@@ -46,110 +450,644 @@ where
     }
 }
 
-impl<T> fmt::Debug for StdoutChannel<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "StdoutChannel")
-    }
-}
-
 impl<T> StdoutChannel<T>
 where
     T: Display + Send + 'static,
 {
+    /// The writer tasks are spawned by the first send, flush or close made
+    /// inside a tokio runtime, so a channel can be created before one is
+    /// running, e.g. in a `static` or before `Runtime::new`
     #[must_use]
     pub fn new() -> Self {
         let stdout_queue = Queue::new().into();
         let stderr_queue = Queue::new().into();
-        let stdout_task = Mutex::new(Some(spawn({
-            let queue = Arc::clone(&stdout_queue);
-            async move { Self::process_stdout(&queue).await }
-        })))
+        let incidents: Arc<Incidents<T>> = Arc::default();
+        let stats: Arc<ChannelStats> = ChannelStats::new("stdout", "stderr")
+            .with_process_streams([true; 2])
+            .into();
+        let control: Arc<TaskControl> = Arc::default();
+        let stdout_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stdout_queue, Stream::Stdout, &incidents, &stats, &control);
+            async move { Self::process_writer(&cx, stdout()).await }
+        }))
         .into();
-        let stderr_task = Mutex::new(Some(spawn({
-            let queue = Arc::clone(&stderr_queue);
-            async move { Self::process_stderr(&queue).await }
-        })))
+        let stderr_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stderr_queue, Stream::Stderr, &incidents, &stats, &control);
+            async move { Self::process_writer(&cx, stderr()).await }
+        }))
         .into();
-        Self {
-            stdout_queue,
-            stderr_queue,
-            stdout_task,
-            stderr_task,
-        }
+        Self::from_parts(
+            [stdout_queue, stderr_queue],
+            [stdout_task, stderr_task],
+            stats,
+            incidents,
+            control,
+        )
     }
 
     #[must_use]
-    pub fn with_mock_stdout(mock_stdout: MockStdout<T>, mock_stderr: MockStdout<T>) -> Self {
+    pub fn with_mock_stdout<O, E>(
+        mock_stdout: MockStdout<T, O>,
+        mock_stderr: MockStdout<T, E>,
+    ) -> Self
+    where
+        O: MockStore<T> + 'static,
+        E: MockStore<T> + 'static,
+    {
         let stdout_queue = Queue::new().into();
         let stderr_queue = Queue::new().into();
-        let stdout_task = Mutex::new(Some(spawn({
-            let queue = Arc::clone(&stdout_queue);
-            async move { Self::process_mock(&queue, &mock_stdout).await }
-        })))
+        let incidents: Arc<Incidents<T>> = Arc::default();
+        let stats: Arc<ChannelStats> = ChannelStats::with_types::<O, E>().into();
+        let control: Arc<TaskControl> = Arc::default();
+        let stdout_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stdout_queue, Stream::Stdout, &incidents, &stats, &control);
+            async move { Self::process_mock(&cx, &mock_stdout).await }
+        }))
         .into();
-        let stderr_task = Mutex::new(Some(spawn({
-            let queue = Arc::clone(&stderr_queue);
-            async move { Self::process_mock(&queue, &mock_stderr).await }
-        })))
+        let stderr_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stderr_queue, Stream::Stderr, &incidents, &stats, &control);
+            async move { Self::process_mock(&cx, &mock_stderr).await }
+        }))
         .into();
-        Self {
-            stdout_queue,
-            stderr_queue,
-            stdout_task,
-            stderr_task,
-        }
+        Self::from_parts(
+            [stdout_queue, stderr_queue],
+            [stdout_task, stderr_task],
+            stats,
+            incidents,
+            control,
+        )
+    }
+
+    /// Create a channel writing each stream to a custom `OutputSink`
+    #[must_use]
+    pub fn with_sinks<O, E>(stdout_sink: O, stderr_sink: E) -> Self
+    where
+        O: OutputSink<T> + 'static,
+        E: OutputSink<T> + 'static,
+    {
+        let stdout_queue = Queue::new().into();
+        let stderr_queue = Queue::new().into();
+        let incidents: Arc<Incidents<T>> = Arc::default();
+        let stats: Arc<ChannelStats> = ChannelStats::for_sinks(&stdout_sink, &stderr_sink).into();
+        let control: Arc<TaskControl> = Arc::default();
+        let stdout_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stdout_queue, Stream::Stdout, &incidents, &stats, &control);
+            async move { Self::process_sink(&cx, stdout_sink).await }
+        }))
+        .into();
+        let stderr_task = sync::Mutex::new(WriterTask::new({
+            let cx = TaskContext::new(&stderr_queue, Stream::Stderr, &incidents, &stats, &control);
+            async move { Self::process_sink(&cx, stderr_sink).await }
+        }))
+        .into();
+        Self::from_parts(
+            [stdout_queue, stderr_queue],
+            [stdout_task, stderr_task],
+            stats,
+            incidents,
+            control,
+        )
     }
 
+    /// # Panics
+    ///
+    /// Panics in debug builds if the channel is closed, in release builds
+    /// the line is dropped
     pub fn send(&self, item: impl Into<T>) {
-        self.stdout_queue.push(StdoutMessage::Mesg(item.into()));
+        if !self.rejects_send() {
+            self.push_line(Stream::Stdout, item.into());
+        }
     }
 
+    /// # Panics
+    ///
+    /// Same as `send`
     pub fn send_err(&self, item: impl Into<T>) {
-        self.stderr_queue.push(StdoutMessage::Mesg(item.into()));
+        if !self.rejects_send() {
+            self.push_line(Stream::Stderr, item.into());
+        }
+    }
+
+    /// Send for lines the crate sends on its own, from drops and background
+    /// tasks that may run after `close`: a line sent to a closed channel is
+    /// dropped and counted as filtered instead of panicking. Returns whether
+    /// the line was queued.
+    pub(crate) fn send_quiet(&self, stream: Stream, item: impl Into<T>) -> bool {
+        if self.is_closed() {
+            self.stats.filtered(stream);
+            return false;
+        }
+        self.push_line(stream, item.into());
+        true
+    }
+
+    fn push_line(&self, stream: Stream, item: T) {
+        if self.discards(stream) {
+            return;
+        }
+        self.start_tasks();
+        let queue = match stream {
+            Stream::Stdout => {
+                self.stats.sent_stdout();
+                &self.stdout_queue
+            }
+            Stream::Stderr => {
+                self.stats.sent_stderr();
+                &self.stderr_queue
+            }
+        };
+        queue.push(StdoutMessage::Mesg(item, stream, Slot::default()));
+    }
+
+    /// Pace `send_paced` and `send_err_paced` with `rate_limiter`.
+    ///
+    /// Once more than `threshold` lines are queued on a stream each send
+    /// costs twice as many permits for every further `threshold` lines (up to
+    /// 16), so bursts slow producers down gradually instead of growing the
+    /// queue without bound.
+    #[must_use]
+    pub fn with_rate_limit(mut self, rate_limiter: RateLimiter, threshold: usize) -> Self {
+        self.pacing = Some(Arc::new(Pacing {
+            rate_limiter,
+            threshold: threshold.max(1),
+        }));
+        self
+    }
+
+    /// Add a line to be sent to stderr when the channel is closed
+    pub fn add_close_report(&self, report: impl Fn() -> T + Send + Sync + 'static) {
+        self.close_reports.lock().push(Box::new(report));
+    }
+
+    /// Run `hook` when the channel is closed, before the close reports and
+    /// the queued lines are written, e.g. to write a report file. Errors are
+    /// passed to the error hook and don't stop the close.
+    pub fn add_close_hook<F>(&self, hook: impl FnOnce() -> F + Send + 'static)
+    where
+        F: Future<Output = Result<(), StdoutChannelError>> + Send + 'static,
+    {
+        self.close_hooks
+            .lock()
+            .push(Box::new(move || Box::pin(hook())));
+    }
+
+    /// Send to stdout after acquiring permits from the rate limiter set with
+    /// `with_rate_limit`, same as `send` if there is none. Returns
+    /// `SendStatus::Closed` if the channel is closed.
+    pub async fn send_paced(&self, item: impl Into<T>) -> SendStatus {
+        if self.is_closed() {
+            return SendStatus::Closed;
+        }
+        if self.discards(Stream::Stdout) {
+            return SendStatus::Accepted;
+        }
+        self.start_tasks();
+        self.stats.sent_stdout();
+        let queue = &self.stdout_queue;
+        Self::push_paced(self.pacing.as_deref(), queue, Stream::Stdout, item.into()).await
     }
 
-    /// Close the `StdoutChannel`
+    /// Send to stderr after acquiring permits from the rate limiter set with
+    /// `with_rate_limit`, same as `send_err` if there is none. Returns
+    /// `SendStatus::Closed` if the channel is closed.
+    pub async fn send_err_paced(&self, item: impl Into<T>) -> SendStatus {
+        if self.is_closed() {
+            return SendStatus::Closed;
+        }
+        if self.discards(Stream::Stderr) {
+            return SendStatus::Accepted;
+        }
+        self.start_tasks();
+        self.stats.sent_stderr();
+        let queue = &self.stderr_queue;
+        Self::push_paced(self.pacing.as_deref(), queue, Stream::Stderr, item.into()).await
+    }
+
+    async fn push_paced(
+        pacing: Option<&Pacing>,
+        queue: &StdoutQueue<T>,
+        stream: Stream,
+        item: T,
+    ) -> SendStatus {
+        let Some(pacing) = pacing else {
+            queue.push(StdoutMessage::Mesg(item, stream, Slot::default()));
+            return SendStatus::Accepted;
+        };
+        let shift = (queue.len() / pacing.threshold).min(MAX_PACING_SHIFT);
+        for _ in 0..1 << shift {
+            pacing.rate_limiter.acquire().await;
+        }
+        queue.push(StdoutMessage::Mesg(item, stream, Slot::default()));
+        let queued = queue.len();
+        if queued > pacing.threshold {
+            SendStatus::SlowDown { queued }
+        } else {
+            SendStatus::Accepted
+        }
+    }
+
+    /// Wait until every line sent so far has been written to stdout and
+    /// stderr (or their sinks) and flushed, leaving the channel open. Call
+    /// before prompting the user or handing the terminal to a child process.
+    pub async fn flush(&self) {
+        let stdout = Self::push_barrier(&self.stdout_queue, &self.stdout_task);
+        let stderr = Self::push_barrier(&self.stderr_queue, &self.stderr_task);
+        // a barrier is dropped unanswered if its writer task fails
+        for barrier in stdout.into_iter().chain(stderr) {
+            barrier.await.ok();
+        }
+    }
+
+    /// Flush both streams every `interval` (at least 1 ms) until the
+    /// channel is closed, so the last lines written to a buffered sink
+    /// don't wait for more output or `close`. Intervals without new lines
+    /// are skipped.
+    ///
+    /// The flushing task is spawned with the writer tasks and holds no
+    /// reference keeping the channel alive, it stops once the channel and
+    /// its clones are dropped.
+    #[must_use]
+    pub fn with_flush_interval(self, interval: Duration) -> Self {
+        let interval = interval.max(Duration::from_millis(1));
+        let queues = [&self.stdout_queue, &self.stderr_queue].map(Arc::downgrade);
+        let tasks = [&self.stdout_task, &self.stderr_task].map(Arc::downgrade);
+        let stats = Arc::downgrade(&self.stats);
+        let closing = Arc::downgrade(&self.closing);
+        let flusher = async move {
+            let mut flushed = 0;
+            loop {
+                tokio::time::sleep(interval).await;
+                let (Some(stats), Some(closing)) = (stats.upgrade(), closing.upgrade()) else {
+                    return;
+                };
+                if closing.load(Ordering::Acquire) {
+                    return;
+                }
+                let sent = stats.sent();
+                if sent == flushed {
+                    continue;
+                }
+                let mut barriers = Vec::new();
+                for (queue, task) in queues.iter().zip(&tasks) {
+                    let (Some(queue), Some(task)) = (queue.upgrade(), task.upgrade()) else {
+                        return;
+                    };
+                    barriers.extend(Self::push_barrier(&queue, &task));
+                }
+                drop((stats, closing));
+                for barrier in barriers {
+                    barrier.await.ok();
+                }
+                flushed = sent;
+            }
+        };
+        *self.flusher.lock() = Some(Box::pin(flusher));
+        self.started.store(false, Ordering::Release);
+        self
+    }
+
+    fn push_barrier(
+        queue: &StdoutQueue<T>,
+        task: &sync::Mutex<WriterTask>,
+    ) -> Option<oneshot::Receiver<()>> {
+        if task.lock().start().is_none_or(JoinHandle::is_finished) {
+            return None;
+        }
+        let (tx, rx) = oneshot::channel();
+        queue.push(StdoutMessage::Flush(tx));
+        Some(rx)
+    }
+
+    /// Close the `StdoutChannel`.
+    ///
+    /// Only the first call on a channel or any of its clones closes it,
+    /// later and concurrent calls wait for that one and return the same
+    /// outcome, failures as `StdoutChannelError::AlreadyClosed`.
     /// # Errors
     ///
-    /// Will error if there have been any errors or panics in the stdout and
-    /// stderr tasks
+    /// Will error if there have been any errors in the stdout and stderr
+    /// tasks, panics are caught there and reported to the error hook instead
     pub async fn close(&self) -> Result<(), StdoutChannelError> {
+        self.close_once(None).await
+    }
+
+    /// Close the `StdoutChannel`, cancelling the writes of the writer tasks
+    /// and discarding whatever is still queued if they take longer than
+    /// `timeout`, e.g. because the reader of a pipe stopped reading. How far
+    /// each cancelled write got is reported to the error hook as a
+    /// `StdoutChannelError::WriteCancelled`.
+    /// # Errors
+    ///
+    /// Will error with `StdoutChannelError::CloseTimeout` if the tasks were
+    /// aborted, or like `close`
+    pub async fn close_with_timeout(&self, timeout: Duration) -> Result<(), StdoutChannelError> {
+        self.close_once(Some(timeout)).await
+    }
+
+    async fn close_once(&self, timeout: Option<Duration>) -> Result<(), StdoutChannelError> {
+        let mut closed = self.closed.lock().await;
+        if let Some(result) = &*closed {
+            return result.clone().map_err(StdoutChannelError::AlreadyClosed);
+        }
+        let result = match timeout {
+            Some(timeout) => self.close_within(timeout).await,
+            None => self.close_tasks().await,
+        };
+        *closed = Some(result.as_ref().copied().map_err(|e| error_chain(e).into()));
+        result
+    }
+
+    async fn close_tasks(&self) -> Result<(), StdoutChannelError> {
+        self.run_close_hooks().await;
+        let mut tasks = self.start_close();
+        Self::join_tasks(&mut tasks).await?;
+        self.dump_quarantined();
+        Ok(())
+    }
+
+    async fn close_within(&self, timeout: Duration) -> Result<(), StdoutChannelError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let _ = tokio::time::timeout_at(deadline, self.run_close_hooks()).await;
+        let mut tasks = self.start_close();
+        let Ok(result) = tokio::time::timeout_at(deadline, Self::join_tasks(&mut tasks)).await
+        else {
+            self.cancel_tasks(tasks).await;
+            self.discard_queued();
+            return Err(StdoutChannelError::CloseTimeout(timeout));
+        };
+        result?;
+        self.dump_quarantined();
+        Ok(())
+    }
+
+    async fn run_close_hooks(&self) {
+        let hooks = std::mem::take(&mut *self.close_hooks.lock());
+        for hook in hooks {
+            if let Err(e) = hook().await {
+                self.incidents.report(&e);
+            }
+        }
+    }
+
+    /// Cancel the writes `tasks` are stuck in and report how far they got
+    /// to the error hook, aborting tasks that don't stop
+    async fn cancel_tasks(&self, tasks: Vec<StdoutTask>) {
+        self.cancel_writes();
+        for mut task in tasks {
+            match tokio::time::timeout(CANCEL_GRACE, &mut task).await {
+                Ok(Ok(Err(e))) => self.incidents.report(&e),
+                Ok(_) => {}
+                Err(_) => task.abort(),
+            }
+        }
+    }
+
+    /// Cancel the writer tasks and discard every queued line, close report
+    /// and close hook without writing or running them
+    pub fn abort(&self) {
+        self.close_reports.lock().clear();
+        self.close_hooks.lock().clear();
+        for task in self.take_tasks() {
+            task.abort();
+        }
+        self.discard_queued();
+    }
+
+    /// Queue the close reports and a `Close` message on each stream,
+    /// returning the writer tasks to wait for
+    fn start_close(&self) -> Vec<StdoutTask> {
+        let reports = std::mem::take(&mut *self.close_reports.lock());
+        for report in reports {
+            self.send_err(report());
+        }
         self.stdout_queue.push(StdoutMessage::Close);
         self.stderr_queue.push(StdoutMessage::Close);
-        if let Some(stdout_task) = self.stdout_task.lock().await.take() {
-            stdout_task.await??;
-        }
-        if let Some(stderr_task) = self.stderr_task.lock().await.take() {
-            stderr_task.await??;
+        self.take_tasks()
+    }
+
+    fn take_tasks(&self) -> Vec<StdoutTask> {
+        self.closing.store(true, Ordering::Release);
+        let stdout_task = self.stdout_task.lock().take();
+        let stderr_task = self.stderr_task.lock().take();
+        stdout_task.into_iter().chain(stderr_task).collect()
+    }
+
+    /// Wait for `tasks`, removing each one once it finished
+    async fn join_tasks(tasks: &mut Vec<StdoutTask>) -> Result<(), StdoutChannelError> {
+        while let Some(task) = tasks.first_mut() {
+            let result = task.await;
+            tasks.remove(0);
+            result??;
         }
         Ok(())
     }
 
-    async fn process_stdout(queue: &StdoutQueue<T>) -> Result<(), StdoutChannelError> {
-        let mut buf = Buffer::new();
-        while let StdoutMessage::Mesg(line) = queue.pop().await {
-            stdout().write_all(buf.write_line(line)?).await?;
+    /// Drop every queued message, releasing bounded slots and answering
+    /// pending flushes
+    fn discard_queued(&self) {
+        while self.stdout_queue.try_pop().is_some() {}
+        while self.stderr_queue.try_pop().is_some() {}
+    }
+
+    /// Write every message already queued behind the first one, or queued
+    /// within the coalesce window, in a single (vectored) write
+    async fn process_writer(
+        cx: &TaskContext<T>,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<(), StdoutChannelError> {
+        // lines batched before a panicking one are kept and written by the
+        // next run
+        let mut batch = LineBatch::new();
+        let mut slots = Vec::new();
+        loop {
+            let run = Self::drain_writer(cx, &mut writer, &mut batch, &mut slots);
+            match catch_unwind(run).await {
+                Ok(result) => return result,
+                Err(panic) => cx.incidents.record(cx.stream, &*panic),
+            }
+        }
+    }
+
+    async fn drain_writer(
+        cx: &TaskContext<T>,
+        writer: &mut (impl AsyncWrite + Unpin),
+        batch: &mut LineBatch,
+        slots: &mut Vec<Slot>,
+    ) -> Result<(), StdoutChannelError> {
+        loop {
+            let mut closed = false;
+            let mut barrier = None;
+            let mut next = Some(cx.queue.pop().await);
+            let deadline = cx.control.coalesce_deadline();
+            while let Some(message) = next.take() {
+                match message {
+                    StdoutMessage::Mesg(line, _, slot) => {
+                        match quarantine::render(&line, |line| batch.push(line)) {
+                            Ok(()) => slots.push(slot),
+                            Err(reason) => cx.incidents.quarantine(cx.stream, line, reason),
+                        }
+                    }
+                    StdoutMessage::Flush(tx) => {
+                        barrier = Some(tx);
+                        break;
+                    }
+                    StdoutMessage::Close => {
+                        closed = true;
+                        break;
+                    }
+                }
+                if batch.len() < MAX_BATCH_LINES {
+                    next = match (cx.queue.try_pop(), deadline) {
+                        (None, Some(deadline)) => timeout_at(deadline, cx.queue.pop()).await.ok(),
+                        (next, _) => next,
+                    };
+                }
+            }
+            let lines = batch.len();
+            let bytes = match cx.write(cx.stream, batch.write_to(writer)).await {
+                Err(StdoutChannelError::WriteCancelled { stream, .. }) => {
+                    let written = batch.partial();
+                    return Err(StdoutChannelError::WriteCancelled { stream, written });
+                }
+                result => result?,
+            };
+            cx.stats.written(cx.stream, lines, bytes);
+            slots.clear();
+            if let Some(tx) = barrier {
+                cx.write(cx.stream, flush(writer)).await?;
+                tx.send(()).ok();
+            }
+            if closed {
+                cx.write(cx.stream, flush(writer)).await?;
+                return Ok(());
+            }
         }
-        Ok(())
     }
 
-    async fn process_stderr(queue: &StdoutQueue<T>) -> Result<(), StdoutChannelError> {
+    async fn process_sink(
+        cx: &TaskContext<T>,
+        mut sink: impl OutputSink<T>,
+    ) -> Result<(), StdoutChannelError> {
         let mut buf = Buffer::new();
-        while let StdoutMessage::Mesg(line) = queue.pop().await {
-            stderr().write_all(buf.write_line(line)?).await?;
+        loop {
+            let run = Self::drain_sink(cx, &mut sink, &mut buf);
+            match catch_unwind(run).await {
+                Ok(Ok(())) => return cx.write(cx.stream, sink.close()).await,
+                Ok(Err(e)) => return Err(e),
+                Err(panic) => cx.incidents.record(cx.stream, &*panic),
+            }
         }
-        Ok(())
     }
 
-    async fn process_mock(
-        queue: &StdoutQueue<T>,
-        mock_stdout: &MockStdout<T>,
+    async fn drain_sink(
+        cx: &TaskContext<T>,
+        sink: &mut impl OutputSink<T>,
+        buf: &mut Buffer,
     ) -> Result<(), StdoutChannelError> {
-        while let StdoutMessage::Mesg(line) = queue.pop().await {
-            mock_stdout.lock().await.push(line);
+        loop {
+            match cx.queue.pop().await {
+                StdoutMessage::Mesg(item, stream, slot) => {
+                    match quarantine::render(&item, |item| buf.write_line(item).map(|_| ())) {
+                        Ok(()) => {
+                            let bytes = buf.bytes().len();
+                            let line = OutputLine::new(item, buf.bytes(), stream);
+                            cx.write(stream, sink.write(line)).await?;
+                            cx.stats.written(stream, 1, bytes);
+                        }
+                        Err(reason) => cx.incidents.quarantine(stream, item, reason),
+                    }
+                    drop(slot);
+                }
+                StdoutMessage::Flush(tx) => {
+                    cx.write(cx.stream, sink.flush()).await?;
+                    tx.send(()).ok();
+                }
+                StdoutMessage::Close => return Ok(()),
+            }
         }
-        Ok(())
     }
+
+    async fn process_mock<S: MockStore<T>>(
+        cx: &TaskContext<T>,
+        mock_stdout: &MockStdout<T, S>,
+    ) -> Result<(), StdoutChannelError> {
+        loop {
+            match catch_unwind(Self::drain_mock(cx, mock_stdout)).await {
+                Ok(result) => return result,
+                Err(panic) => cx.incidents.record(cx.stream, &*panic),
+            }
+        }
+    }
+
+    /// Mock stores are given the items unrendered, so lines are counted as
+    /// written with no bytes
+    async fn drain_mock<S: MockStore<T>>(
+        cx: &TaskContext<T>,
+        mock_stdout: &MockStdout<T, S>,
+    ) -> Result<(), StdoutChannelError> {
+        loop {
+            match cx.queue.pop().await {
+                StdoutMessage::Mesg(line, _, slot) => {
+                    let locked = async { Ok(mock_stdout.lock().await) };
+                    cx.write(cx.stream, locked).await?.push(line)?;
+                    cx.stats.written(cx.stream, 1, 0);
+                    drop(slot);
+                }
+                StdoutMessage::Flush(tx) => {
+                    tx.send(()).ok();
+                }
+                StdoutMessage::Close => return Ok(()),
+            }
+        }
+    }
+}
+
+/// State shared by a writer task with the channel it writes for
+struct TaskContext<T> {
+    queue: Arc<StdoutQueue<T>>,
+    stream: Stream,
+    incidents: Arc<Incidents<T>>,
+    stats: Arc<ChannelStats>,
+    control: Arc<TaskControl>,
+}
+
+impl<T> TaskContext<T> {
+    fn new(
+        queue: &Arc<StdoutQueue<T>>,
+        stream: Stream,
+        incidents: &Arc<Incidents<T>>,
+        stats: &Arc<ChannelStats>,
+        control: &Arc<TaskControl>,
+    ) -> Self {
+        Self {
+            queue: Arc::clone(queue),
+            stream,
+            incidents: Arc::clone(incidents),
+            stats: Arc::clone(stats),
+            control: Arc::clone(control),
+        }
+    }
+
+    /// Run the write `fut` for a line of `stream` unless writes are
+    /// cancelled first
+    async fn write<R>(
+        &self,
+        stream: Stream,
+        fut: impl Future<Output = Result<R, StdoutChannelError>>,
+    ) -> Result<R, StdoutChannelError> {
+        self.control
+            .run(fut)
+            .await
+            .unwrap_or(Err(StdoutChannelError::WriteCancelled {
+                stream,
+                written: 0,
+            }))
+    }
+}
+
+async fn flush(writer: &mut (impl AsyncWrite + Unpin)) -> Result<(), StdoutChannelError> {
+    writer.flush().await?;
+    Ok(())
 }
 
 const MAX_BUFFER_CAPACITY: usize = 4096;
@@ -161,7 +1099,7 @@ impl Buffer {
         Self(Vec::new())
     }
 
-    pub fn write_line<T: Display>(&mut self, line: T) -> Result<&[u8], StdoutChannelError> {
+    pub fn write_line<T: Display>(&mut self, line: &T) -> Result<&[u8], StdoutChannelError> {
         self.0.clear();
         if self.0.capacity() > MAX_BUFFER_CAPACITY {
             self.0.shrink_to(MAX_BUFFER_CAPACITY);
@@ -169,10 +1107,113 @@ impl Buffer {
         writeln!(self.0, "{line}")?;
         Ok(&self.0)
     }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+const MAX_BATCH_LINES: usize = 64;
+
+/// Lines rendered into reused per-line buffers, written out with
+/// `write_vectored` or, for writers that don't support vectoring, copied into
+/// one contiguous buffer first.
+struct LineBatch {
+    lines: Vec<Vec<u8>>,
+    used: usize,
+    scratch: Vec<u8>,
+    /// Bytes of the lines in use written so far
+    written: usize,
+}
+
+impl LineBatch {
+    fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            used: 0,
+            scratch: Vec::new(),
+            written: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.used
+    }
+
+    /// Bytes written of the first line not written completely
+    fn partial(&self) -> usize {
+        let mut written = self.written;
+        for line in &self.lines[..self.used] {
+            if written < line.len() {
+                return written;
+            }
+            written -= line.len();
+        }
+        0
+    }
+
+    fn push<T: Display>(&mut self, line: &T) -> Result<(), StdoutChannelError> {
+        if self.used == self.lines.len() {
+            self.lines.push(Vec::new());
+        }
+        let buf = &mut self.lines[self.used];
+        buf.clear();
+        if buf.capacity() > MAX_BUFFER_CAPACITY {
+            buf.shrink_to(MAX_BUFFER_CAPACITY);
+        }
+        writeln!(buf, "{line}")?;
+        self.used += 1;
+        Ok(())
+    }
+
+    async fn write_to(
+        &mut self,
+        writer: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<usize, StdoutChannelError> {
+        let lines = &self.lines[..self.used];
+        let bytes = lines.iter().map(Vec::len).sum();
+        if writer.is_write_vectored() {
+            let mut slices: Vec<_> = lines.iter().map(|l| IoSlice::new(l)).collect();
+            let mut slices = &mut slices[..];
+            while !slices.is_empty() {
+                let n = writer.write_vectored(slices).await?;
+                if n == 0 {
+                    return Err(IoError::from(std::io::ErrorKind::WriteZero).into());
+                }
+                self.written += n;
+                IoSlice::advance_slices(&mut slices, n);
+            }
+        } else {
+            self.scratch.clear();
+            for line in lines {
+                self.scratch.extend_from_slice(line);
+            }
+            while self.written < self.scratch.len() {
+                let n = writer.write(&self.scratch[self.written..]).await?;
+                if n == 0 {
+                    return Err(IoError::from(std::io::ErrorKind::WriteZero).into());
+                }
+                self.written += n;
+            }
+            if self.scratch.capacity() > MAX_BUFFER_CAPACITY * MAX_BATCH_LINES {
+                self.scratch = Vec::new();
+            }
+        }
+        self.used = 0;
+        self.written = 0;
+        Ok(bytes)
+    }
 }
 
-#[derive(Clone)]
-pub struct MockStdout<T>(Arc<Mutex<Vec<T>>>);
+/// Captures the lines sent to a stream in a `MockStore`, a `Vec` unless
+/// created with `with_store`
+pub struct MockStdout<T, S = Vec<T>>(Arc<Mutex<S>>, PhantomData<fn(T)>);
+
+impl<T, S> Clone for MockStdout<T, S> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0), PhantomData)
+    }
+}
 
 impl<T> Default for MockStdout<T> {
     fn default() -> Self {
@@ -180,8 +1221,8 @@ impl<T> Default for MockStdout<T> {
     }
 }
 
-impl<T> Deref for MockStdout<T> {
-    type Target = Mutex<Vec<T>>;
+impl<T, S> Deref for MockStdout<T, S> {
+    type Target = Mutex<S>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -190,7 +1231,56 @@ impl<T> Deref for MockStdout<T> {
 impl<T> MockStdout<T> {
     #[must_use]
     pub fn new() -> Self {
-        Self(Mutex::new(Vec::new()).into())
+        Self::with_store(Vec::new())
+    }
+}
+
+impl<T, S> MockStdout<T, S> {
+    #[must_use]
+    pub fn with_store(store: S) -> Self {
+        Self(Mutex::new(store).into(), PhantomData)
+    }
+
+    /// Lock from synchronous code, e.g. a `Drop` impl or a non-async test
+    /// helper. Outside a runtime and on a multi-threaded one this blocks
+    /// until the lock is free. On a current-thread runtime whoever holds the
+    /// lock is a task suspended on this very thread, which can't resume
+    /// while it waits, so it panics instead.
+    /// # Panics
+    ///
+    /// Panics on a current-thread runtime if the mock is locked, e.g. by a
+    /// test holding `lock().await` across an `.await`
+    pub fn lock_sync(&self) -> MutexGuard<'_, S> {
+        match Handle::try_current() {
+            Err(_) => self.0.blocking_lock(),
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.0.blocking_lock())
+            }
+            Ok(_) => self
+                .0
+                .try_lock()
+                .expect("MockStdout is locked by a task of this current-thread runtime"),
+        }
+    }
+
+    /// Copy of everything captured so far
+    #[must_use]
+    pub fn snapshot(&self) -> S
+    where
+        S: Clone,
+    {
+        self.lock_sync().clone()
+    }
+}
+
+/// Lets a mock capture one stream while the other goes to a real sink
+impl<T, S> OutputSink<T> for MockStdout<T, S>
+where
+    T: Send + 'static,
+    S: MockStore<T>,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        Box::pin(async move { self.lock().await.push(line.into_item()) })
     }
 }
 
@@ -198,7 +1288,187 @@ impl<T> MockStdout<T> {
 mod tests {
     use stack_string::StackString;
 
-    use super::{MockStdout, StdoutChannel, StdoutChannelError};
+    use std::{
+        io,
+        panic::AssertUnwindSafe,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        task::{Context, Poll},
+    };
+    use tokio::io::AsyncWrite;
+
+    use super::{
+        ChannelStats, Duration, LineBatch, MockStdout, OutputLine, OutputSink, Queue, RateLimiter,
+        SendStatus, SinkFuture, Slot, StdoutChannel, StdoutChannelError, StdoutMessage, StdoutSink,
+        Stream, TaskContext, TaskControl,
+    };
+
+    /// Accepts at most `max` bytes per call, optionally vectored
+    struct ShortWriter {
+        data: Vec<u8>,
+        max: usize,
+        vectored: bool,
+        calls: usize,
+    }
+
+    impl AsyncWrite for ShortWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let n = buf.len().min(self.max);
+            self.data.extend_from_slice(&buf[..n]);
+            self.calls += 1;
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            bufs: &[io::IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let mut n = 0;
+            for buf in bufs {
+                let take = buf.len().min(self.max - n);
+                self.data.extend_from_slice(&buf[..take]);
+                n += take;
+            }
+            self.calls += 1;
+            Poll::Ready(Ok(n))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.vectored
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Accepts `limit` bytes, then never finishes another write
+    struct StallingWriter {
+        written: Arc<AtomicUsize>,
+        limit: usize,
+    }
+
+    impl AsyncWrite for StallingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let written = self.written.load(Ordering::Acquire);
+            let n = buf.len().min(self.limit - written);
+            if n == 0 {
+                return Poll::Pending;
+            }
+            self.written.fetch_add(n, Ordering::AcqRel);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Counts its writes
+    struct CountingWriter(Arc<AtomicUsize>);
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.0.fetch_add(1, Ordering::AcqRel);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_window() -> Result<(), StdoutChannelError> {
+        for (window, expected) in [(Duration::ZERO, 2), (Duration::from_millis(200), 1)] {
+            let queue = Arc::new(Queue::new());
+            let control = Arc::new(TaskControl::default());
+            control.set_coalesce_window(window);
+            let stats = Arc::new(ChannelStats::new("stdout", "stderr"));
+            let cx = TaskContext::new(&queue, Stream::Stdout, &Arc::default(), &stats, &control);
+            let writes = Arc::new(AtomicUsize::new(0));
+            let writer = CountingWriter(Arc::clone(&writes));
+            let task =
+                tokio::spawn(async move { StdoutChannel::process_writer(&cx, writer).await });
+            queue.push(StdoutMessage::Mesg(
+                "a".to_string(),
+                Stream::Stdout,
+                Slot::default(),
+            ));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            queue.push(StdoutMessage::Mesg(
+                "b".to_string(),
+                Stream::Stdout,
+                Slot::default(),
+            ));
+            queue.push(StdoutMessage::Close);
+            task.await??;
+            assert_eq!(writes.load(Ordering::Acquire), expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_writes() -> Result<(), StdoutChannelError> {
+        let queue = Arc::new(Queue::new());
+        let control = Arc::default();
+        let stats = Arc::new(ChannelStats::new("stdout", "stderr"));
+        let cx = TaskContext::new(&queue, Stream::Stdout, &Arc::default(), &stats, &control);
+        for line in ["first", "second"] {
+            queue.push(StdoutMessage::Mesg(
+                line.to_string(),
+                Stream::Stdout,
+                Slot::default(),
+            ));
+        }
+        let written = Arc::new(AtomicUsize::new(0));
+        let writer = StallingWriter {
+            written: Arc::clone(&written),
+            limit: 9,
+        };
+        let task = tokio::spawn(async move { StdoutChannel::process_writer(&cx, writer).await });
+        while written.load(Ordering::Acquire) < 9 {
+            tokio::task::yield_now().await;
+        }
+        control.cancel();
+        // all of `first\n` and `sec`
+        assert!(matches!(
+            task.await?,
+            Err(StdoutChannelError::WriteCancelled {
+                stream: Stream::Stdout,
+                written: 3
+            })
+        ));
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_default_mockstdout() -> Result<(), StdoutChannelError> {
@@ -253,4 +1523,266 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_new_outside_runtime() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone())
+            .with_flush_interval(Duration::from_millis(10))
+            .with_rate_limit(RateLimiter::new(100, 10), 10);
+        chan.send("queued before the runtime");
+        assert_eq!(chan.describe().stdout.queued, 1);
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async {
+            chan.send_err("spawns the writers");
+            chan.send_paced("paced").await;
+            chan.flush().await;
+            assert_eq!(stdout.snapshot(), ["queued before the runtime", "paced"]);
+            assert_eq!(stderr.snapshot(), ["spawns the writers"]);
+            chan.close().await
+        })
+    }
+
+    struct AssertOnDrop(MockStdout<String>);
+
+    impl Drop for AssertOnDrop {
+        fn drop(&mut self) {
+            assert_eq!(self.0.lock_sync().as_slice(), ["from drop"]);
+        }
+    }
+
+    fn assert_lines(mock: &MockStdout<String>, expected: &[&str]) {
+        assert_eq!(mock.snapshot(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_mock_as_sink() -> Result<(), StdoutChannelError> {
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_sinks(StdoutSink::new(), stderr.clone());
+        chan.send("to stdout");
+        chan.send_err("captured");
+        chan.close().await?;
+        assert_eq!(stderr.snapshot(), ["captured"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lock_sync() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let guard = AssertOnDrop(stdout.clone());
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send("from drop");
+        chan.close().await?;
+        assert_lines(&stdout, &["from drop"]);
+        drop(guard);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_lock_sync_waits() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let holder = tokio::spawn({
+            let stdout = stdout.clone();
+            async move {
+                let mut guard = stdout.lock().await;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                guard.push("held".into());
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(stdout.lock_sync().as_slice(), ["held"]);
+        holder.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "locked by a task of this current-thread runtime")]
+    async fn test_lock_sync_held() {
+        let stdout = MockStdout::<String>::new();
+        let _guard = stdout.lock().await;
+        let _ = stdout.lock_sync();
+    }
+
+    #[tokio::test]
+    async fn test_send_paced() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new())
+            .with_rate_limit(RateLimiter::new(1000, 100), 2);
+
+        // hold the mock so the writer task can't drain the queue
+        let guard = stdout.lock().await;
+        let mut statuses = Vec::new();
+        for i in 0..5 {
+            statuses.push(chan.send_paced(format!("line {i}")).await);
+        }
+        assert_eq!(statuses[0], SendStatus::Accepted);
+        assert!(statuses[4].is_slow_down());
+        drop(guard);
+        chan.close().await?;
+        assert_eq!(stdout.lock().await.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let out = dir.join("flush-out.log");
+        let err = dir.join("flush-err.log");
+        let chan = StdoutChannel::<String>::with_files(&out, &err).await?;
+        chan.send("before prompt");
+        chan.send_err("warning");
+        chan.flush().await;
+        assert_eq!(tokio::fs::read_to_string(&out).await?, "before prompt\n");
+        assert_eq!(tokio::fs::read_to_string(&err).await?, "warning\n");
+        chan.send("after prompt");
+        chan.close().await?;
+        // a closed channel has nothing left to flush
+        chan.flush().await;
+        assert_eq!(
+            tokio::fs::read_to_string(&out).await?,
+            "before prompt\nafter prompt\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_interval() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let out = dir.join("interval-out.log");
+        let err = dir.join("interval-err.log");
+        let chan = StdoutChannel::<String>::with_files(&out, &err)
+            .await?
+            .with_flush_interval(Duration::from_millis(10));
+        chan.send("last line before going quiet");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            tokio::fs::read_to_string(&out).await?,
+            "last line before going quiet\n"
+        );
+        chan.close().await?;
+        Ok(())
+    }
+
+    /// Never finishes writing, like stdout piped to a reader that stopped
+    struct StuckSink;
+
+    impl OutputSink<String> for StuckSink {
+        fn write<'a>(&'a mut self, _: OutputLine<'a, String>) -> SinkFuture<'a> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_with_timeout() -> Result<(), StdoutChannelError> {
+        let chan = StdoutChannel::with_sinks(StuckSink, MockStdout::new());
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        chan.set_error_hook({
+            let errors = Arc::clone(&errors);
+            move |e| errors.lock().unwrap().push(e.to_string())
+        });
+        chan.send("stuck");
+        let timeout = Duration::from_millis(20);
+        let result = chan.close_with_timeout(timeout).await;
+        assert!(matches!(result, Err(StdoutChannelError::CloseTimeout(t)) if t == timeout));
+        assert!(chan.describe().stdout.closed);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            ["stdout write cancelled after 0 bytes of the current line"]
+        );
+
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let guard = stdout.lock().await;
+        chan.send("discarded");
+        chan.send("discarded too");
+        chan.abort();
+        assert_eq!(chan.describe().stdout.queued, 0);
+        drop(guard);
+        chan.close().await?;
+        tokio::task::yield_now().await;
+        assert!(stdout.snapshot().is_empty());
+        Ok(())
+    }
+
+    /// Fails every write, like a full disk
+    struct FailingSink;
+
+    impl OutputSink<String> for FailingSink {
+        fn write<'a>(&'a mut self, _: OutputLine<'a, String>) -> SinkFuture<'a> {
+            Box::pin(async { Err(io::Error::other("disk full").into()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_once() -> Result<(), StdoutChannelError> {
+        let chan = StdoutChannel::with_sinks(FailingSink, MockStdout::new());
+        chan.send("lost");
+        let other = chan.clone();
+        let (first, second) = tokio::join!(chan.close(), other.close());
+        let errors = [first.unwrap_err(), second.unwrap_err()];
+        assert!(errors
+            .iter()
+            .any(|e| matches!(e, StdoutChannelError::IoError(_))));
+        assert!(errors.iter().any(|e| matches!(
+            e,
+            StdoutChannelError::AlreadyClosed(msg) if &**msg == "io error: disk full"
+        )));
+        assert!(matches!(
+            chan.close().await,
+            Err(StdoutChannelError::AlreadyClosed(_))
+        ));
+
+        let chan = StdoutChannel::<String>::with_mock_stdout(MockStdout::new(), MockStdout::new());
+        chan.close().await?;
+        chan.close().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_after_close() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        chan.send("written");
+        assert!(!chan.is_closed());
+        chan.close().await?;
+        assert!(chan.clone().is_closed());
+        assert_eq!(chan.try_send("late"), SendStatus::Closed);
+        assert_eq!(chan.send_err_paced("late").await, SendStatus::Closed);
+        let sent = std::panic::catch_unwind(AssertUnwindSafe(|| chan.send("late")));
+        assert_eq!(sent.is_err(), cfg!(debug_assertions));
+        // lines the crate sends from drops are discarded without a panic
+        let mut writer = chan.writer();
+        std::fmt::Write::write_str(&mut writer, "partial line").ok();
+        drop(writer);
+        assert_eq!(chan.describe().stdout.sent, 1);
+        assert_eq!(chan.describe().stdout.queued, 0);
+        assert_eq!(stdout.snapshot(), ["written"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_line_batch() -> Result<(), StdoutChannelError> {
+        for (vectored, calls) in [(true, 3), (false, 3)] {
+            let mut writer = ShortWriter {
+                data: Vec::new(),
+                max: 8,
+                vectored,
+                calls: 0,
+            };
+            let mut batch = LineBatch::new();
+            for line in ["alpha", "beta", "gamma"] {
+                batch.push(&line)?;
+            }
+            assert_eq!(batch.len(), 3);
+            batch.write_to(&mut writer).await?;
+            assert_eq!(batch.len(), 0);
+            assert_eq!(writer.data, b"alpha\nbeta\ngamma\n");
+            assert_eq!(writer.calls, calls);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,124 @@
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::fmt::Display;
+
+use crate::{sink::Stream, StdoutChannel};
+
+/// A `log` backend sending `error!` and `warn!` records to the stderr queue
+/// of a channel and the others to stdout, as lines like
+/// `WARN  my_crate::db: slow query`. The logger may outlive the channel,
+/// records logged after it is closed are dropped.
+pub struct StdoutChannelLogger<T> {
+    chan: StdoutChannel<T>,
+    level: LevelFilter,
+}
+
+impl<T> StdoutChannelLogger<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// A logger passing records of every level
+    #[must_use]
+    pub fn new(chan: StdoutChannel<T>) -> Self {
+        Self {
+            chan,
+            level: LevelFilter::Trace,
+        }
+    }
+
+    /// Only pass records of `level` and more severe ones
+    #[must_use]
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Install as the logger of the `log` macros and set their maximum
+    /// level to this logger's
+    /// # Errors
+    ///
+    /// Returns `SetLoggerError` if a logger has already been installed
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<T> Log for StdoutChannelLogger<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level && !self.chan.is_closed()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let stream = match record.level() {
+            Level::Error | Level::Warn => Stream::Stderr,
+            _ => Stream::Stdout,
+        };
+        // closing may have raced with `enabled`
+        self.chan.send_quiet(stream, line);
+    }
+
+    /// Lines are written by the writer tasks, `StdoutChannel::close` waits
+    /// for them
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, Log, Record};
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::StdoutChannelLogger;
+
+    #[tokio::test]
+    async fn test_logger() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        // not installed with `init`, other tests may use the global logger
+        let logger = StdoutChannelLogger::new(chan.clone()).with_level(LevelFilter::Info);
+        for (level, message) in [
+            (Level::Info, "started"),
+            (Level::Warn, "slow query"),
+            (Level::Debug, "hidden"),
+            (Level::Error, "connection lost"),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("app::db")
+                    .args(format_args!("{message}"))
+                    .build(),
+            );
+        }
+        chan.close().await?;
+        // e.g. a library logging during shutdown
+        let late = Record::builder().level(Level::Error).build();
+        assert!(!logger.enabled(late.metadata()));
+        logger.log(&late);
+
+        assert_eq!(stdout.snapshot(), ["INFO  app::db: started"]);
+        assert_eq!(
+            stderr.snapshot(),
+            [
+                "WARN  app::db: slow query",
+                "ERROR app::db: connection lost"
+            ]
+        );
+        Ok(())
+    }
+}
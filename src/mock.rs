@@ -0,0 +1,171 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::StdoutChannelError;
+
+/// Where a `MockStdout` puts the lines it captures
+pub trait MockStore<T>: Send {
+    /// # Errors
+    ///
+    /// An error is returned from `StdoutChannel::close`
+    fn push(&mut self, item: T) -> Result<(), StdoutChannelError>;
+}
+
+impl<T: Send> MockStore<T> for Vec<T> {
+    fn push(&mut self, item: T) -> Result<(), StdoutChannelError> {
+        Vec::push(self, item);
+        Ok(())
+    }
+}
+
+/// Forward every line to a test harness, lines sent after the receiver is
+/// dropped are discarded
+impl<T: Send> MockStore<T> for UnboundedSender<T> {
+    fn push(&mut self, item: T) -> Result<(), StdoutChannelError> {
+        self.send(item).ok();
+        Ok(())
+    }
+}
+
+/// Keeps only the last `capacity` lines
+#[derive(Debug)]
+pub struct RingStore<T> {
+    lines: VecDeque<T>,
+    capacity: usize,
+    dropped: usize,
+}
+
+impl<T> RingStore<T> {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> &VecDeque<T> {
+        &self.lines
+    }
+
+    /// Number of lines pushed out of the buffer so far
+    #[must_use]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl<T: Send> MockStore<T> for RingStore<T> {
+    fn push(&mut self, item: T) -> Result<(), StdoutChannelError> {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return Ok(());
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(item);
+        Ok(())
+    }
+}
+
+/// Writes every line straight through to a file instead of keeping it in
+/// memory
+#[derive(Debug)]
+pub struct FileStore {
+    file: File,
+    path: PathBuf,
+    lines: usize,
+}
+
+impl FileStore {
+    /// # Errors
+    ///
+    /// Will error if the file can't be created
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, StdoutChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path)?;
+        Ok(Self {
+            file,
+            path,
+            lines: 0,
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// # Errors
+    ///
+    /// Will error if the file can't be read
+    pub fn read_to_string(&self) -> Result<String, StdoutChannelError> {
+        Ok(std::fs::read_to_string(&self.path)?)
+    }
+}
+
+impl<T: Display> MockStore<T> for FileStore {
+    fn push(&mut self, item: T) -> Result<(), StdoutChannelError> {
+        writeln!(self.file, "{item}")?;
+        self.lines += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{FileStore, RingStore};
+
+    #[tokio::test]
+    async fn test_mock_stores() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("mock-store.log");
+        let ring = MockStdout::with_store(RingStore::new(2));
+        let file = MockStdout::with_store(FileStore::create(&path)?);
+        let chan = StdoutChannel::<String>::with_mock_stdout(ring.clone(), file.clone());
+        for i in 0..5 {
+            chan.send(format!("out {i}"));
+            chan.send_err(format!("err {i}"));
+        }
+        chan.close().await?;
+
+        let ring = ring.lock().await;
+        assert_eq!(ring.lines(), &["out 3", "out 4"]);
+        assert_eq!(ring.dropped(), 3);
+        let file = file.lock().await;
+        assert_eq!(file.lines(), 5);
+        assert_eq!(
+            file.read_to_string()?,
+            "err 0\nerr 1\nerr 2\nerr 3\nerr 4\n"
+        );
+
+        let (tx, mut rx) = unbounded_channel();
+        let chan = StdoutChannel::<String>::with_mock_stdout(
+            MockStdout::with_store(tx),
+            MockStdout::new(),
+        );
+        chan.send("forwarded");
+        assert_eq!(rx.recv().await.as_deref(), Some("forwarded"));
+        chan.close().await?;
+        Ok(())
+    }
+}
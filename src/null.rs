@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use crate::{describe::ChannelStats, sync, StdoutChannel, WriterTask};
+
+impl<T> StdoutChannel<T> {
+    /// Create a channel that drops every line, for code taking a channel
+    /// when the caller wants no output, e.g. with `--quiet`. Dropped lines
+    /// are counted as filtered. No writer tasks are spawned, so this works
+    /// outside a runtime.
+    #[must_use]
+    pub fn null() -> Self {
+        let mut chan = Self::from_parts(
+            [Arc::default(), Arc::default()],
+            [
+                Arc::new(sync::Mutex::new(WriterTask::Taken)),
+                Arc::new(sync::Mutex::new(WriterTask::Taken)),
+            ],
+            Arc::new(ChannelStats::new("null", "null")),
+            Arc::default(),
+            Arc::default(),
+        );
+        chan.null = true;
+        chan
+    }
+
+    /// Whether this channel was created with `null`
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        self.null
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StdoutChannel, StdoutChannelError};
+
+    #[test]
+    fn test_null() -> Result<(), StdoutChannelError> {
+        // no runtime needed to create and use it
+        let chan = StdoutChannel::<String>::null();
+        assert!(chan.is_null() && chan.clone().is_null());
+        chan.send("dropped");
+        chan.send_err("dropped too");
+        assert_eq!(chan.describe().stdout.queued, 0);
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        let stats = runtime.block_on(chan.close_with_stats())?;
+        assert_eq!(stats.stdout.lines_filtered, 1);
+        assert_eq!(stats.stderr.lines_filtered, 1);
+        assert_eq!(stats.stdout.lines_written, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use deadqueue::unlimited::Queue;
+use std::{fmt::Display, sync::Arc};
+
+use crate::{
+    describe::ChannelStats,
+    sink::{
+        stdio::{StderrSink, StdoutSink},
+        OutputLine, OutputSink, SinkFuture, Stream,
+    },
+    sync, StdoutChannel, StdoutQueue, TaskContext, WriterTask,
+};
+
+/// The sinks of an ordered channel, written by its single writer task
+struct OrderedSinks<O, E> {
+    stdout: O,
+    stderr: E,
+}
+
+impl<T, O, E> OutputSink<T> for OrderedSinks<O, E>
+where
+    O: OutputSink<T>,
+    E: OutputSink<T>,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        match line.stream() {
+            Stream::Stdout => self.stdout.write(line),
+            Stream::Stderr => self.stderr.write(line),
+        }
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            self.stdout.flush().await?;
+            self.stderr.flush().await
+        })
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            self.stdout.close().await?;
+            self.stderr.close().await
+        })
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Create a channel whose stdout and stderr lines share one queue and
+    /// writer task, so lines are written in the order they were sent across
+    /// both streams: `send("start"); send_err("failed")` can't appear
+    /// reversed when both are redirected to the same file.
+    ///
+    /// `describe` reports the shared queue length for both streams, and
+    /// `OverflowPolicy::DropOldest` evicts the oldest line of either one.
+    #[must_use]
+    pub fn ordered() -> Self {
+        let stats = ChannelStats::new("stdout", "stderr").with_process_streams([true; 2]);
+        Self::ordered_parts(StdoutSink::new(), StderrSink::new(), stats)
+    }
+
+    /// Same as `ordered` writing each stream to a custom `OutputSink`
+    #[must_use]
+    pub fn ordered_with_sinks<O, E>(stdout_sink: O, stderr_sink: E) -> Self
+    where
+        O: OutputSink<T> + 'static,
+        E: OutputSink<T> + 'static,
+    {
+        let stats = ChannelStats::for_sinks(&stdout_sink, &stderr_sink);
+        Self::ordered_parts(stdout_sink, stderr_sink, stats)
+    }
+
+    fn ordered_parts<O, E>(stdout: O, stderr: E, stats: ChannelStats) -> Self
+    where
+        O: OutputSink<T> + 'static,
+        E: OutputSink<T> + 'static,
+    {
+        let queue: Arc<StdoutQueue<T>> = Queue::new().into();
+        let incidents = Arc::default();
+        let stats = Arc::new(stats);
+        let control = Arc::default();
+        let task = Arc::new(sync::Mutex::new(WriterTask::new({
+            // lines are counted by the stream they were sent to
+            let cx = TaskContext::new(&queue, Stream::Stdout, &incidents, &stats, &control);
+            let sinks = OrderedSinks { stdout, stderr };
+            async move { Self::process_sink(&cx, sinks).await }
+        })));
+        Self::from_parts(
+            [Arc::clone(&queue), queue],
+            [Arc::clone(&task), task],
+            stats,
+            incidents,
+            control,
+        )
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Whether this channel was created with `ordered` or
+    /// `ordered_with_sinks`
+    #[must_use]
+    pub fn is_ordered(&self) -> bool {
+        Arc::ptr_eq(&self.stdout_queue, &self.stderr_queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_ordered() -> Result<(), StdoutChannelError> {
+        // both streams captured in one store, like a file both are
+        // redirected to
+        let combined = MockStdout::<String>::new();
+        let chan = StdoutChannel::ordered_with_sinks(combined.clone(), combined.clone());
+        assert!(chan.is_ordered());
+        let mut expected = Vec::new();
+        for i in 0..50 {
+            chan.send(format!("start {i}"));
+            chan.send_err(format!("failed {i}"));
+            expected.push(format!("start {i}"));
+            expected.push(format!("failed {i}"));
+        }
+        chan.flush().await;
+        assert_eq!(combined.snapshot(), expected);
+        chan.close().await?;
+        assert!(chan.describe().stderr.closed);
+        assert!(!StdoutChannel::<String>::new().is_ordered());
+        Ok(())
+    }
+}
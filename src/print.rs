@@ -0,0 +1,188 @@
+use std::{
+    fmt::{self, Display},
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::AsyncWrite;
+
+use crate::{sink::Stream, StdoutChannel};
+
+/// Format a line and send it to stdout like `println!`, e.g.
+/// `ch_println!(chan, "copied {} files", n)`
+#[macro_export]
+macro_rules! ch_println {
+    ($chan:expr) => {
+        $chan.send(::std::string::String::new())
+    };
+    ($chan:expr, $($arg:tt)+) => {
+        $chan.send(::std::format!($($arg)+))
+    };
+}
+
+/// Format a line and send it to stderr like `eprintln!`, e.g.
+/// `ch_eprintln!(chan, "error: {}", e)`
+#[macro_export]
+macro_rules! ch_eprintln {
+    ($chan:expr) => {
+        $chan.send_err(::std::string::String::new())
+    };
+    ($chan:expr, $($arg:tt)+) => {
+        $chan.send_err(::std::format!($($arg)+))
+    };
+}
+
+/// A `fmt::Write` for `write!` and `writeln!` and a `tokio::io::AsyncWrite`
+/// for APIs like `tokio::io::copy`, sending each line once its newline is
+/// written. A line left without one is sent on `shutdown` or when the
+/// writer is dropped.
+pub struct ChannelWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    chan: StdoutChannel<T>,
+    stream: Stream,
+    buf: Vec<u8>,
+}
+
+impl<T> ChannelWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while let Some(i) = rest.iter().position(|&b| b == b'\n') {
+            self.buf.extend_from_slice(&rest[..i]);
+            self.send_buf();
+            rest = &rest[i + 1..];
+        }
+        self.buf.extend_from_slice(rest);
+    }
+
+    fn send_buf(&mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        let line = String::from_utf8(buf)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+        // a writer dropped after `close` loses its partial line
+        self.chan.send_quiet(self.stream, line);
+    }
+}
+
+impl<T> fmt::Write for ChannelWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<T> AsyncWrite for ChannelWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write_bytes(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Lines are queued as soon as they end, `StdoutChannel::close` waits
+    /// for them to be written
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.buf.is_empty() {
+            this.send_buf();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Drop for ChannelWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.send_buf();
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// A writer sending lines to stdout
+    #[must_use]
+    pub fn writer(&self) -> ChannelWriter<T> {
+        self.stream_writer(Stream::Stdout)
+    }
+
+    /// A writer sending lines to stderr
+    #[must_use]
+    pub fn err_writer(&self) -> ChannelWriter<T> {
+        self.stream_writer(Stream::Stderr)
+    }
+
+    fn stream_writer(&self, stream: Stream) -> ChannelWriter<T> {
+        ChannelWriter {
+            chan: self.clone(),
+            stream,
+            buf: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_print_macros() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let n = 3;
+        ch_println!(chan, "copied {} files", n);
+        ch_println!(chan);
+        ch_eprintln!(chan, "error: {n:>3}");
+        {
+            let mut out = chan.writer();
+            write!(out, "a").unwrap();
+            writeln!(out, "b {}", 1).unwrap();
+            write!(out, "c\nd").unwrap();
+        }
+        writeln!(chan.err_writer(), "warning: {n} left").unwrap();
+
+        let mut input: &[u8] = b"from\nchild\xff\npartial";
+        let mut err = chan.err_writer();
+        tokio::io::copy(&mut input, &mut err).await?;
+        err.shutdown().await?;
+        chan.close().await?;
+
+        assert_eq!(stdout.snapshot(), ["copied 3 files", "", "ab 1", "c", "d"]);
+        assert_eq!(
+            stderr.snapshot(),
+            [
+                "error:   3",
+                "warning: 3 left",
+                "from",
+                "child\u{fffd}",
+                "partial"
+            ]
+        );
+        Ok(())
+    }
+}
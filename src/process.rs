@@ -0,0 +1,112 @@
+use std::fmt::Display;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Child,
+    task::JoinHandle,
+};
+
+use crate::{sink::Stream, StdoutChannel, StdoutChannelError};
+
+/// The tasks forwarding a child's output, returned by
+/// `StdoutChannel::pipe_child`
+pub struct PipedChild {
+    stdout: Option<JoinHandle<Result<u64, StdoutChannelError>>>,
+    stderr: Option<JoinHandle<Result<u64, StdoutChannelError>>>,
+}
+
+impl PipedChild {
+    /// Wait until the child closed its stdout and stderr and every line has
+    /// been sent, returns the number of lines sent from each
+    /// # Errors
+    ///
+    /// Will error if reading either stream failed
+    pub async fn wait(self) -> Result<(u64, u64), StdoutChannelError> {
+        let mut lines = (0, 0);
+        if let Some(task) = self.stdout {
+            lines.0 = task.await??;
+        }
+        if let Some(task) = self.stderr {
+            lines.1 = task.await??;
+        }
+        Ok(lines)
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Forward the stdout and stderr of `child` line by line to the queues
+    /// of the same streams. Only streams spawned with `Stdio::piped()` are
+    /// read, they are taken from `child`.
+    pub fn pipe_child(&self, child: &mut Child) -> PipedChild {
+        self.pipe_child_with_prefix(child, "")
+    }
+
+    /// `pipe_child` writing `prefix` before every line, e.g. `[build] `
+    pub fn pipe_child_with_prefix(&self, child: &mut Child, prefix: &str) -> PipedChild {
+        PipedChild {
+            stdout: child
+                .stdout
+                .take()
+                .map(|out| self.spawn_pipe(out, Stream::Stdout, prefix)),
+            stderr: child
+                .stderr
+                .take()
+                .map(|err| self.spawn_pipe(err, Stream::Stderr, prefix)),
+        }
+    }
+
+    fn spawn_pipe(
+        &self,
+        reader: impl AsyncRead + Unpin + Send + 'static,
+        stream: Stream,
+        prefix: &str,
+    ) -> JoinHandle<Result<u64, StdoutChannelError>> {
+        let chan = self.clone();
+        let prefix = prefix.to_string();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            let mut buf = Vec::new();
+            let mut lines = 0;
+            while reader.read_until(b'\n', &mut buf).await? > 0 {
+                let bytes = buf.strip_suffix(b"\n").unwrap_or(&buf);
+                let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+                let line = format!("{prefix}{}", String::from_utf8_lossy(bytes));
+                if chan.send_quiet(stream, line) {
+                    lines += 1;
+                }
+                buf.clear();
+            }
+            Ok(lines)
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Stdio;
+    use tokio::process::Command;
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_pipe_child() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        let mut child = Command::new("sh")
+            .args(["-c", "echo one; echo failed >&2; printf 'two\\r\\nthree'"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let piped = chan.pipe_child_with_prefix(&mut child, "[sh] ");
+        assert!(child.wait().await?.success());
+        assert_eq!(piped.wait().await?, (3, 1));
+        chan.close().await?;
+
+        assert_eq!(stdout.snapshot(), ["[sh] one", "[sh] two", "[sh] three"]);
+        assert_eq!(stderr.snapshot(), ["[sh] failed"]);
+        Ok(())
+    }
+}
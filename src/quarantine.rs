@@ -0,0 +1,139 @@
+use std::{fmt, io::Write, panic::AssertUnwindSafe};
+
+use crate::{
+    error_chain, incident::panic_message, sink::Stream, StdoutChannel, StdoutChannelError,
+};
+
+/// Times a writer task tries to render a line before quarantining it
+const MAX_RENDER_ATTEMPTS: usize = 3;
+
+/// A line that failed to render every time, kept out of the stream so the
+/// lines behind it are still written
+pub struct Quarantined<T> {
+    pub stream: Stream,
+    pub item: T,
+    /// The last formatting error or panic message
+    pub reason: String,
+}
+
+impl<T> fmt::Debug for Quarantined<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Quarantined")
+            .field("stream", &self.stream)
+            .field("reason", &self.reason)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Call `render` on `item` until it succeeds, returning the reason of the
+/// last failure if none of the attempts do. Panics count as failures.
+pub(crate) fn render<T>(
+    item: &T,
+    mut render: impl FnMut(&T) -> Result<(), StdoutChannelError>,
+) -> Result<(), String> {
+    let mut reason = String::new();
+    for _ in 0..MAX_RENDER_ATTEMPTS {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| render(item))) {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(e)) => reason = error_chain(&e),
+            Err(panic) => reason = format!("panicked: {}", panic_message(&*panic)),
+        }
+    }
+    Err(reason)
+}
+
+impl<T> StdoutChannel<T> {
+    /// Number of quarantined lines not yet taken with `take_quarantined`
+    #[must_use]
+    pub fn quarantined(&self) -> usize {
+        self.incidents.quarantine.lock().len()
+    }
+
+    /// Take the lines quarantined so far. Lines still quarantined when the
+    /// channel is closed are listed on the process stderr.
+    #[must_use]
+    pub fn take_quarantined(&self) -> Vec<Quarantined<T>> {
+        std::mem::take(&mut *self.incidents.quarantine.lock())
+    }
+
+    pub(crate) fn dump_quarantined(&self) {
+        let quarantined = self.take_quarantined();
+        if quarantined.is_empty() {
+            return;
+        }
+        let mut stderr = std::io::stderr().lock();
+        writeln!(
+            stderr,
+            "stdout-channel: {} lines quarantined",
+            quarantined.len()
+        )
+        .ok();
+        for q in quarantined {
+            writeln!(stderr, "  {}: {}", q.stream, q.reason).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{sink::Stream, MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Fragile {
+        Fine(&'static str),
+        Panics,
+        Fails,
+    }
+
+    impl fmt::Display for Fragile {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Fine(s) => f.write_str(s),
+                Self::Panics => panic!("bad formatter"),
+                Self::Fails => Err(fmt::Error),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantine() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<Fragile>::new();
+        let chan = StdoutChannel::with_sinks(stdout.clone(), MockStdout::new());
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        chan.set_error_hook({
+            let errors = Arc::clone(&errors);
+            move |e| errors.lock().unwrap().push(e.to_string())
+        });
+        for item in [
+            Fragile::Fine("a"),
+            Fragile::Panics,
+            Fragile::Fails,
+            Fragile::Fine("b"),
+        ] {
+            chan.send(item);
+        }
+        chan.flush().await;
+        assert_eq!(stdout.snapshot(), [Fragile::Fine("a"), Fragile::Fine("b")]);
+        assert_eq!(chan.quarantined(), 2);
+        assert_eq!(chan.panics(), 0);
+        let errors = errors.lock().unwrap().clone();
+        assert_eq!(
+            errors[0],
+            "stdout line quarantined: panicked: bad formatter"
+        );
+        // std panics when a Display impl returns an error of its own
+        assert!(errors[1].starts_with("stdout line quarantined: panicked: a formatting trait"));
+
+        let quarantined = chan.take_quarantined();
+        assert_eq!(quarantined[0].item, Fragile::Panics);
+        assert_eq!(quarantined[1].stream, Stream::Stdout);
+        assert_eq!(chan.quarantined(), 0);
+        chan.close().await?;
+        Ok(())
+    }
+}
@@ -1,51 +1,418 @@
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+pub mod backend;
+pub mod quota;
+#[cfg(feature = "redis")]
+pub mod redis_backend;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    hash::Hash,
+    io,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    sync::Notify,
+    sync::{Notify, OwnedSemaphorePermit, Semaphore},
     task::{spawn, JoinHandle},
-    time::{sleep, Duration},
+    time::{sleep, sleep_until, Duration},
 };
 
+use crate::{sync::Mutex, StdoutChannelError};
+
+/// Permits are refilled by a task spawned on the first acquire, so a
+/// limiter can be created outside a runtime. The task is aborted once the
+/// last clone of the limiter is dropped.
 #[derive(Clone)]
 pub struct RateLimiter {
     inner: Arc<RateLimiterInner>,
-    #[allow(dead_code)]
-    rate_task: Arc<JoinHandle<()>>,
+    refill: Arc<RefillTask>,
+    concurrency: Option<Arc<Semaphore>>,
+    jitter: Option<Duration>,
+    rng: Option<Arc<Mutex<fastrand::Rng>>>,
+    name: Option<Arc<str>>,
+}
+
+/// Held while a rate limited operation is in flight, the concurrency slot
+/// (if any) is released on drop
+#[must_use]
+pub struct RatePermit {
+    _slot: Option<OwnedSemaphorePermit>,
 }
 
 impl RateLimiter {
+    /// Allow `max_per_unit_time` permits every `unit_time_ms`. Works outside
+    /// a runtime, the refill task is spawned by the first acquire.
     #[must_use]
     pub fn new(max_per_unit_time: usize, unit_time_ms: usize) -> Self {
-        let inner = Arc::new(RateLimiterInner::new(max_per_unit_time, unit_time_ms));
-        let rate_task = Arc::new({
-            let inner = inner.clone();
-            spawn(async move {
-                inner.check_reset().await;
-            })
+        Self::smoothed(max_per_unit_time, unit_time_ms, 1)
+    }
+
+    /// Refill the permits in `steps` equal portions spread over each unit of
+    /// time instead of all at once at the start of it
+    #[must_use]
+    pub fn smoothed(max_per_unit_time: usize, unit_time_ms: usize, steps: usize) -> Self {
+        let inner = RateLimiterInner::new(max_per_unit_time, unit_time_ms, steps.max(1));
+        let first_refill = inner.refill_interval();
+        Self::with_refill(inner, first_refill)
+    }
+
+    /// Continue from a state saved by a previous process. If the saved
+    /// window hasn't ended yet only its remaining permits are available until
+    /// the next refill, otherwise the limiter starts out full.
+    #[must_use]
+    pub fn restore(max_per_unit_time: usize, unit_time_ms: usize, state: RateLimiterState) -> Self {
+        Self::restore_smoothed(max_per_unit_time, unit_time_ms, 1, state)
+    }
+
+    /// `restore` a limiter created with `smoothed`. The refills due since the
+    /// state was saved are added to its remaining permits and the next one
+    /// happens when it would have in the previous process.
+    #[must_use]
+    pub fn restore_smoothed(
+        max_per_unit_time: usize,
+        unit_time_ms: usize,
+        steps: usize,
+        state: RateLimiterState,
+    ) -> Self {
+        let inner = RateLimiterInner::new(max_per_unit_time, unit_time_ms, steps.max(1));
+        let step_time = inner.refill_interval();
+        let elapsed = SystemTime::now()
+            .duration_since(state.window_start)
+            .unwrap_or_default();
+        let step_nanos = step_time.as_nanos().max(1);
+        let refills = usize::try_from(elapsed.as_nanos() / step_nanos).unwrap_or(usize::MAX);
+        if refills >= inner.steps {
+            return Self::with_refill(inner, step_time);
+        }
+        let into_step =
+            Duration::from_nanos(u64::try_from(elapsed.as_nanos() % step_nanos).unwrap_or(0));
+        let refilled = max_per_unit_time * refills / inner.steps;
+        inner.remaining.store(
+            (state.remaining + refilled).min(max_per_unit_time),
+            Ordering::SeqCst,
+        );
+        inner.window_start_ms.store(
+            unix_millis(state.window_start + elapsed.saturating_sub(into_step)),
+            Ordering::SeqCst,
+        );
+        Self::with_refill(inner, step_time.saturating_sub(into_step))
+    }
+
+    /// Snapshot of the current window, to be saved and passed to `restore`
+    #[must_use]
+    pub fn state(&self) -> RateLimiterState {
+        RateLimiterState {
+            remaining: self.inner.remaining.load(Ordering::SeqCst),
+            window_start: UNIX_EPOCH
+                + Duration::from_millis(self.inner.window_start_ms.load(Ordering::SeqCst)),
+        }
+    }
+
+    /// Write `state()` to `path`
+    /// # Errors
+    ///
+    /// Will error if the file can't be written
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), StdoutChannelError> {
+        tokio::fs::write(path, self.state().to_string()).await?;
+        Ok(())
+    }
+
+    /// `restore` from a file written by `save`, or start out full if there is
+    /// no such file
+    /// # Errors
+    ///
+    /// Will error if the file exists but can't be read or parsed
+    pub async fn load(
+        max_per_unit_time: usize,
+        unit_time_ms: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, StdoutChannelError> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(s) => Ok(Self::restore(max_per_unit_time, unit_time_ms, s.parse()?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::new(max_per_unit_time, unit_time_ms))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// A limiter whose first refill is `first_refill` from now
+    fn with_refill(inner: RateLimiterInner, first_refill: Duration) -> Self {
+        let inner = Arc::new(inner);
+        let refill = Arc::new(RefillTask {
+            inner: Arc::clone(&inner),
+            first_refill: Instant::now() + first_refill,
+            task: OnceLock::new(),
         });
-        Self { inner, rate_task }
+        Self {
+            inner,
+            refill,
+            concurrency: None,
+            jitter: None,
+            rng: None,
+            name: None,
+        }
+    }
+
+    /// Delay each acquire by a random duration up to `max_jitter`, so many
+    /// processes throttled the same way don't fire in lockstep
+    #[must_use]
+    pub fn with_jitter(mut self, max_jitter: Duration) -> Self {
+        self.jitter = Some(max_jitter);
+        self
+    }
+
+    /// Draw the jitter from an RNG seeded with `seed` so runs are
+    /// reproducible
+    #[must_use]
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.rng = Some(Arc::new(Mutex::new(fastrand::Rng::with_seed(seed))));
+        self
+    }
+
+    /// Also allow at most `max_in_flight` `RatePermit`s to be held at once
+    #[must_use]
+    pub fn with_concurrency(mut self, max_in_flight: usize) -> Self {
+        self.concurrency = Some(Arc::new(Semaphore::new(max_in_flight)));
+        self
+    }
+
+    /// Make every acquire wait until `until`, e.g. the time given by the
+    /// `Retry-After` header of a 429 response. An earlier cooldown never
+    /// shortens one already in place.
+    pub fn cooldown_until(&self, until: Instant) {
+        let mut cooldown = self.inner.cooldown_until.lock();
+        *cooldown = Some(cooldown.map_or(until, |c| c.max(until)));
+    }
+
+    /// `cooldown_until` `penalty` from now
+    pub fn penalize(&self, penalty: Duration) {
+        self.cooldown_until(Instant::now() + penalty);
     }
 
+    /// Wait for the next time based permit, ignores the concurrency limit
     pub async fn acquire(&self) {
+        self.refill.start();
+        self.inner.acquire().await;
+        self.apply_jitter().await;
+    }
+
+    /// Wait for `n` permits, e.g. one per byte when pacing output by bytes
+    /// per second. More than `max_per_unit_time` permits are taken over
+    /// several units of time.
+    pub async fn acquire_n(&self, n: usize) {
+        self.refill.start();
+        self.inner.acquire_n(n).await;
+        self.apply_jitter().await;
+    }
+
+    /// Wait for both a free concurrency slot and a time based permit
+    pub async fn acquire_permit(&self) -> RatePermit {
+        let slot = match &self.concurrency {
+            Some(semaphore) => Arc::clone(semaphore).acquire_owned().await.ok(),
+            None => None,
+        };
+        self.refill.start();
         self.inner.acquire().await;
+        self.apply_jitter().await;
+        RatePermit { _slot: slot }
+    }
+
+    async fn apply_jitter(&self) {
+        if let Some(max_jitter) = self.jitter {
+            let nanos = u64::try_from(max_jitter.as_nanos()).unwrap_or(u64::MAX);
+            let nanos = match &self.rng {
+                Some(rng) => rng.lock().u64(0..=nanos),
+                None => fastrand::u64(0..=nanos),
+            };
+            sleep(Duration::from_nanos(nanos)).await;
+        }
+    }
+}
+
+/// The refill task of a `RateLimiter`, shared by its clones
+struct RefillTask {
+    inner: Arc<RateLimiterInner>,
+    first_refill: Instant,
+    task: OnceLock<JoinHandle<()>>,
+}
+
+impl RefillTask {
+    fn start(&self) {
+        self.task.get_or_init(|| {
+            let inner = Arc::clone(&self.inner);
+            let first_refill = self.first_refill.saturating_duration_since(Instant::now());
+            spawn(async move { inner.check_reset(first_refill).await })
+        });
+    }
+}
+
+impl Drop for RefillTask {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.get() {
+            task.abort();
+        }
+    }
+}
+
+/// Permits left in the current window of a `RateLimiter`, serialized as
+/// `<remaining> <window start in unix milliseconds>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimiterState {
+    pub remaining: usize,
+    pub window_start: SystemTime,
+}
+
+impl fmt::Display for RateLimiterState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.remaining, unix_millis(self.window_start))
+    }
+}
+
+impl FromStr for RateLimiterState {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid rate limiter state");
+        let mut fields = s.split_whitespace();
+        let remaining = fields
+            .next()
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(invalid)?;
+        let start_ms: u64 = fields
+            .next()
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            remaining,
+            window_start: UNIX_EPOCH + Duration::from_millis(start_ms),
+        })
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    u64::try_from(millis).unwrap_or(u64::MAX)
+}
+
+/// A global limit shared by all keys plus a separate limit per key, an
+/// acquire has to get a permit from both (e.g. 100 requests per second in
+/// total but at most 10 per customer).
+///
+/// Per key limiters are created on first use. Once one has been full and
+/// unused for a whole unit of time it's dropped by a later acquire, `remove`
+/// drops it right away. Either stops its refill task once no acquire for
+/// the key is in flight.
+#[derive(Clone)]
+pub struct HierarchicalRateLimiter<K> {
+    global: RateLimiter,
+    per_key: Arc<Mutex<PerKey<K>>>,
+    max_per_key: usize,
+    unit_time_ms: usize,
+}
+
+struct PerKey<K> {
+    limiters: HashMap<K, KeyLimiter>,
+    last_sweep: Instant,
+}
+
+struct KeyLimiter {
+    limiter: RateLimiter,
+    last_used: Instant,
+}
+
+impl KeyLimiter {
+    /// Whether the limiter has all its permits and nobody used it since
+    /// `idle_since`
+    fn is_idle(&self, idle_since: Instant) -> bool {
+        let inner = &self.limiter.inner;
+        self.last_used <= idle_since
+            && Arc::strong_count(&self.limiter.refill) == 1
+            && inner.remaining.load(Ordering::SeqCst) >= inner.max_per_unit_time
+    }
+}
+
+impl<K> HierarchicalRateLimiter<K>
+where
+    K: Hash + Eq + Clone,
+{
+    #[must_use]
+    pub fn new(max_global: usize, max_per_key: usize, unit_time_ms: usize) -> Self {
+        Self {
+            global: RateLimiter::new(max_global, unit_time_ms),
+            per_key: Arc::new(Mutex::new(PerKey {
+                limiters: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+            max_per_key,
+            unit_time_ms,
+        }
+    }
+
+    /// Wait for a permit for `key`, the per key permit is taken first so a
+    /// busy key can't hold up global permits while it is throttled
+    pub async fn acquire(&self, key: &K) {
+        let limiter = {
+            let mut per_key = self.per_key.lock();
+            let now = Instant::now();
+            let window = Duration::from_millis(self.unit_time_ms as u64);
+            // at most once per unit of time, so acquires stay cheap
+            if let Some(idle_since) = now.checked_sub(window) {
+                if per_key.last_sweep <= idle_since {
+                    per_key.limiters.retain(|_, l| !l.is_idle(idle_since));
+                    per_key.last_sweep = now;
+                }
+            }
+            let entry = per_key
+                .limiters
+                .entry(key.clone())
+                .or_insert_with(|| KeyLimiter {
+                    limiter: RateLimiter::new(self.max_per_key, self.unit_time_ms),
+                    last_used: now,
+                });
+            entry.last_used = now;
+            entry.limiter.clone()
+        };
+        limiter.acquire().await;
+        self.global.acquire().await;
+    }
+
+    /// Drop the limiter of `key`, e.g. once a customer's session ended
+    pub fn remove(&self, key: &K) {
+        self.per_key.lock().limiters.remove(key);
     }
 }
 
 struct RateLimiterInner {
     max_per_unit_time: usize,
     unit_time_ms: usize,
+    steps: usize,
     remaining: AtomicUsize,
+    window_start_ms: AtomicU64,
+    cooldown_until: Mutex<Option<Instant>>,
     notify: Notify,
 }
 
 impl RateLimiterInner {
-    fn new(max_per_unit_time: usize, unit_time_ms: usize) -> Self {
+    fn new(max_per_unit_time: usize, unit_time_ms: usize, steps: usize) -> Self {
         Self {
             max_per_unit_time,
             unit_time_ms,
+            steps,
             remaining: AtomicUsize::new(max_per_unit_time),
+            window_start_ms: AtomicU64::new(unix_millis(SystemTime::now())),
+            cooldown_until: Mutex::new(None),
             notify: Notify::new(),
         }
     }
@@ -64,8 +431,40 @@ impl RateLimiterInner {
             .is_ok()
     }
 
+    fn cooldown(&self) -> Option<Instant> {
+        let mut cooldown = self.cooldown_until.lock();
+        if cooldown.is_some_and(|until| until > Instant::now()) {
+            return *cooldown;
+        }
+        *cooldown = None;
+        None
+    }
+
+    async fn acquire_n(&self, mut needed: usize) {
+        while needed > 0 {
+            if let Some(until) = self.cooldown() {
+                sleep_until(until.into()).await;
+                continue;
+            }
+            let taken = self
+                .remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                    (x > 0).then(|| x - x.min(needed))
+                })
+                .map_or(0, |x| x.min(needed));
+            needed -= taken;
+            if needed > 0 {
+                self.notify.notified().await;
+            }
+        }
+    }
+
     async fn acquire(&self) {
         loop {
+            if let Some(until) = self.cooldown() {
+                sleep_until(until.into()).await;
+                continue;
+            }
             if self.decrement_remaining() {
                 return;
             }
@@ -73,12 +472,42 @@ impl RateLimiterInner {
         }
     }
 
-    async fn check_reset(&self) {
-        loop {
-            self.remaining
-                .fetch_max(self.max_per_unit_time, Ordering::SeqCst);
+    fn start_window(&self) {
+        self.window_start_ms
+            .store(unix_millis(SystemTime::now()), Ordering::SeqCst);
+    }
+
+    fn refill_interval(&self) -> Duration {
+        Duration::from_millis(self.unit_time_ms as u64)
+            / u32::try_from(self.steps).unwrap_or(u32::MAX)
+    }
+
+    // the bucket starts out full, so the first refill only happens after
+    // `first_refill`
+    async fn check_reset(&self, first_refill: Duration) {
+        sleep(first_refill).await;
+        if self.steps == 1 {
+            loop {
+                self.remaining
+                    .fetch_max(self.max_per_unit_time, Ordering::SeqCst);
+                self.start_window();
+                self.notify.notify_waiters();
+                sleep(Duration::from_millis(self.unit_time_ms as u64)).await;
+            }
+        }
+        let step_time = self.refill_interval();
+        for step in (0..self.steps).cycle() {
+            let refill = self.max_per_unit_time * (step + 1) / self.steps
+                - self.max_per_unit_time * step / self.steps;
+            let max = self.max_per_unit_time;
+            let _ = self
+                .remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                    Some((x + refill).min(max))
+                });
+            self.start_window();
             self.notify.notify_waiters();
-            sleep(Duration::from_millis(self.unit_time_ms as u64)).await;
+            sleep(step_time).await;
         }
     }
 }
@@ -86,9 +515,12 @@ impl RateLimiterInner {
 #[cfg(test)]
 mod tests {
     use log::debug;
-    use std::sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::SystemTime,
     };
     use time::OffsetDateTime;
     use tokio::{
@@ -96,8 +528,9 @@ mod tests {
         time::{sleep, Duration},
     };
 
-    use crate::rate_limiter::RateLimiter;
+    use crate::rate_limiter::{HierarchicalRateLimiter, RateLimiter, RateLimiterState};
     use crate::StdoutChannelError;
+    use tokio::time::timeout;
 
     #[tokio::test]
     async fn test_rate_limiter() -> Result<(), StdoutChannelError> {
@@ -141,4 +574,154 @@ mod tests {
         assert_eq!(test_count.load(Ordering::SeqCst), 10_000);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_hierarchical_rate_limiter() -> Result<(), StdoutChannelError> {
+        let limiter = HierarchicalRateLimiter::new(3, 2, 10_000);
+        let counts = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+        let tasks: Vec<_> = [0, 0, 0, 1, 1]
+            .iter()
+            .map(|&key: &usize| {
+                let limiter = limiter.clone();
+                let counts = counts.clone();
+                spawn(async move {
+                    limiter.acquire(&key).await;
+                    counts[key].fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        sleep(Duration::from_millis(50)).await;
+
+        let a = counts[0].load(Ordering::SeqCst);
+        let b = counts[1].load(Ordering::SeqCst);
+        assert!(a <= 2 && b <= 2);
+        assert_eq!(a + b, 3);
+        for t in tasks {
+            t.abort();
+        }
+
+        let refilled = Arc::downgrade(&limiter.per_key.lock().limiters[&0].limiter.inner);
+        limiter.remove(&0);
+        sleep(Duration::from_millis(10)).await;
+        // the aborted refill task released the limiter's state
+        assert!(refilled.upgrade().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_keys_evicted() -> Result<(), StdoutChannelError> {
+        let limiter = HierarchicalRateLimiter::new(100, 2, 50);
+        limiter.acquire(&0).await;
+        limiter.acquire(&1).await;
+        assert_eq!(limiter.per_key.lock().limiters.len(), 2);
+        sleep(Duration::from_millis(80)).await;
+        // both were refilled and sat idle for a whole unit of time
+        limiter.acquire(&2).await;
+
+        let per_key = limiter.per_key.lock();
+        assert_eq!(per_key.limiters.keys().collect::<Vec<_>>(), [&2]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_permit() -> Result<(), StdoutChannelError> {
+        let limiter = RateLimiter::new(100, 1000).with_concurrency(2);
+        let first = limiter.acquire_permit().await;
+        let _second = limiter.acquire_permit().await;
+        assert!(timeout(Duration::from_millis(20), limiter.acquire_permit())
+            .await
+            .is_err());
+        drop(first);
+        assert!(timeout(Duration::from_millis(20), limiter.acquire_permit())
+            .await
+            .is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_smoothing_and_jitter() -> Result<(), StdoutChannelError> {
+        let limiter = RateLimiter::smoothed(4, 400, 4);
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        // the next permit arrives after one step, not a whole unit of time
+        assert!(timeout(Duration::from_millis(300), limiter.acquire())
+            .await
+            .is_ok());
+
+        let limiter = RateLimiter::new(100, 1000).with_jitter(Duration::from_millis(10));
+        assert!(timeout(Duration::from_millis(200), async {
+            for _ in 0..5 {
+                limiter.acquire().await;
+            }
+        })
+        .await
+        .is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_persistence() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("rate-limiter");
+        let limiter = RateLimiter::new(5, 60_000);
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(limiter.state().remaining, 2);
+        limiter.save(&path).await?;
+
+        let restored = RateLimiter::load(5, 60_000, &path).await?;
+        restored.acquire().await;
+        restored.acquire().await;
+        assert!(timeout(Duration::from_millis(20), restored.acquire())
+            .await
+            .is_err());
+
+        let expired: RateLimiterState = "0 1000".parse()?;
+        let fresh = RateLimiter::restore(5, 60_000, expired);
+        assert_eq!(fresh.state().remaining, 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_smoothed() -> Result<(), StdoutChannelError> {
+        // saved 50ms into a 100ms refill step with no permits left
+        let state = RateLimiterState {
+            remaining: 0,
+            window_start: SystemTime::now() - Duration::from_millis(50),
+        };
+        let limiter = RateLimiter::restore_smoothed(4, 400, 4, state);
+        let start = std::time::Instant::now();
+        limiter.acquire().await;
+        let elapsed = start.elapsed();
+        // the next step, not the end of the whole unit of time
+        assert!(elapsed >= Duration::from_millis(30));
+        assert!(elapsed < Duration::from_millis(200));
+
+        // two steps since the save refilled two permits
+        let state = RateLimiterState {
+            remaining: 1,
+            window_start: SystemTime::now() - Duration::from_millis(250),
+        };
+        let limiter = RateLimiter::restore_smoothed(4, 400, 4, state);
+        assert_eq!(limiter.state().remaining, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_penalize() -> Result<(), StdoutChannelError> {
+        let limiter = RateLimiter::new(100, 1000);
+        limiter.acquire().await;
+        limiter.penalize(Duration::from_millis(100));
+        // a shorter cooldown doesn't cut the penalty short
+        limiter.penalize(Duration::from_millis(10));
+        let start = std::time::Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        let start = std::time::Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        Ok(())
+    }
 }
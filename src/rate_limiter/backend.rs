@@ -0,0 +1,66 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{
+    rate_limiter::{HierarchicalRateLimiter, RateLimiter},
+    StdoutChannelError,
+};
+
+pub type BackendFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), StdoutChannelError>> + Send + 'a>>;
+
+/// Where the permits of a rate limit are kept, so several instances of a
+/// service sharing one upstream quota can coordinate through e.g. Redis
+/// while a single process uses the in-memory `RateLimiter`.
+pub trait RateLimitBackend: Send + Sync {
+    /// Wait until a permit for `key` has been taken
+    fn acquire<'a>(&'a self, key: &'a str) -> BackendFuture<'a>;
+}
+
+/// A single bucket, the key is ignored
+impl RateLimitBackend for RateLimiter {
+    fn acquire<'a>(&'a self, _key: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            RateLimiter::acquire(self).await;
+            Ok(())
+        })
+    }
+}
+
+/// One bucket per key below the global one
+impl RateLimitBackend for HierarchicalRateLimiter<String> {
+    fn acquire<'a>(&'a self, key: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            HierarchicalRateLimiter::acquire(self, &key.to_string()).await;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+    use tokio::time::timeout;
+
+    use crate::{
+        rate_limiter::{HierarchicalRateLimiter, RateLimiter},
+        StdoutChannelError,
+    };
+
+    use super::RateLimitBackend;
+
+    #[tokio::test]
+    async fn test_in_memory_backends() -> Result<(), StdoutChannelError> {
+        let backends: Vec<Arc<dyn RateLimitBackend>> = vec![
+            Arc::new(RateLimiter::new(2, 60_000)),
+            Arc::new(HierarchicalRateLimiter::<String>::new(10, 2, 60_000)),
+        ];
+        for backend in backends {
+            backend.acquire("api").await?;
+            backend.acquire("api").await?;
+            assert!(timeout(Duration::from_millis(20), backend.acquire("api"))
+                .await
+                .is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+    time::{Duration, SystemTime},
+};
+
+use crate::{rate_limiter::RateLimiter, StdoutChannel};
+
+/// A number of permits per period, e.g. `Quota::per_hour(1000)` for an API
+/// allowing a thousand requests an hour
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quota {
+    name: Option<String>,
+    limit: usize,
+    period: Duration,
+}
+
+impl Quota {
+    #[must_use]
+    pub fn new(limit: usize, period: Duration) -> Self {
+        Self {
+            name: None,
+            limit,
+            period,
+        }
+    }
+
+    #[must_use]
+    pub fn per_second(limit: usize) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    #[must_use]
+    pub fn per_minute(limit: usize) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    #[must_use]
+    pub fn per_hour(limit: usize) -> Self {
+        Self::new(limit, Duration::from_secs(3600))
+    }
+
+    /// Name shown in usage reports
+    #[must_use]
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// Usage of a limiter's current window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+    pub name: Option<String>,
+    pub limit: usize,
+    pub consumed: usize,
+    pub remaining: usize,
+    pub reset_at: SystemTime,
+}
+
+impl Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reset_in = self
+            .reset_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}: {}/{} used, {} remaining, resets in {}s",
+            self.name.as_deref().unwrap_or("quota"),
+            self.consumed,
+            self.limit,
+            self.remaining,
+            reset_in.as_secs()
+        )
+    }
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn from_quota(quota: Quota) -> Self {
+        let unit_time_ms = usize::try_from(quota.period.as_millis()).unwrap_or(usize::MAX);
+        let mut limiter = Self::new(quota.limit, unit_time_ms);
+        limiter.name = quota.name.map(Into::into);
+        limiter
+    }
+
+    #[must_use]
+    pub fn usage(&self) -> Usage {
+        let limit = self.inner.max_per_unit_time;
+        let state = self.state();
+        let remaining = state.remaining.min(limit);
+        Usage {
+            name: self.name.as_deref().map(Into::into),
+            limit,
+            consumed: limit - remaining,
+            remaining,
+            reset_at: state.window_start + self.inner.refill_interval(),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Print the usage of `limiter` to stderr when the channel is closed
+    pub fn report_quota(&self, limiter: &RateLimiter) {
+        let limiter = limiter.clone();
+        self.add_close_report(move || limiter.usage().to_string().into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{Quota, RateLimiter};
+
+    #[tokio::test]
+    async fn test_quota_usage() -> Result<(), StdoutChannelError> {
+        let limiter = RateLimiter::from_quota(Quota::per_hour(1000).named("github"));
+        for _ in 0..12 {
+            limiter.acquire().await;
+        }
+        let usage = limiter.usage();
+        assert_eq!(usage.name.as_deref(), Some("github"));
+        assert_eq!((usage.consumed, usage.remaining), (12, 988));
+
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::new(), stderr.clone());
+        chan.report_quota(&limiter);
+        chan.close().await?;
+        let report = stderr.lock().await;
+        assert_eq!(report.len(), 1);
+        assert!(report[0].starts_with("github: 12/1000 used, 988 remaining, resets in 35"));
+        Ok(())
+    }
+}
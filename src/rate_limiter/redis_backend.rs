@@ -0,0 +1,80 @@
+use redis::{aio::ConnectionManager, Client, Script};
+use std::convert::TryFrom;
+use tokio::time::{sleep, Duration};
+
+use crate::{
+    rate_limiter::backend::{BackendFuture, RateLimitBackend},
+    StdoutChannelError,
+};
+
+// Count the permit in the key's current window, returning the count and the
+// time left in the window
+const ACQUIRE_SCRIPT: &str = r"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[1])
+end
+return {count, redis.call('PTTL', KEYS[1])}
+";
+
+/// Fixed window limiter shared through Redis: at most `max_per_window`
+/// permits per key every `window_ms` milliseconds across all processes using
+/// the same server and prefix.
+#[derive(Clone)]
+pub struct RedisRateLimiter {
+    connection: ConnectionManager,
+    script: Script,
+    prefix: String,
+    max_per_window: u64,
+    window_ms: u64,
+}
+
+impl RedisRateLimiter {
+    /// # Errors
+    ///
+    /// Will error if the connection to Redis can't be established
+    pub async fn new(
+        client: Client,
+        max_per_window: u64,
+        window_ms: u64,
+    ) -> Result<Self, StdoutChannelError> {
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            script: Script::new(ACQUIRE_SCRIPT),
+            prefix: "stdout-channel:rate:".into(),
+            max_per_window,
+            window_ms: window_ms.max(1),
+        })
+    }
+
+    /// Prefix of the Redis keys, `stdout-channel:rate:` by default
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    async fn acquire_permit(&self, key: &str) -> Result<(), StdoutChannelError> {
+        let key = format!("{}{key}", self.prefix);
+        let mut connection = self.connection.clone();
+        loop {
+            let (count, ttl_ms): (u64, i64) = self
+                .script
+                .key(&key)
+                .arg(self.window_ms)
+                .invoke_async(&mut connection)
+                .await?;
+            if count <= self.max_per_window {
+                return Ok(());
+            }
+            let wait = u64::try_from(ttl_ms).unwrap_or(1).max(1);
+            sleep(Duration::from_millis(wait)).await;
+        }
+    }
+}
+
+impl RateLimitBackend for RedisRateLimiter {
+    fn acquire<'a>(&'a self, key: &'a str) -> BackendFuture<'a> {
+        Box::pin(self.acquire_permit(key))
+    }
+}
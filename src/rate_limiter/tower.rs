@@ -0,0 +1,122 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::rate_limiter::RateLimiter;
+
+/// `tower::Layer` acquiring a `RatePermit` from the limiter before every
+/// request, the permit is held until the response is ready so a limit set
+/// with `with_concurrency` caps in-flight requests too.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    rate_limiter: RateLimiter,
+}
+
+impl RateLimitLayer {
+    #[must_use]
+    pub fn new(rate_limiter: RateLimiter) -> Self {
+        Self { rate_limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            rate_limiter: self.rate_limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    rate_limiter: RateLimiter,
+}
+
+type ResponseFuture<R, E> = Pin<Box<dyn Future<Output = Result<R, E>> + Send>>;
+
+impl<S, Request> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request> + Clone + Send + 'static,
+    S::Future: Send,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // the clone isn't ready yet, so keep the service that was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let rate_limiter = self.rate_limiter.clone();
+        Box::pin(async move {
+            let _permit = rate_limiter.acquire_permit().await;
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        future::{ready, Ready},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use tokio::time::timeout;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::rate_limiter::RateLimiter;
+
+    use super::RateLimitLayer;
+
+    #[derive(Clone)]
+    struct Echo(Arc<AtomicUsize>);
+
+    impl Service<u32> for Echo {
+        type Response = u32;
+        type Error = Infallible;
+        type Future = Ready<Result<u32, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: u32) -> Self::Future {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ready(Ok(request))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_layer() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let layer = RateLimitLayer::new(RateLimiter::new(2, 60_000));
+        let mut service = layer.layer(Echo(Arc::clone(&calls)));
+
+        assert_eq!(service.call(1).await, Ok(1));
+        assert_eq!(service.call(2).await, Ok(2));
+        assert!(timeout(Duration::from_millis(20), service.call(3))
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,287 @@
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time::interval};
+
+use crate::{banner::json_string, sink::Stream, sync::Mutex, StdoutChannel};
+
+#[derive(Default)]
+struct Samples {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+#[derive(Default)]
+struct HistogramHandle(Mutex<Samples>);
+
+impl HistogramFn for HistogramHandle {
+    fn record(&self, value: f64) {
+        let mut samples = self.0.lock();
+        if samples.count == 0 {
+            samples.min = value;
+            samples.max = value;
+        } else {
+            samples.min = samples.min.min(value);
+            samples.max = samples.max.max(value);
+        }
+        samples.count += 1;
+        samples.sum += value;
+    }
+}
+
+enum Metric {
+    Counter(Arc<AtomicU64>),
+    Gauge(Arc<AtomicU64>),
+    Histogram(Arc<HistogramHandle>),
+}
+
+struct Registry<T> {
+    chan: StdoutChannel<T>,
+    metrics: Mutex<BTreeMap<Key, Metric>>,
+}
+
+/// `metrics::Recorder` that keeps counters, gauges and histograms in memory
+/// and sends their current values through a channel on `flush`, one line
+/// per metric: `name{label=value} 3` for counters and gauges and `name
+/// count=2 min=1 max=3 mean=2` for histograms, or a JSON record per metric
+/// in JSON mode. Histograms are reset by every flush, counters and gauges
+/// keep their value.
+pub struct MetricsRecorder<T> {
+    registry: Arc<Registry<T>>,
+}
+
+impl<T> Clone for MetricsRecorder<T> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: Arc::clone(&self.registry),
+        }
+    }
+}
+
+fn render_name(key: &Key, json: bool) -> String {
+    let mut name = String::new();
+    if json {
+        write!(name, r#""metric":{},"labels":{{"#, json_string(key.name())).ok();
+        for (i, label) in key.labels().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(
+                name,
+                "{sep}{}:{}",
+                json_string(label.key()),
+                json_string(label.value())
+            )
+            .ok();
+        }
+        name.push('}');
+    } else {
+        name.push_str(key.name());
+        for (i, label) in key.labels().enumerate() {
+            let sep = if i == 0 { '{' } else { ',' };
+            write!(name, "{sep}{}={}", label.key(), label.value()).ok();
+        }
+        if key.labels().next().is_some() {
+            name.push('}');
+        }
+    }
+    name
+}
+
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".into()
+    }
+}
+
+impl<T> MetricsRecorder<T> {
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>) -> Self {
+        Self {
+            registry: Arc::new(Registry {
+                chan: chan.clone(),
+                metrics: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Current value of every metric, sorted by key
+    #[must_use]
+    pub fn render(&self) -> Vec<String> {
+        let json = self.registry.chan.output_config().format.is_json();
+        let metrics = self.registry.metrics.lock();
+        metrics
+            .iter()
+            .map(|(key, metric)| {
+                let name = render_name(key, json);
+                match metric {
+                    Metric::Counter(c) => {
+                        let value = c.load(Ordering::Relaxed);
+                        if json {
+                            format!(r#"{{{name},"type":"counter","value":{value}}}"#)
+                        } else {
+                            format!("{name} {value}")
+                        }
+                    }
+                    Metric::Gauge(g) => {
+                        let value = f64::from_bits(g.load(Ordering::Relaxed));
+                        if json {
+                            let value = json_number(value);
+                            format!(r#"{{{name},"type":"gauge","value":{value}}}"#)
+                        } else {
+                            format!("{name} {value}")
+                        }
+                    }
+                    Metric::Histogram(h) => {
+                        let s = std::mem::take(&mut *h.0.lock());
+                        #[allow(clippy::cast_precision_loss)]
+                        let mean = if s.count == 0 {
+                            0.0
+                        } else {
+                            s.sum / s.count as f64
+                        };
+                        if json {
+                            format!(
+                                r#"{{{name},"type":"histogram","count":{},"min":{},"max":{},"mean":{}}}"#,
+                                s.count,
+                                json_number(s.min),
+                                json_number(s.max),
+                                json_number(mean)
+                            )
+                        } else {
+                            format!(
+                                "{name} count={} min={} max={} mean={mean}",
+                                s.count, s.min, s.max
+                            )
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn register(&self, key: &Key, new: impl FnOnce() -> Metric) -> Metric {
+        let mut metrics = self.registry.metrics.lock();
+        let metric = metrics.entry(key.clone()).or_insert_with(new);
+        match metric {
+            Metric::Counter(c) => Metric::Counter(Arc::clone(c)),
+            Metric::Gauge(g) => Metric::Gauge(Arc::clone(g)),
+            Metric::Histogram(h) => Metric::Histogram(Arc::clone(h)),
+        }
+    }
+}
+
+impl<T> MetricsRecorder<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Send the lines from `render` to stdout, dropped if the channel is
+    /// closed
+    pub fn flush(&self) {
+        for line in self.render() {
+            self.registry.chan.send_quiet(Stream::Stdout, line);
+        }
+    }
+
+    /// Call `flush` every `period` until the returned task is aborted
+    #[must_use]
+    pub fn spawn_flush(&self, period: Duration) -> JoinHandle<()> {
+        let recorder = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = interval(period);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                recorder.flush();
+            }
+        })
+    }
+}
+
+/// Registering a name under one kind of metric and using it as another
+/// yields a no-op handle
+impl<T> Recorder for MetricsRecorder<T>
+where
+    T: Send + 'static,
+{
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        match self.register(key, || Metric::Counter(Arc::default())) {
+            Metric::Counter(c) => Counter::from_arc(c),
+            _ => Counter::noop(),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        match self.register(key, || Metric::Gauge(Arc::default())) {
+            Metric::Gauge(g) => Gauge::from_arc(g),
+            _ => Gauge::noop(),
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        match self.register(key, || Metric::Histogram(Arc::default())) {
+            Metric::Histogram(h) => Histogram::from_arc(h),
+            _ => Histogram::noop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, OutputConfig, OutputFormat, StdoutChannel, StdoutChannelError};
+
+    use super::MetricsRecorder;
+
+    #[tokio::test]
+    async fn test_metrics_recorder() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let recorder = MetricsRecorder::new(&chan);
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("requests", "method" => "GET").increment(3);
+            metrics::counter!("requests", "method" => "GET").increment(1);
+            metrics::gauge!("queue_depth").set(7.5);
+            metrics::histogram!("latency_ms").record(1.0);
+            metrics::histogram!("latency_ms").record(3.0);
+        });
+        recorder.flush();
+
+        let json = MetricsRecorder::new(&chan.clone().with_output_config(OutputConfig {
+            format: OutputFormat::Json,
+            ..OutputConfig::default()
+        }));
+        metrics::with_local_recorder(&json, || {
+            metrics::counter!("requests", "method" => "GET").increment(2);
+        });
+        json.flush();
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [
+                "latency_ms count=2 min=1 max=3 mean=2",
+                "queue_depth 7.5",
+                "requests{method=GET} 4",
+                r#"{"metric":"requests","labels":{"method":"GET"},"type":"counter","value":2}"#,
+            ]
+        );
+        Ok(())
+    }
+}
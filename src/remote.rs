@@ -0,0 +1,171 @@
+use std::fmt::Display;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    task::{spawn, JoinHandle},
+};
+
+use crate::{sink::Stream, RateLimiter, StdoutChannel, StdoutChannelError};
+
+/// A remote host whose command output is forwarded into a `StdoutChannel`.
+///
+/// Every forwarded line is prefixed with `[name] `, and if a rate limit is
+/// set each host gets its own `RateLimiter` so a single chatty machine can't
+/// drown out the rest of the fleet.
+#[derive(Clone)]
+pub struct RemoteHost {
+    name: String,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl RemoteHost {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            rate_limiter: None,
+        }
+    }
+
+    /// Limit this host to `max_per_unit_time` lines every `unit_time_ms`
+    /// milliseconds
+    #[must_use]
+    pub fn with_rate_limit(mut self, max_per_unit_time: usize, unit_time_ms: usize) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(max_per_unit_time, unit_time_ms));
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+type RemoteTask = JoinHandle<Result<(), StdoutChannelError>>;
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Forward the stdout and stderr of a remote command into the channel.
+    ///
+    /// The readers can be anything implementing `AsyncRead`, e.g. the piped
+    /// output of an `openssh` child or a `russh` channel stream. The returned
+    /// task completes once both readers reach EOF.
+    pub fn pipe_remote<O, E>(&self, host: &RemoteHost, stdout: O, stderr: E) -> RemoteTask
+    where
+        O: AsyncRead + Unpin + Send + 'static,
+        E: AsyncRead + Unpin + Send + 'static,
+    {
+        self.pipe_remote_opt(host, Some(stdout), Some(stderr))
+    }
+
+    /// Forward the output of an `openssh` child process, taking whichever of
+    /// its stdout and stderr were piped.
+    #[cfg(feature = "openssh")]
+    pub fn pipe_openssh_child<S>(
+        &self,
+        host: &RemoteHost,
+        child: &mut openssh::Child<S>,
+    ) -> RemoteTask {
+        let stdout = child.stdout().take();
+        let stderr = child.stderr().take();
+        self.pipe_remote_opt(host, stdout, stderr)
+    }
+
+    fn pipe_remote_opt<O, E>(
+        &self,
+        host: &RemoteHost,
+        stdout: Option<O>,
+        stderr: Option<E>,
+    ) -> RemoteTask
+    where
+        O: AsyncRead + Unpin + Send + 'static,
+        E: AsyncRead + Unpin + Send + 'static,
+    {
+        let stdout_task = stdout.map(|reader| {
+            let chan = self.clone();
+            let host = host.clone();
+            spawn(async move {
+                forward_lines(reader, &host, |line| {
+                    chan.send_quiet(Stream::Stdout, line);
+                })
+                .await
+            })
+        });
+        let stderr_task = stderr.map(|reader| {
+            let chan = self.clone();
+            let host = host.clone();
+            spawn(async move {
+                forward_lines(reader, &host, |line| {
+                    chan.send_quiet(Stream::Stderr, line);
+                })
+                .await
+            })
+        });
+        spawn(async move {
+            if let Some(stdout_task) = stdout_task {
+                stdout_task.await??;
+            }
+            if let Some(stderr_task) = stderr_task {
+                stderr_task.await??;
+            }
+            Ok(())
+        })
+    }
+}
+
+async fn forward_lines<R, F>(
+    reader: R,
+    host: &RemoteHost,
+    send: F,
+) -> Result<(), StdoutChannelError>
+where
+    R: AsyncRead + Unpin,
+    F: Fn(String),
+{
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            return Ok(());
+        }
+        if let Some(rate_limiter) = &host.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        send(format!("[{}] {line}", host.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::RemoteHost;
+
+    #[tokio::test]
+    async fn test_pipe_remote() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+
+        let web = RemoteHost::new("web-1").with_rate_limit(10, 10);
+        let db = RemoteHost::new("db-1");
+
+        let web_task = chan.pipe_remote(&web, &b"uptime 3 days\nload 0.1\n"[..], &b""[..]);
+        let db_task = chan.pipe_remote(&db, &b"ok"[..], &b"disk full\r\n"[..]);
+        web_task.await??;
+        db_task.await??;
+        chan.close().await?;
+
+        let stdout = stdout.lock().await;
+        assert_eq!(stdout.len(), 3);
+        assert!(stdout.contains(&"[web-1] uptime 3 days".to_string()));
+        assert!(stdout.contains(&"[web-1] load 0.1".to_string()));
+        assert!(stdout.contains(&"[db-1] ok".to_string()));
+        assert_eq!(stderr.lock().await.as_slice(), ["[db-1] disk full"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{sync::Mutex, AnnotationLevel, StdoutChannel, StdoutChannelError};
+
+/// Source range of a diagnostic, lines and columns are 1-based
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start_line: usize,
+    pub start_column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+}
+
+impl Region {
+    /// A whole line
+    #[must_use]
+    pub fn line(line: usize) -> Self {
+        Self {
+            start_line: line,
+            start_column: None,
+            end_line: None,
+            end_column: None,
+        }
+    }
+
+    #[must_use]
+    pub fn new(start_line: usize, start_column: usize, end_line: usize, end_column: usize) -> Self {
+        Self {
+            start_line,
+            start_column: Some(start_column),
+            end_line: Some(end_line),
+            end_column: Some(end_column),
+        }
+    }
+}
+
+/// A single finding recorded into a `SarifReport`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub region: Region,
+    pub level: AnnotationLevel,
+    pub rule_id: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn to_value(&self) -> Value {
+        let mut region = json!({"startLine": self.region.start_line});
+        for (key, value) in [
+            ("startColumn", self.region.start_column),
+            ("endLine", self.region.end_line),
+            ("endColumn", self.region.end_column),
+        ] {
+            if let Some(value) = value {
+                region[key] = value.into();
+            }
+        }
+        json!({
+            "ruleId": self.rule_id,
+            "level": sarif_level(self.level),
+            "message": {"text": self.message},
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": {"uri": self.file},
+                    "region": region,
+                }
+            }],
+        })
+    }
+}
+
+fn sarif_level(level: AnnotationLevel) -> &'static str {
+    match level {
+        AnnotationLevel::Error => "error",
+        AnnotationLevel::Warning => "warning",
+        AnnotationLevel::Notice => "note",
+    }
+}
+
+/// Prints diagnostics through a `StdoutChannel` as they are recorded and
+/// writes them all as a SARIF 2.1.0 document.
+///
+/// The document is written when the channel is closed, with every
+/// diagnostic recorded until then. Call `close` to write it earlier, e.g.
+/// when the channel stays open until the process exits.
+pub struct SarifReport<T> {
+    chan: StdoutChannel<T>,
+    tool: Arc<Tool>,
+    path: PathBuf,
+    diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+}
+
+struct Tool {
+    name: String,
+    version: String,
+}
+
+impl<T> SarifReport<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// A report written to `path` when `chan` is closed
+    #[must_use]
+    pub fn new(
+        chan: &StdoutChannel<T>,
+        tool_name: impl Into<String>,
+        tool_version: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Self {
+        let report = Self {
+            chan: chan.clone(),
+            tool: Arc::new(Tool {
+                name: tool_name.into(),
+                version: tool_version.into(),
+            }),
+            path: path.as_ref().to_path_buf(),
+            diagnostics: Arc::default(),
+        };
+        let (tool, path, diagnostics) = (
+            Arc::clone(&report.tool),
+            report.path.clone(),
+            Arc::clone(&report.diagnostics),
+        );
+        chan.add_close_hook(move || async move {
+            let sarif = to_sarif(&tool, &diagnostics.lock());
+            tokio::fs::write(&path, serde_json::to_vec_pretty(&sarif)?).await?;
+            Ok(())
+        });
+        report
+    }
+
+    /// Print a `file:line:col: level[rule]: message` line to stderr and keep
+    /// the diagnostic for the SARIF document
+    pub fn record(&self, diagnostic: Diagnostic) {
+        let column = diagnostic
+            .region
+            .start_column
+            .map_or_else(String::new, |c| format!(":{c}"));
+        self.chan.send_err(format!(
+            "{}:{}{column}: {}[{}]: {}",
+            diagnostic.file,
+            diagnostic.region.start_line,
+            sarif_level(diagnostic.level),
+            diagnostic.rule_id,
+            diagnostic.message
+        ));
+        self.diagnostics.lock().push(diagnostic);
+    }
+
+    /// Build the SARIF document for the recorded diagnostics
+    #[must_use]
+    pub fn to_sarif(&self) -> Value {
+        to_sarif(&self.tool, &self.diagnostics.lock())
+    }
+
+    /// Write the SARIF document to the configured path now, it's written
+    /// again when the channel is closed
+    /// # Errors
+    ///
+    /// Will error if the document can't be serialized or written
+    pub async fn close(&self) -> Result<(), StdoutChannelError> {
+        let sarif = serde_json::to_vec_pretty(&self.to_sarif())?;
+        tokio::fs::write(&self.path, sarif).await?;
+        Ok(())
+    }
+}
+
+/// The SARIF document of `diagnostics` found by `tool`
+fn to_sarif(tool: &Tool, diagnostics: &[Diagnostic]) -> Value {
+    let rules: BTreeSet<_> = diagnostics.iter().map(|d| d.rule_id.as_str()).collect();
+    let rules: Vec<_> = rules.into_iter().map(|id| json!({"id": id})).collect();
+    let results: Vec<_> = diagnostics.iter().map(Diagnostic::to_value).collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool.name,
+                    "version": tool.version,
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{AnnotationLevel, MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{Diagnostic, Region, SarifReport};
+
+    #[tokio::test]
+    async fn test_sarif_report() -> Result<(), StdoutChannelError> {
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::new(), stderr.clone());
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("sarif.json");
+        let report = SarifReport::new(&chan, "lint", "0.1.0", &path);
+        report.record(Diagnostic {
+            file: "src/lib.rs".into(),
+            region: Region::new(3, 5, 3, 9),
+            level: AnnotationLevel::Warning,
+            rule_id: "unused".into(),
+            message: "unused variable `x`".into(),
+        });
+        report.record(Diagnostic {
+            file: "src/main.rs".into(),
+            region: Region::line(10),
+            level: AnnotationLevel::Notice,
+            rule_id: "style".into(),
+            message: "consider a shorter name".into(),
+        });
+        report.close().await?;
+        chan.close().await?;
+
+        assert_eq!(
+            stderr.lock().await.as_slice(),
+            [
+                "src/lib.rs:3:5: warning[unused]: unused variable `x`",
+                "src/main.rs:10: note[style]: consider a shorter name",
+            ]
+        );
+
+        let sarif: Value = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "lint");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 2);
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "unused");
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/lib.rs");
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(location["region"]["endColumn"], 9);
+        assert!(
+            run["results"][1]["locations"][0]["physicalLocation"]["region"]
+                .get("endLine")
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_written_on_channel_close() -> Result<(), StdoutChannelError> {
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::<String>::new(), MockStdout::new());
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("sarif.json");
+        let report = SarifReport::new(&chan, "lint", "0.1.0", &path);
+        report.record(Diagnostic {
+            file: "src/lib.rs".into(),
+            region: Region::line(1),
+            level: AnnotationLevel::Error,
+            rule_id: "syntax".into(),
+            message: "expected `;`".into(),
+        });
+        drop(report);
+        chan.close().await?;
+
+        let sarif: Value = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+        assert_eq!(sarif["runs"][0]["results"][0]["ruleId"], "syntax");
+        Ok(())
+    }
+}
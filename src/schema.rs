@@ -0,0 +1,104 @@
+use schemars::{schema_for, JsonSchema};
+use serde_json::{json, Value};
+use std::fmt::Display;
+
+use crate::{StdoutChannel, StdoutChannelError};
+
+/// JSON Schema describing the record type `R` of a machine readable output
+/// stream
+#[must_use]
+pub fn json_schema<R: JsonSchema>() -> Value {
+    schema_for!(R).to_value()
+}
+
+/// Header record announcing which record type and schema version follow
+#[must_use]
+pub fn schema_header<R: JsonSchema>(version: &str) -> Value {
+    json!({
+        "schema": R::schema_name(),
+        "version": version,
+    })
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Write a one line `{"schema": ..., "version": ...}` header record, to
+    /// be sent before the first record of JSON output so downstream parsers
+    /// can check what they are reading. Does nothing unless the output
+    /// format is JSON.
+    /// # Errors
+    ///
+    /// Will error if the header can't be serialized
+    pub fn send_schema_header<R: JsonSchema>(
+        &self,
+        version: &str,
+    ) -> Result<(), StdoutChannelError> {
+        if self.output_config().format.is_json() {
+            self.send(serde_json::to_string(&schema_header::<R>(version))?);
+        }
+        Ok(())
+    }
+
+    /// Write the pretty printed JSON Schema of `R`, e.g. for a `--schema`
+    /// flag. Does nothing unless the output format is JSON.
+    /// # Errors
+    ///
+    /// Will error if the schema can't be serialized
+    pub fn send_schema<R: JsonSchema>(&self) -> Result<(), StdoutChannelError> {
+        if self.output_config().format.is_json() {
+            self.send(serde_json::to_string_pretty(&json_schema::<R>())?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+    use serde_json::Value;
+
+    use crate::{MockStdout, OutputConfig, OutputFormat, StdoutChannel, StdoutChannelError};
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct FileRecord {
+        path: String,
+        size: u64,
+    }
+
+    #[tokio::test]
+    async fn test_schema() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new())
+            .with_output_config(OutputConfig {
+                format: OutputFormat::Json,
+                ..OutputConfig::default()
+            });
+
+        chan.send_schema_header::<FileRecord>("1.2")?;
+        chan.send_schema::<FileRecord>()?;
+        chan.close().await?;
+
+        let stdout = stdout.lock().await;
+        assert_eq!(stdout[0], r#"{"schema":"FileRecord","version":"1.2"}"#);
+        let schema: Value = serde_json::from_str(&stdout[1])?;
+        assert_eq!(schema["title"], "FileRecord");
+        assert_eq!(schema["properties"]["size"]["type"], "integer");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_text_mode() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+
+        chan.send_schema_header::<FileRecord>("1.2")?;
+        chan.send_schema::<FileRecord>()?;
+        chan.close().await?;
+
+        assert!(stdout.snapshot().is_empty());
+        Ok(())
+    }
+}
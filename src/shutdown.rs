@@ -0,0 +1,159 @@
+use std::{fmt::Display, future::Future, io::Write, pin::Pin, time::Duration};
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    task::block_in_place,
+};
+
+use crate::{error_chain, StdoutChannel, StdoutChannelError};
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+type CloseFuture = Pin<Box<dyn Future<Output = Result<(), StdoutChannelError>> + Send>>;
+type ShutdownHook = Box<dyn FnOnce(Duration) -> CloseFuture + Send>;
+
+/// Channels to close before the runtime shuts down, added with
+/// `StdoutChannel::register_shutdown`.
+///
+/// `run` closes them at the end of `main`. When the hooks are dropped
+/// without being run, e.g. on an early return through `?`, the channels are
+/// closed on drop instead. That blocks the current worker thread, so it
+/// needs a multi-thread runtime, on a current-thread runtime or outside of
+/// a runtime the number of channels left open is listed on the process
+/// stderr.
+pub struct ShutdownHooks {
+    hooks: Vec<ShutdownHook>,
+    timeout: Duration,
+}
+
+impl Default for ShutdownHooks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownHooks {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            hooks: Vec::new(),
+            timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Time each channel gets to finish writing before its tasks are
+    /// aborted, 5 seconds by default
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Close every registered channel in the order they were registered
+    /// # Errors
+    ///
+    /// Returns the first error of `StdoutChannel::close_with_timeout`, the
+    /// channels after a failing one are still closed
+    pub async fn run(mut self) -> Result<(), StdoutChannelError> {
+        Self::close_all(std::mem::take(&mut self.hooks), self.timeout).await
+    }
+
+    async fn close_all(
+        hooks: Vec<ShutdownHook>,
+        timeout: Duration,
+    ) -> Result<(), StdoutChannelError> {
+        let mut result = Ok(());
+        for hook in hooks {
+            let closed = hook(timeout).await;
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+        result
+    }
+}
+
+impl Drop for ShutdownHooks {
+    fn drop(&mut self) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let hooks = std::mem::take(&mut self.hooks);
+        let mut stderr = std::io::stderr();
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                let closed =
+                    block_in_place(|| handle.block_on(Self::close_all(hooks, self.timeout)));
+                if let Err(e) = closed {
+                    writeln!(
+                        stderr,
+                        "stdout-channel: shutdown failed: {}",
+                        error_chain(&e)
+                    )
+                    .ok();
+                }
+            }
+            _ => {
+                writeln!(
+                    stderr,
+                    "stdout-channel: {} channels not closed before shutdown",
+                    hooks.len()
+                )
+                .ok();
+            }
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Close this channel when `hooks` are run or dropped
+    pub fn register_shutdown(&self, hooks: &mut ShutdownHooks) {
+        let chan = self.clone();
+        hooks.hooks.push(Box::new(move |timeout| {
+            Box::pin(async move { chan.close_with_timeout(timeout).await })
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, ShutdownHooks, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_hooks() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let early_return = || -> Result<(), StdoutChannelError> {
+            let mut hooks = ShutdownHooks::new();
+            chan.register_shutdown(&mut hooks);
+            chan.send("written on drop");
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound).into())
+        };
+        assert!(early_return().is_err());
+        assert!(chan.describe().stdout.closed);
+        assert_eq!(stdout.snapshot(), ["written on drop"]);
+
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::new(), stderr.clone());
+        let mut hooks = ShutdownHooks::new();
+        chan.register_shutdown(&mut hooks);
+        chan.clone().register_shutdown(&mut hooks);
+        assert_eq!(hooks.len(), 2);
+        chan.send_err("written on run");
+        hooks.run().await?;
+        assert_eq!(stderr.snapshot(), ["written on run"]);
+        Ok(())
+    }
+}
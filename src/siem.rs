@@ -0,0 +1,192 @@
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Write},
+};
+
+use crate::{event::Event, FieldValue};
+
+/// Record formats of SIEMs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SiemDialect {
+    /// Common Event Format,
+    /// `CEF:0|vendor|product|version|id|name|severity|key=value ...`
+    Cef,
+    /// Log Event Extended Format 1.0,
+    /// `LEEF:1.0|vendor|product|version|id|key=value<TAB>...`
+    Leef,
+}
+
+/// Writes `Event`s as CEF or LEEF records, sent with `Event::emit_as`.
+///
+/// The event name is the event class id. Field names are written as they
+/// are unless mapped to a key of the dialect with `map_field`, e.g.
+/// `client_ip` to `src`. A `severity` field (0-10) sets the severity and
+/// isn't written as a field.
+#[derive(Clone, Debug)]
+pub struct SiemFormatter {
+    dialect: SiemDialect,
+    vendor: String,
+    product: String,
+    version: String,
+    mapping: Vec<(String, String)>,
+    default_severity: u8,
+}
+
+impl SiemFormatter {
+    #[must_use]
+    pub fn new(
+        dialect: SiemDialect,
+        vendor: impl Into<String>,
+        product: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        Self {
+            dialect,
+            vendor: vendor.into(),
+            product: product.into(),
+            version: version.into(),
+            mapping: Vec::new(),
+            default_severity: 5,
+        }
+    }
+
+    /// Write the field `from` as `to`
+    #[must_use]
+    pub fn map_field(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.mapping.push((from.into(), to.into()));
+        self
+    }
+
+    /// Severity of events without a `severity` field, 5 unless set
+    #[must_use]
+    pub fn with_default_severity(mut self, severity: u8) -> Self {
+        self.default_severity = severity.min(10);
+        self
+    }
+
+    fn key<'a>(&'a self, field: &'a str) -> &'a str {
+        self.mapping
+            .iter()
+            .find(|(from, _)| from == field)
+            .map_or(field, |(_, to)| to)
+    }
+
+    /// The record of an event called `name` with `fields`
+    #[must_use]
+    pub fn format(&self, name: &str, fields: &[(String, FieldValue)]) -> String {
+        let severity = fields
+            .iter()
+            .find(|(key, _)| key == "severity")
+            .and_then(|(_, value)| match value {
+                FieldValue::Int(i) => u8::try_from(*i).ok(),
+                FieldValue::UInt(u) => u8::try_from(*u).ok(),
+                _ => None,
+            })
+            .map_or(self.default_severity, |s| s.min(10));
+        let header = [&self.vendor, &self.product, &self.version, name]
+            .iter()
+            .map(|part| escape_header(part))
+            .collect::<Vec<_>>()
+            .join("|");
+        let fields = fields.iter().filter(|(key, _)| key != "severity");
+        let mut line = String::new();
+        match self.dialect {
+            SiemDialect::Cef => {
+                write!(line, "CEF:0|{header}|{}|{severity}|", escape_header(name)).ok();
+                for (i, (key, value)) in fields.enumerate() {
+                    let sep = if i > 0 { " " } else { "" };
+                    let value = escape_value(value, &['\\', '=']);
+                    write!(line, "{sep}{}={value}", sanitize_key(self.key(key))).ok();
+                }
+            }
+            SiemDialect::Leef => {
+                write!(line, "LEEF:1.0|{header}|sev={severity}").ok();
+                for (key, value) in fields {
+                    let value = escape_value(value, &['\\', '\t']);
+                    write!(line, "\t{}={value}", sanitize_key(self.key(key))).ok();
+                }
+            }
+        }
+        line
+    }
+}
+
+fn escape_header(part: &str) -> String {
+    part.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Backslash-escape `special` in a field value, line breaks become `\n`
+/// and `\r`
+fn escape_value(value: &impl Display, special: &[char]) -> String {
+    let value = value.to_string();
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' if special.contains(&'\t') => out.push_str("\\t"),
+            c if special.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn sanitize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect()
+}
+
+impl<T> Event<'_, T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Send the event to stdout as a `formatter` record, whatever the
+    /// output format of the channel
+    pub fn emit_as(self, formatter: &SiemFormatter) {
+        self.chan.send(formatter.format(&self.name, &self.fields));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{SiemDialect, SiemFormatter};
+
+    #[tokio::test]
+    async fn test_siem_formats() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), MockStdout::new());
+        let cef = SiemFormatter::new(SiemDialect::Cef, "Acme", "Gate|way", "1.0")
+            .map_field("client_ip", "src");
+        let leef = SiemFormatter::new(SiemDialect::Leef, "Acme", "Gateway", "1.0")
+            .map_field("client_ip", "src")
+            .with_default_severity(3);
+        chan.event("login_failed")
+            .field("client_ip", "10.0.0.1")
+            .field("msg", "bad=password\nagain")
+            .field("severity", 8)
+            .emit_as(&cef);
+        chan.event("login_failed")
+            .field("client_ip", "10.0.0.1")
+            .field("msg", "tab\there")
+            .emit_as(&leef);
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.snapshot(),
+            [
+                r"CEF:0|Acme|Gate\|way|1.0|login_failed|login_failed|8|src=10.0.0.1 msg=bad\=password\nagain",
+                "LEEF:1.0|Acme|Gateway|1.0|login_failed|sev=3\tsrc=10.0.0.1\tmsg=tab\\there",
+            ]
+        );
+        Ok(())
+    }
+}
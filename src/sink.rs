@@ -0,0 +1,131 @@
+pub mod atomic;
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
+pub mod disk_guard;
+#[cfg(all(feature = "eventlog", windows))]
+pub mod eventlog;
+pub mod fault;
+pub mod file;
+pub mod framed;
+#[cfg(feature = "gelf")]
+pub mod gelf;
+pub mod keyed;
+#[cfg(all(feature = "logcat", target_os = "android"))]
+pub mod logcat;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "oslog")]
+pub mod oslog;
+pub mod paced;
+pub mod part;
+pub mod partitioned;
+pub mod retention;
+#[cfg(feature = "rotation")]
+pub mod rotating;
+pub mod stdio;
+#[cfg(feature = "syslog")]
+pub mod syslog;
+pub mod tee;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+
+use std::{fmt, future::Future, pin::Pin};
+
+use crate::StdoutChannelError;
+
+/// Which of the two streams of a `StdoutChannel` a line was sent to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        })
+    }
+}
+
+/// A line handed to an `OutputSink`: the item that was sent along with its
+/// rendered bytes, including the trailing newline.
+pub struct OutputLine<'a, T> {
+    item: T,
+    bytes: &'a [u8],
+    stream: Stream,
+}
+
+impl<'a, T> OutputLine<'a, T> {
+    pub(crate) fn new(item: T, bytes: &'a [u8], stream: Stream) -> Self {
+        Self {
+            item,
+            bytes,
+            stream,
+        }
+    }
+
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    pub fn into_item(self) -> T {
+        self.item
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    #[must_use]
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+}
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = Result<(), StdoutChannelError>> + Send + 'a>>;
+
+/// Destination of one of the streams of a `StdoutChannel`.
+///
+/// Each sink is driven by its own writer task, `write` is called for every
+/// line in order and `close` once when the channel is closed.
+pub trait OutputSink<T>: Send {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a>;
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        self.flush()
+    }
+
+    /// Whether lines end up on the process stdout or stderr, which may be a
+    /// terminal. Sinks wrapping another one return what it does.
+    fn is_process_stream(&self) -> bool {
+        false
+    }
+}
+
+impl<T, S> OutputSink<T> for Box<S>
+where
+    S: OutputSink<T> + ?Sized,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        (**self).write(line)
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        (**self).flush()
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        (**self).close()
+    }
+
+    fn is_process_stream(&self) -> bool {
+        (**self).is_process_stream()
+    }
+}
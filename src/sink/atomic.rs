@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    sink::{file::FileSinkOptions, OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+/// Writes to a temporary file next to `path` and only renames it into place
+/// on close, after syncing the file and its directory, so readers never see
+/// a partially written file. If the channel is never closed the temporary
+/// file is removed when the sink is dropped.
+pub struct AtomicFileSink {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl AtomicFileSink {
+    /// # Errors
+    ///
+    /// Will error if the temporary file can't be created
+    pub async fn new(path: impl AsRef<Path>) -> Result<Self, StdoutChannelError> {
+        Self::with_options(path, FileSinkOptions::new()).await
+    }
+
+    /// Create the sink, opening the temporary file with `options` (the
+    /// append flag is ignored)
+    /// # Errors
+    ///
+    /// Will error if the temporary file can't be created
+    pub async fn with_options(
+        path: impl AsRef<Path>,
+        options: FileSinkOptions,
+    ) -> Result<Self, StdoutChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let file_name = path
+            .file_name()
+            .map_or_else(|| "output".into(), |n| n.to_string_lossy());
+        let tmp_path = path.with_file_name(format!(".{file_name}.tmp-{}", std::process::id()));
+        let file = options.open_file(&tmp_path, false).await?;
+        Ok(Self {
+            path,
+            tmp_path,
+            writer: Some(BufWriter::new(file)),
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write_all(bytes).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), StdoutChannelError> {
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+        writer.flush().await?;
+        writer.into_inner().sync_all().await?;
+        fs::rename(&self.tmp_path, &self.path).await?;
+        sync_parent_dir(&self.path).await
+    }
+}
+
+#[cfg(unix)]
+async fn sync_parent_dir(path: &Path) -> Result<(), StdoutChannelError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent).await?.sync_all().await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn sync_parent_dir(_: &Path) -> Result<(), StdoutChannelError> {
+    Ok(())
+}
+
+impl Drop for AtomicFileSink {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+impl<T> OutputSink<T> for AtomicFileSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.commit())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use crate::{StdoutChannel, StdoutChannelError};
+
+    use super::AtomicFileSink;
+
+    #[tokio::test]
+    async fn test_atomic_file_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let out = dir.join("out.txt");
+        let err = dir.join("err.txt");
+
+        let chan = StdoutChannel::<String>::with_sinks(
+            AtomicFileSink::new(&out).await?,
+            AtomicFileSink::new(&err).await?,
+        );
+        chan.send("first");
+        chan.send("second");
+        chan.send_err("oops");
+        assert!(fs::metadata(&out).await.is_err());
+        chan.close().await?;
+
+        assert_eq!(fs::read_to_string(&out).await?, "first\nsecond\n");
+        assert_eq!(fs::read_to_string(&err).await?, "oops\n");
+        let mut entries = fs::read_dir(&dir).await?;
+        let mut count = 0;
+        while entries.next_entry().await?.is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+
+        let abandoned = AtomicFileSink::new(dir.join("never.txt")).await?;
+        drop(abandoned);
+        assert!(fs::metadata(dir.join("never.txt")).await.is_err());
+
+        Ok(())
+    }
+}
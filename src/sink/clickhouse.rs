@@ -0,0 +1,228 @@
+use std::{fmt::Write as _, io};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+/// Rows inserted at once unless set with `with_batch_rows`
+pub const DEFAULT_BATCH_ROWS: usize = 1000;
+
+/// Inserts lines as rows of a table through the HTTP interface of a
+/// `ClickHouse` server, batched into `INSERT ... FORMAT JSONEachRow`
+/// requests. Every line must be a JSON object whose keys are columns of the
+/// table, like the lines of a `JsonChannel`; blank lines are skipped.
+///
+/// A batch is inserted once it holds `batch_rows` rows and on flush and
+/// close, each over a new connection. A batch the server rejects is
+/// dropped and the write fails with its exception. Plain HTTP only, put a
+/// proxy in front of the server for TLS.
+pub struct ClickHouseSink {
+    addr: String,
+    table: String,
+    credentials: Option<(String, String)>,
+    batch_rows: usize,
+    rows: Vec<u8>,
+    count: usize,
+}
+
+impl ClickHouseSink {
+    /// Insert into `table`, optionally qualified as `database.table`, on the
+    /// server at `addr`, e.g. `localhost:8123`
+    /// # Errors
+    ///
+    /// Will error if `table` isn't made of ASCII letters, digits, `_` and `.`
+    pub fn new(addr: impl Into<String>, table: &str) -> Result<Self, StdoutChannelError> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.');
+        if table.is_empty() || !table.chars().all(valid) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid ClickHouse table name {table:?}"),
+            )
+            .into());
+        }
+        Ok(Self {
+            addr: addr.into(),
+            table: table.into(),
+            credentials: None,
+            batch_rows: DEFAULT_BATCH_ROWS,
+            rows: Vec::new(),
+            count: 0,
+        })
+    }
+
+    /// Authenticate as `user`, the server's `default` user otherwise
+    #[must_use]
+    pub fn with_credentials(
+        mut self,
+        user: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some((user.into(), password.into()));
+        self
+    }
+
+    /// Rows buffered before an insert, `DEFAULT_BATCH_ROWS` unless set, at
+    /// least 1
+    #[must_use]
+    pub fn with_batch_rows(mut self, batch_rows: usize) -> Self {
+        self.batch_rows = batch_rows.max(1);
+        self
+    }
+
+    /// The head of the insert request for a body of `len` bytes
+    fn request_head(&self, len: usize) -> Result<String, StdoutChannelError> {
+        let mut head = format!(
+            "POST /?query=INSERT%20INTO%20{}%20FORMAT%20JSONEachRow HTTP/1.1\r\nHost: {}\r\n\
+             Content-Length: {len}\r\nConnection: close\r\n",
+            self.table, self.addr
+        );
+        if let Some((user, password)) = &self.credentials {
+            if [user, password].iter().any(|s| s.contains(['\r', '\n'])) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "ClickHouse credentials can't contain line breaks",
+                )
+                .into());
+            }
+            write!(
+                head,
+                "X-ClickHouse-User: {user}\r\nX-ClickHouse-Key: {password}\r\n"
+            )
+            .ok();
+        }
+        head.push_str("\r\n");
+        Ok(head)
+    }
+
+    async fn insert(&mut self) -> Result<(), StdoutChannelError> {
+        if self.count == 0 {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        self.count = 0;
+        let head = self.request_head(rows.len())?;
+        let mut stream = TcpStream::connect(self.addr.as_str()).await?;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&rows).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        let response = String::from_utf8_lossy(&response);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status = head.lines().next().unwrap_or_default();
+        match status
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+        {
+            Some(200..=299) => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "ClickHouse insert into {} failed: {status}: {}",
+                self.table,
+                body.trim()
+            ))
+            .into()),
+        }
+    }
+}
+
+impl<T> OutputSink<T> for ClickHouseSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        let row = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        if !row.iter().all(u8::is_ascii_whitespace) {
+            self.rows.extend_from_slice(row);
+            self.rows.push(b'\n');
+            self.count += 1;
+        }
+        Box::pin(async move {
+            if self.count >= self.batch_rows {
+                self.insert().await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.insert())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::ClickHouseSink;
+
+    /// Read one request, returning its head and body
+    async fn read_request(stream: &mut TcpStream) -> Result<(String, String), StdoutChannelError> {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        loop {
+            let n = stream.read(&mut buf).await?;
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let len: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .and_then(|len| len.parse().ok())
+                    .unwrap_or_default();
+                if body.len() >= len || n == 0 {
+                    return Ok((head.to_string(), body.to_string()));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clickhouse_sink() -> Result<(), StdoutChannelError> {
+        assert!(ClickHouseSink::new("localhost:8123", "events; DROP").is_err());
+
+        let server = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = server.local_addr()?.to_string();
+        let requests = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for reply in [
+                "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 404 Not Found\r\n\r\nCode: 60. DB::Exception: Unknown table\n",
+            ] {
+                let (mut stream, _) = server.accept().await?;
+                requests.push(read_request(&mut stream).await?);
+                stream.write_all(reply.as_bytes()).await?;
+            }
+            Ok::<_, StdoutChannelError>(requests)
+        });
+
+        let sink = ClickHouseSink::new(addr, "logs.events")?
+            .with_credentials("ingest", "secret")
+            .with_batch_rows(2);
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        chan.send(r#"{"id":1}"#);
+        chan.send("");
+        chan.send(r#"{"id":2}"#);
+        chan.send(r#"{"id":3}"#);
+        let error = chan.close().await.unwrap_err();
+        assert!(format!("{error:?}").contains("404 Not Found: Code: 60."));
+
+        let requests = requests.await??;
+        let (head, body) = &requests[0];
+        assert!(head.starts_with(
+            "POST /?query=INSERT%20INTO%20logs.events%20FORMAT%20JSONEachRow HTTP/1.1\r\n"
+        ));
+        assert!(head.contains("X-ClickHouse-User: ingest\r\n"));
+        assert_eq!(body, "{\"id\":1}\n{\"id\":2}\n");
+        assert_eq!(requests[1].1, "{\"id\":3}\n");
+        Ok(())
+    }
+}
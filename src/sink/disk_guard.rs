@@ -0,0 +1,234 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::sink::{OutputLine, OutputSink, SinkFuture};
+
+/// Free space reported by a `DiskGuard` when it changes mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiskStatus {
+    Ok { available: u64 },
+    Low { available: u64 },
+}
+
+type Probe = Box<dyn Fn(&Path) -> io::Result<u64> + Send>;
+
+/// Wraps a sink and checks the free space of the filesystem holding `path`
+/// before writing.
+///
+/// While less than `min_available` bytes are free the guard is degraded and
+/// only passes on lines accepted by the `keep` predicate, everything else is
+/// dropped. The `on_change` hook is called whenever the guard enters or
+/// leaves degraded mode.
+pub struct DiskGuard<T, S> {
+    inner: S,
+    path: PathBuf,
+    min_available: u64,
+    interval: Duration,
+    last_check: Option<Instant>,
+    degraded: bool,
+    keep: Box<dyn Fn(&T) -> bool + Send>,
+    on_change: Box<dyn Fn(DiskStatus) + Send>,
+    probe: Probe,
+}
+
+impl<T, S> DiskGuard<T, S> {
+    /// Guard `inner`, by default dropping every line while degraded and
+    /// checking at most once a second
+    #[must_use]
+    pub fn new(inner: S, path: impl AsRef<Path>, min_available: u64) -> Self {
+        Self {
+            inner,
+            path: path.as_ref().to_path_buf(),
+            min_available,
+            interval: Duration::from_secs(1),
+            last_check: None,
+            degraded: false,
+            keep: Box::new(|_| false),
+            on_change: Box::new(|_| {}),
+            probe: Box::new(available_space),
+        }
+    }
+
+    /// Lines still written while degraded
+    #[must_use]
+    pub fn keep(mut self, keep: impl Fn(&T) -> bool + Send + 'static) -> Self {
+        self.keep = Box::new(keep);
+        self
+    }
+
+    #[must_use]
+    pub fn on_change(mut self, on_change: impl Fn(DiskStatus) + Send + 'static) -> Self {
+        self.on_change = Box::new(on_change);
+        self
+    }
+
+    /// Minimum time between two free space checks
+    #[must_use]
+    pub fn check_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    #[must_use]
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    #[cfg(test)]
+    fn with_probe(mut self, probe: impl Fn(&Path) -> io::Result<u64> + Send + 'static) -> Self {
+        self.probe = Box::new(probe);
+        self
+    }
+
+    fn check(&mut self) {
+        if self.last_check.is_some_and(|t| t.elapsed() < self.interval) {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+        // keep the current mode if the filesystem can't be queried
+        if let Ok(available) = (self.probe)(&self.path) {
+            let degraded = available < self.min_available;
+            if degraded != self.degraded {
+                self.degraded = degraded;
+                (self.on_change)(if degraded {
+                    DiskStatus::Low { available }
+                } else {
+                    DiskStatus::Ok { available }
+                });
+            }
+        }
+    }
+}
+
+impl<T, S> OutputSink<T> for DiskGuard<T, S>
+where
+    S: OutputSink<T>,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        self.check();
+        if self.degraded && !(self.keep)(line.item()) {
+            return Box::pin(async { Ok(()) });
+        }
+        self.inner.write(line)
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        self.inner.close()
+    }
+
+    fn is_process_stream(&self) -> bool {
+        self.inner.is_process_stream()
+    }
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`
+/// # Errors
+///
+/// Will error if the filesystem can't be queried
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `statvfs` is plain old data for which all-zero is valid
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid nul-terminated string and `stat` is a
+    // properly sized, writable `statvfs`
+    if unsafe { libc::statvfs(path.as_ptr(), std::ptr::addr_of_mut!(stat)) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+/// Bytes available on the filesystem holding `path`, not supported on this
+/// platform so the guard never degrades
+/// # Errors
+///
+/// Never errors
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    use tokio::fs;
+
+    use crate::{
+        sink::{file::FileSink, OutputLine, OutputSink, Stream},
+        StdoutChannelError,
+    };
+
+    use super::{available_space, DiskGuard, DiskStatus};
+
+    #[test]
+    fn test_available_space() {
+        assert!(available_space(&std::env::temp_dir()).unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_disk_guard() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let available = Arc::new(AtomicU64::new(1 << 20));
+        let changes = Arc::new(Mutex::new(Vec::new()));
+
+        let mut guard = DiskGuard::new(FileSink::open(dir.join("out")).await?, dir, 1024)
+            .check_interval(Duration::ZERO)
+            .keep(|line: &&str| line.starts_with("ERROR"))
+            .on_change({
+                let changes = Arc::clone(&changes);
+                move |status| changes.lock().unwrap().push(status)
+            })
+            .with_probe({
+                let available = Arc::clone(&available);
+                move |_| Ok(available.load(Ordering::SeqCst))
+            });
+        for (line, free) in [
+            ("INFO one\n", 512),
+            ("DEBUG two\n", 512),
+            ("ERROR three\n", 4096),
+            ("INFO four\n", 4096),
+        ] {
+            guard
+                .write(OutputLine::new(line, line.as_bytes(), Stream::Stdout))
+                .await?;
+            available.store(free, Ordering::SeqCst);
+        }
+        assert!(!guard.is_degraded());
+        OutputSink::<&str>::close(&mut guard).await?;
+
+        assert_eq!(
+            fs::read_to_string(dir.join("out")).await?,
+            "INFO one\nERROR three\nINFO four\n"
+        );
+        assert_eq!(
+            changes.lock().unwrap().as_slice(),
+            [
+                DiskStatus::Low { available: 512 },
+                DiskStatus::Ok { available: 4096 }
+            ]
+        );
+        Ok(())
+    }
+}
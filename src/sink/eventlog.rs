@@ -0,0 +1,213 @@
+use std::{convert::TryFrom, io, iter, ptr};
+use windows_sys::Win32::{
+    Foundation::{ERROR_SUCCESS, HANDLE},
+    System::{
+        EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+        },
+        Registry::{
+            RegCloseKey, RegCreateKeyExW, RegSetValueExW, HKEY, HKEY_LOCAL_MACHINE, KEY_SET_VALUE,
+            REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+        },
+    },
+};
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture, Stream},
+    Level, StdoutChannelError,
+};
+
+/// Message file of `eventcreate`, its ids 1 to 1000 show the inserted line
+const MESSAGE_FILE: &str = r"%SystemRoot%\System32\EventCreate.exe";
+/// Longest string `ReportEventW` accepts, in UTF-16 units
+const MAX_MESSAGE_LEN: usize = 31_839;
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(iter::once(0)).collect()
+}
+
+fn event_type(level: Level) -> REPORT_EVENT_TYPE {
+    match level {
+        Level::Error => EVENTLOG_ERROR_TYPE,
+        Level::Warning => EVENTLOG_WARNING_TYPE,
+        _ => EVENTLOG_INFORMATION_TYPE,
+    }
+}
+
+/// Reports each line to the Windows Event Log of a registered source. The
+/// event type is error for stderr and information for stdout, unless the
+/// line starts with a level like `warning: `. Lines longer than the 31839
+/// characters an event can hold are cut.
+pub struct EventLogSink {
+    handle: HANDLE,
+    event_id: u32,
+}
+
+// SAFETY: event log handles aren't tied to the thread that opened them
+unsafe impl Send for EventLogSink {}
+
+impl EventLogSink {
+    /// Register `source` in the Application log, with the message file of
+    /// `eventcreate` so the Event Viewer shows lines as they are. Usually
+    /// done once by an installer, it needs administrator rights.
+    /// # Errors
+    ///
+    /// Will error if the registry key of the source can't be written
+    pub fn install_source(source: &str) -> Result<(), StdoutChannelError> {
+        let subkey = wide(&format!(
+            r"SYSTEM\CurrentControlSet\Services\EventLog\Application\{source}"
+        ));
+        let mut key: HKEY = ptr::null_mut();
+        // SAFETY: the strings are NUL-terminated and `key` is only used once
+        // the call succeeded
+        let status = unsafe {
+            RegCreateKeyExW(
+                HKEY_LOCAL_MACHINE,
+                subkey.as_ptr(),
+                0,
+                ptr::null(),
+                REG_OPTION_NON_VOLATILE,
+                KEY_SET_VALUE,
+                ptr::null(),
+                &mut key,
+                ptr::null_mut(),
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status.cast_signed()).into());
+        }
+        let file: Vec<u8> = wide(MESSAGE_FILE)
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let types = 7u32.to_le_bytes();
+        let mut status = ERROR_SUCCESS;
+        for (name, kind, data) in [
+            ("EventMessageFile", REG_EXPAND_SZ, &file[..]),
+            ("TypesSupported", REG_DWORD, &types[..]),
+        ] {
+            let name = wide(name);
+            // SAFETY: `key` is open and `data` is a valid value of `kind`
+            status = unsafe {
+                RegSetValueExW(
+                    key,
+                    name.as_ptr(),
+                    0,
+                    kind,
+                    data.as_ptr(),
+                    u32::try_from(data.len()).unwrap_or(u32::MAX),
+                )
+            };
+            if status != ERROR_SUCCESS {
+                break;
+            }
+        }
+        // SAFETY: `key` was opened above
+        unsafe { RegCloseKey(key) };
+        if status == ERROR_SUCCESS {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(status.cast_signed()).into())
+        }
+    }
+
+    /// Report to `source` on the local machine. Sources that weren't
+    /// installed still log, but the Event Viewer notes the missing
+    /// description.
+    /// # Errors
+    ///
+    /// Will error if the event log can't be opened
+    pub fn register(source: &str) -> Result<Self, StdoutChannelError> {
+        let source = wide(source);
+        // SAFETY: `source` is NUL-terminated
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self {
+            handle,
+            event_id: 1,
+        })
+    }
+
+    /// Event id of reported lines, 1 unless set. Installed sources show
+    /// the line for ids 1 to 1000.
+    #[must_use]
+    pub fn with_event_id(mut self, event_id: u32) -> Self {
+        self.event_id = event_id;
+        self
+    }
+
+    fn report(&self, bytes: &[u8], stream: Stream) -> Result<(), StdoutChannelError> {
+        let line = String::from_utf8_lossy(bytes.strip_suffix(b"\n").unwrap_or(bytes));
+        let kind = event_type(Level::of_line(&line, stream));
+        let mut message: Vec<u16> = line.encode_utf16().take(MAX_MESSAGE_LEN).collect();
+        message.push(0);
+        let strings = [message.as_ptr()];
+        // SAFETY: `strings` holds one NUL-terminated string alive for the call
+        let ok = unsafe {
+            ReportEventW(
+                self.handle,
+                kind,
+                0,
+                self.event_id,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EventLogSink {
+    fn drop(&mut self) {
+        // SAFETY: the handle was returned by `RegisterEventSourceW`
+        unsafe { DeregisterEventSource(self.handle) };
+    }
+}
+
+impl<T> OutputSink<T> for EventLogSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let result = self.report(line.bytes(), line.stream());
+        Box::pin(async { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sink::Stream, Level, MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{
+        event_type, EventLogSink, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+        EVENTLOG_WARNING_TYPE,
+    };
+
+    #[tokio::test]
+    async fn test_event_log_sink() -> Result<(), StdoutChannelError> {
+        assert_eq!(
+            event_type(Level::of_line("warning: disk low", Stream::Stdout)),
+            EVENTLOG_WARNING_TYPE
+        );
+        assert_eq!(
+            event_type(Level::of_line("failed", Stream::Stderr)),
+            EVENTLOG_ERROR_TYPE
+        );
+        assert_eq!(
+            event_type(Level::of_line("done", Stream::Stdout)),
+            EVENTLOG_INFORMATION_TYPE
+        );
+
+        let sink = EventLogSink::register("stdout-channel-test")?.with_event_id(100);
+        let chan = StdoutChannel::<String>::with_sinks(MockStdout::new(), sink);
+        chan.send_err("error: reported by the test suite");
+        chan.close().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use std::{collections::VecDeque, io, sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture},
+    sync::Mutex,
+};
+
+/// What a `FaultSink` does with a line
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultAction {
+    /// Write the line normally
+    Pass,
+    /// Fail the write without touching the inner sink
+    Fail,
+    /// Silently discard the line
+    Drop,
+    /// Write the line twice
+    Duplicate,
+    /// Write only the first `n` bytes of the line, then fail
+    Partial(usize),
+}
+
+/// A fault to inject into a single write: wait `delay`, then apply `action`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fault {
+    pub delay: Duration,
+    pub action: FaultAction,
+}
+
+impl Fault {
+    #[must_use]
+    pub fn pass() -> Self {
+        Self::from(FaultAction::Pass)
+    }
+
+    #[must_use]
+    pub fn fail() -> Self {
+        Self::from(FaultAction::Fail)
+    }
+
+    #[must_use]
+    pub fn drop_line() -> Self {
+        Self::from(FaultAction::Drop)
+    }
+
+    #[must_use]
+    pub fn duplicate() -> Self {
+        Self::from(FaultAction::Duplicate)
+    }
+
+    #[must_use]
+    pub fn partial(n: usize) -> Self {
+        Self::from(FaultAction::Partial(n))
+    }
+
+    /// Write normally after `delay`
+    #[must_use]
+    pub fn delay(delay: Duration) -> Self {
+        Self::pass().after(delay)
+    }
+
+    #[must_use]
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl From<FaultAction> for Fault {
+    fn from(action: FaultAction) -> Self {
+        Self {
+            delay: Duration::ZERO,
+            action,
+        }
+    }
+}
+
+/// Decides, before each write of a `FaultSink`, which fault to inject
+pub trait FaultInjector<T>: Send {
+    fn inject(&mut self, line: &OutputLine<'_, T>) -> Fault;
+}
+
+impl<T, F> FaultInjector<T> for F
+where
+    F: FnMut(&OutputLine<'_, T>) -> Fault + Send,
+{
+    fn inject(&mut self, line: &OutputLine<'_, T>) -> Fault {
+        self(line)
+    }
+}
+
+/// Faults queued from a test while the channel is running, each write takes
+/// the next one and passes once the queue is empty
+#[derive(Clone, Default)]
+pub struct FaultPlan(Arc<Mutex<VecDeque<Fault>>>);
+
+impl FaultPlan {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, fault: impl Into<Fault>) {
+        self.0.lock().push_back(fault.into());
+    }
+}
+
+impl<T> FaultInjector<T> for FaultPlan {
+    fn inject(&mut self, _line: &OutputLine<'_, T>) -> Fault {
+        self.0.lock().pop_front().unwrap_or_else(Fault::pass)
+    }
+}
+
+/// Random faults with a fixed latency, see `FlakySink`
+pub struct RandomFaults {
+    latency: Duration,
+    error_rate: f64,
+    partial_rate: f64,
+    rng: fastrand::Rng,
+}
+
+impl Default for RandomFaults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomFaults {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            error_rate: 0.0,
+            partial_rate: 0.0,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    #[must_use]
+    pub fn error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = error_rate;
+        self
+    }
+
+    #[must_use]
+    pub fn partial_rate(mut self, partial_rate: f64) -> Self {
+        self.partial_rate = partial_rate;
+        self
+    }
+
+    /// Use an RNG seeded with `seed` so the same faults are injected on
+    /// every run
+    #[must_use]
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+}
+
+impl<T> FaultInjector<T> for RandomFaults {
+    fn inject(&mut self, line: &OutputLine<'_, T>) -> Fault {
+        let fault = if self.rng.f64() < self.error_rate {
+            Fault::fail()
+        } else if self.rng.f64() < self.partial_rate {
+            Fault::partial(self.rng.usize(..line.bytes().len().max(1)))
+        } else {
+            Fault::pass()
+        };
+        fault.after(self.latency)
+    }
+}
+
+/// Wraps a sink and asks a `FaultInjector` what to do before every write,
+/// for testing how an application copes with a misbehaving output.
+pub struct FaultSink<S, F> {
+    inner: S,
+    injector: F,
+}
+
+impl<S, F> FaultSink<S, F> {
+    #[must_use]
+    pub fn new(inner: S, injector: F) -> Self {
+        Self { inner, injector }
+    }
+
+    pub fn injector_mut(&mut self) -> &mut F {
+        &mut self.injector
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<T, S, F> OutputSink<T> for FaultSink<S, F>
+where
+    S: OutputSink<T>,
+    F: FaultInjector<T>,
+    T: Clone + Send + 'static,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let fault = self.injector.inject(&line);
+        Box::pin(async move {
+            if fault.delay > Duration::ZERO {
+                sleep(fault.delay).await;
+            }
+            match fault.action {
+                FaultAction::Pass => self.inner.write(line).await,
+                FaultAction::Fail => Err(io::Error::other("injected write error").into()),
+                FaultAction::Drop => Ok(()),
+                FaultAction::Duplicate => {
+                    let again = OutputLine::new(line.item().clone(), line.bytes(), line.stream());
+                    self.inner.write(line).await?;
+                    self.inner.write(again).await
+                }
+                FaultAction::Partial(n) => {
+                    let (stream, bytes) = (line.stream(), line.bytes());
+                    let prefix = &bytes[..n.min(bytes.len())];
+                    self.inner
+                        .write(OutputLine::new(line.into_item(), prefix, stream))
+                        .await?;
+                    Err(io::Error::from(io::ErrorKind::WriteZero).into())
+                }
+            }
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        self.inner.close()
+    }
+
+    fn is_process_stream(&self) -> bool {
+        self.inner.is_process_stream()
+    }
+}
+
+/// Test helper wrapping a sink to simulate slow or unreliable output.
+///
+/// Every write is delayed by `latency`, fails with probability `error_rate`
+/// and with probability `partial_rate` only passes a prefix of the line on
+/// to the inner sink before failing, like a `write_all` interrupted midway.
+pub struct FlakySink<S>(FaultSink<S, RandomFaults>);
+
+impl<S> FlakySink<S> {
+    #[must_use]
+    pub fn new(inner: S) -> Self {
+        Self(FaultSink::new(inner, RandomFaults::new()))
+    }
+
+    #[must_use]
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.0.injector.latency = latency;
+        self
+    }
+
+    /// Probability, between 0 and 1, of a write failing
+    #[must_use]
+    pub fn error_rate(mut self, error_rate: f64) -> Self {
+        self.0.injector.error_rate = error_rate;
+        self
+    }
+
+    /// Probability, between 0 and 1, of a write being cut short
+    #[must_use]
+    pub fn partial_rate(mut self, partial_rate: f64) -> Self {
+        self.0.injector.partial_rate = partial_rate;
+        self
+    }
+
+    /// See `RandomFaults::with_rng`
+    #[must_use]
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.0.injector.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0.into_inner()
+    }
+}
+
+impl<T, S> OutputSink<T> for FlakySink<S>
+where
+    S: OutputSink<T>,
+    T: Clone + Send + 'static,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        self.0.write(line)
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        OutputSink::<T>::flush(&mut self.0)
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        OutputSink::<T>::close(&mut self.0)
+    }
+
+    fn is_process_stream(&self) -> bool {
+        OutputSink::<T>::is_process_stream(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use tokio::fs;
+
+    use crate::{sink::file::FileSink, StdoutChannel, StdoutChannelError};
+
+    use crate::sink::{OutputLine, Stream};
+
+    use super::{Fault, FaultAction, FaultInjector, FaultPlan, FaultSink, FlakySink, RandomFaults};
+
+    #[tokio::test]
+    async fn test_flaky_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+
+        let slow = FlakySink::new(FileSink::open(dir.join("slow")).await?)
+            .latency(Duration::from_millis(20));
+        let chan = StdoutChannel::<&str>::with_sinks(slow, FileSink::open(dir.join("err")).await?);
+        let start = Instant::now();
+        chan.send("one");
+        chan.send("two");
+        chan.close().await?;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(fs::read_to_string(dir.join("slow")).await?, "one\ntwo\n");
+
+        let failing = FlakySink::new(FileSink::open(dir.join("fail")).await?).error_rate(1.0);
+        let chan =
+            StdoutChannel::<&str>::with_sinks(failing, FileSink::open(dir.join("err")).await?);
+        chan.send("lost");
+        assert!(chan.close().await.is_err());
+
+        let torn = FlakySink::new(FileSink::open(dir.join("torn")).await?).partial_rate(1.0);
+        let chan = StdoutChannel::<&str>::with_sinks(torn, FileSink::open(dir.join("err")).await?);
+        chan.send("a long line");
+        assert!(chan.close().await.is_err());
+        let written = fs::read_to_string(dir.join("torn")).await?;
+        assert!("a long line\n".starts_with(&written) && written.len() < 12);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fault_plan() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let plan = FaultPlan::new();
+        let sink = FaultSink::new(FileSink::open(dir.join("out")).await?, plan.clone());
+        let chan = StdoutChannel::<&str>::with_sinks(sink, FileSink::open(dir.join("err")).await?);
+
+        plan.push(FaultAction::Drop);
+        plan.push(Fault::duplicate().after(Duration::from_millis(5)));
+        chan.send("dropped");
+        chan.send("twice");
+        chan.send("once");
+        chan.close().await?;
+        assert_eq!(
+            fs::read_to_string(dir.join("out")).await?,
+            "twice\ntwice\nonce\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded_random_faults() {
+        let faults = |seed| {
+            let mut injector = RandomFaults::new()
+                .error_rate(0.3)
+                .partial_rate(0.3)
+                .with_rng(seed);
+            let line = OutputLine::new((), b"some output\n", Stream::Stdout);
+            (0..32).map(|_| injector.inject(&line)).collect::<Vec<_>>()
+        };
+        assert_eq!(faults(7), faults(7));
+        assert_ne!(faults(7), faults(8));
+    }
+}
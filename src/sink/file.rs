@@ -0,0 +1,353 @@
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture},
+    StdoutChannel, StdoutChannelError,
+};
+
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// How file sinks open their files.
+///
+/// The defaults truncate existing files, create new ones with the usual
+/// `0o666` (minus umask) permissions and mark descriptors close-on-exec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileSinkOptions {
+    append: bool,
+    mode: Option<u32>,
+    cloexec: bool,
+    buffer_size: usize,
+}
+
+impl Default for FileSinkOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSinkOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            append: false,
+            mode: None,
+            cloexec: true,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+
+    /// Append to an existing file instead of truncating it, only used by
+    /// sinks writing a single file
+    #[must_use]
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Permission bits for newly created files, e.g. `0o600` for sensitive
+    /// logs (ignored on non-unix platforms)
+    #[must_use]
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Whether the descriptor is closed on `exec`, disable to let child
+    /// processes inherit the file
+    #[must_use]
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Bytes buffered before a `FileSink` writes to its file, 8 KiB by
+    /// default. 0 writes every line directly.
+    #[must_use]
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    #[must_use]
+    pub fn is_append(&self) -> bool {
+        self.append
+    }
+
+    /// Open a `FileSink` with these options
+    /// # Errors
+    ///
+    /// Will error if the file can't be opened
+    pub async fn open(self, path: impl AsRef<Path>) -> Result<FileSink, StdoutChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let file = self.open_file(&path, self.append).await?;
+        Ok(FileSink {
+            path,
+            writer: BufWriter::with_capacity(self.buffer_size, file),
+        })
+    }
+
+    /// Open `path` for writing, appending or truncating
+    pub(crate) async fn open_file(
+        &self,
+        path: &Path,
+        append: bool,
+    ) -> Result<File, StdoutChannelError> {
+        let mut options = OpenOptions::new();
+        options.create(true);
+        if append {
+            options.append(true);
+        } else {
+            options.write(true).truncate(true);
+        }
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            options.mode(mode);
+        }
+        let file = options.open(path).await?;
+        #[cfg(unix)]
+        if !self.cloexec {
+            clear_cloexec(&file)?;
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(unix)]
+fn clear_cloexec(file: &File) -> Result<(), StdoutChannelError> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is a valid descriptor owned by `file` for the whole call
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Directory for the log files of `app`: `$XDG_STATE_HOME/{app}` (default
+/// `~/.local/state/{app}`) on unix, `~/Library/Logs/{app}` on macOS and
+/// `%LOCALAPPDATA%\{app}\logs` on Windows
+#[must_use]
+pub fn default_log_dir(app: &str) -> Option<PathBuf> {
+    log_dir_from(app, |name| std::env::var_os(name))
+}
+
+fn log_dir_from(app: &str, var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let var = |name| var(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        return var("LOCALAPPDATA").map(|d| d.join(app).join("logs"));
+    }
+    let home = var("HOME");
+    if cfg!(target_os = "macos") {
+        return home.map(|h| h.join("Library").join("Logs").join(app));
+    }
+    var("XDG_STATE_HOME")
+        .filter(|d| d.is_absolute())
+        .or_else(|| home.map(|h| h.join(".local").join("state")))
+        .map(|d| d.join(app))
+}
+
+/// Path and options of the log file of `default_for_app`, creating its
+/// directory
+pub(crate) async fn default_app_file(
+    app: &str,
+) -> Result<(PathBuf, FileSinkOptions), StdoutChannelError> {
+    let dir = default_log_dir(app)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no home directory"))?;
+    tokio::fs::create_dir_all(&dir).await?;
+    let options = FileSinkOptions::new().append(true).mode(0o600);
+    Ok((dir.join(format!("{app}.log")), options))
+}
+
+/// Buffered sink writing every line to a single file
+pub struct FileSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl FileSink {
+    /// Open `path` with the default `FileSinkOptions`, truncating it
+    /// # Errors
+    ///
+    /// Will error if the file can't be opened
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, StdoutChannelError> {
+        FileSinkOptions::new().open(path).await
+    }
+
+    #[must_use]
+    pub fn options() -> FileSinkOptions {
+        FileSinkOptions::new()
+    }
+
+    /// Append to `{app}.log` in the platform's usual log directory, see
+    /// `default_log_dir`, creating the directory. New files are only
+    /// readable by the current user. The file is never rotated, with the
+    /// `rotation` feature `RotatingFileSink::default_for_app` writes the same
+    /// file with rotation defaults.
+    /// # Errors
+    ///
+    /// Will error if no home directory can be determined or the directory or
+    /// file can't be created
+    pub async fn default_for_app(app: &str) -> Result<Self, StdoutChannelError> {
+        let (path, options) = default_app_file(app).await?;
+        options.open(path).await
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        self.writer.write_all(bytes).await?;
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for FileSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Write stdout and stderr to two files, truncating them
+    /// # Errors
+    ///
+    /// Will error if either file can't be opened
+    pub async fn with_files(
+        stdout_path: impl AsRef<Path>,
+        stderr_path: impl AsRef<Path>,
+    ) -> Result<Self, StdoutChannelError> {
+        Self::with_files_options(stdout_path, stderr_path, FileSinkOptions::new()).await
+    }
+
+    /// Write stdout and stderr to two files opened with `options`, e.g.
+    /// `FileSink::options().append(true)`
+    /// # Errors
+    ///
+    /// Will error if either file can't be opened
+    pub async fn with_files_options(
+        stdout_path: impl AsRef<Path>,
+        stderr_path: impl AsRef<Path>,
+        options: FileSinkOptions,
+    ) -> Result<Self, StdoutChannelError> {
+        Ok(Self::with_sinks(
+            options.open(stdout_path).await?,
+            options.open(stderr_path).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use crate::{StdoutChannel, StdoutChannelError};
+
+    use super::FileSink;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_default_log_dir() {
+        use std::{ffi::OsString, path::Path};
+
+        use super::log_dir_from;
+
+        let dir = log_dir_from("app", |name| match name {
+            "HOME" => Some(OsString::from("/home/me")),
+            _ => None,
+        });
+        assert_eq!(dir.as_deref(), Some(Path::new("/home/me/.local/state/app")));
+        let dir = log_dir_from("app", |name| match name {
+            "HOME" => Some("/home/me".into()),
+            "XDG_STATE_HOME" => Some("/state".into()),
+            _ => None,
+        });
+        assert_eq!(dir.as_deref(), Some(Path::new("/state/app")));
+        assert_eq!(log_dir_from("app", |_| None), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_options() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let out = dir.join("out.log");
+        let err = dir.join("err.log");
+        fs::write(&out, "old\n").await?;
+        fs::write(&err, "old\n").await?;
+
+        let chan = StdoutChannel::<String>::with_sinks(
+            FileSink::options()
+                .append(true)
+                .mode(0o600)
+                .open(&out)
+                .await?,
+            FileSink::open(&err).await?,
+        );
+        chan.send("new");
+        chan.send_err("new");
+        chan.close().await?;
+
+        assert_eq!(fs::read_to_string(&out).await?, "old\nnew\n");
+        assert_eq!(fs::read_to_string(&err).await?, "new\n");
+
+        let chan = StdoutChannel::<String>::with_files_options(
+            &out,
+            &err,
+            FileSink::options().append(true),
+        )
+        .await?;
+        chan.send("again");
+        chan.close().await?;
+        assert_eq!(fs::read_to_string(&out).await?, "old\nnew\nagain\n");
+        let chan = StdoutChannel::<String>::with_files(&out, &err).await?;
+        chan.send_err("truncated");
+        chan.close().await?;
+        assert_eq!(fs::read_to_string(&out).await?, "");
+        assert_eq!(fs::read_to_string(&err).await?, "truncated\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::{fs::PermissionsExt, io::AsRawFd};
+
+            let secret = dir.join("secret.log");
+            let sink = FileSink::options()
+                .mode(0o600)
+                .cloexec(false)
+                .open(&secret)
+                .await?;
+            let mode = fs::metadata(&secret).await?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let fd = sink.writer.get_ref().as_raw_fd();
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+            assert_eq!(flags & libc::FD_CLOEXEC, 0);
+        }
+
+        Ok(())
+    }
+}
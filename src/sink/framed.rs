@@ -0,0 +1,158 @@
+use std::time::SystemTime;
+
+use crate::{
+    clock::{Clock, TimeZone, Timestamp, TimestampFormat},
+    sink::{OutputLine, OutputSink, SinkFuture},
+};
+
+/// Wraps a sink, prefixing each line with the time it is written and/or a
+/// fixed tag, and/or ending it with a terminator other than `\n`, e.g.
+/// `\r\n` for a serial console
+pub struct FramedSink<S> {
+    inner: S,
+    terminator: Box<[u8]>,
+    prefix: Box<[u8]>,
+    timestamps: bool,
+    clock: Clock,
+    time_zone: TimeZone,
+    format: TimestampFormat,
+    buf: Vec<u8>,
+}
+
+impl<S> FramedSink<S> {
+    /// Wrap `inner` without changing its lines
+    #[must_use]
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            terminator: (*b"\n").into(),
+            prefix: Box::default(),
+            timestamps: false,
+            clock: Clock::System,
+            time_zone: TimeZone::Utc,
+            format: TimestampFormat::Rfc3339,
+            buf: Vec::new(),
+        }
+    }
+
+    /// End lines with `terminator` instead of `\n`
+    #[must_use]
+    pub fn with_terminator(mut self, terminator: &str) -> Self {
+        self.terminator = terminator.as_bytes().into();
+        self
+    }
+
+    /// Prefix lines with `prefix`, e.g. `[worker-3] `, after the timestamp
+    /// if there is one
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.as_bytes().into();
+        self
+    }
+
+    /// Prefix lines with a timestamp like `2024-01-02T03:04:05Z `, read
+    /// from the system clock unless set with `with_clock`
+    #[must_use]
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Write timestamps in `format` instead of RFC 3339
+    #[must_use]
+    pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Write wall-clock timestamps in `zone` instead of UTC
+    #[must_use]
+    pub fn with_time_zone(mut self, zone: TimeZone) -> Self {
+        self.time_zone = zone;
+        self
+    }
+
+    /// Replace the wall clock used for timestamps
+    #[must_use]
+    pub fn with_now(self, now: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.with_clock(Clock::custom(move || Timestamp::Utc(now())))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn frame(&mut self, bytes: &[u8]) {
+        self.buf.clear();
+        if self.timestamps {
+            let timestamp = self.clock.now().in_zone(self.time_zone);
+            self.buf
+                .extend_from_slice(self.format.format(timestamp).as_bytes());
+            self.buf.push(b' ');
+        }
+        self.buf.extend_from_slice(&self.prefix);
+        self.buf
+            .extend_from_slice(bytes.strip_suffix(b"\n").unwrap_or(bytes));
+        self.buf.extend_from_slice(&self.terminator);
+    }
+}
+
+impl<T, S> OutputSink<T> for FramedSink<S>
+where
+    S: OutputSink<T>,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        self.frame(line.bytes());
+        let stream = line.stream();
+        self.inner
+            .write(OutputLine::new(line.into_item(), &self.buf, stream))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        self.inner.close()
+    }
+
+    fn is_process_stream(&self) -> bool {
+        self.inner.is_process_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use tokio::fs;
+
+    use crate::{sink::file::FileSink, MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::FramedSink;
+
+    #[tokio::test]
+    async fn test_framed_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("framed.log");
+        let stdout = FramedSink::new(FileSink::open(&path).await?)
+            .with_terminator("\r\n")
+            .with_timestamps(true)
+            .with_prefix("[tty] ")
+            .with_now(|| UNIX_EPOCH + Duration::from_secs(1_704_164_645));
+        let chan = StdoutChannel::<&str>::with_sinks(stdout, MockStdout::new());
+        chan.send("a");
+        chan.send("b");
+        chan.close().await?;
+        assert_eq!(
+            fs::read_to_string(&path).await?,
+            "2024-01-02T03:04:05Z [tty] a\r\n2024-01-02T03:04:05Z [tty] b\r\n"
+        );
+        Ok(())
+    }
+}
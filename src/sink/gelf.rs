@@ -0,0 +1,353 @@
+use std::{
+    fmt::Write,
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket};
+
+use crate::{
+    banner::json_string,
+    clock::{Clock, Timestamp},
+    sink::{OutputLine, OutputSink, SinkFuture, Stream},
+    zlib, Level, StdoutChannelError,
+};
+
+/// Graylog's default chunk size, small enough for most paths' MTU
+pub const DEFAULT_CHUNK_SIZE: usize = 1420;
+
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+const CHUNK_HEADER_LEN: usize = 12;
+const MAX_CHUNKS: usize = 128;
+
+/// Turns lines into GELF 1.1 messages. The level is 3 (error) for stderr
+/// and 6 (info) for stdout, unless the line starts with a level like
+/// `warning: ` as written by `print_summary`.
+///
+/// Fields added with `with_field` are in every message, `GelfSink::with_tags`
+/// adds fields taken from each item.
+pub struct GelfFormatter {
+    host: String,
+    fields: Vec<(String, String)>,
+    clock: Clock,
+}
+
+impl GelfFormatter {
+    #[must_use]
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            fields: Vec::new(),
+            clock: Clock::System,
+        }
+    }
+
+    /// Add `_key` to every message, e.g. a service name or environment
+    #[must_use]
+    pub fn with_field(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.fields.push((field_key(key), value.into()));
+        self
+    }
+
+    /// Read the `timestamp` field from `clock`, timestamps that aren't
+    /// wall-clock times are left out so Graylog uses the time of receipt
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The JSON payload of one line, without its trailing newline
+    #[must_use]
+    pub fn format(&self, line: &[u8], stream: Stream) -> Vec<u8> {
+        self.format_tagged(line, stream, &[])
+    }
+
+    /// Same as `format` also adding `_key` for every one of `tags`, after
+    /// the fields of `with_field`
+    #[must_use]
+    pub fn format_tagged(&self, line: &[u8], stream: Stream, tags: &[(String, String)]) -> Vec<u8> {
+        let line = String::from_utf8_lossy(line.strip_suffix(b"\n").unwrap_or(line));
+        let level = Level::of_line(&line, stream).syslog_severity();
+        let mut payload = format!(
+            r#"{{"version":"1.1","host":{},"short_message":{},"level":{level}"#,
+            json_string(&self.host),
+            json_string(&line),
+        );
+        if let Timestamp::Utc(time) | Timestamp::Offset(time, _) = self.clock.now() {
+            if let Ok(since_epoch) = time.duration_since(std::time::UNIX_EPOCH) {
+                write!(
+                    payload,
+                    r#","timestamp":{}.{:06}"#,
+                    since_epoch.as_secs(),
+                    since_epoch.subsec_micros()
+                )
+                .ok();
+            }
+        }
+        write!(payload, r#","_stream":"{stream}""#).ok();
+        for (key, value) in &self.fields {
+            write!(payload, r#","_{key}":{}"#, json_string(value)).ok();
+        }
+        for (key, value) in tags {
+            let key = field_key(key);
+            write!(payload, r#","_{key}":{}"#, json_string(value)).ok();
+        }
+        payload.push('}');
+        payload.into_bytes()
+    }
+}
+
+/// `key` without the characters GELF doesn't allow in field names
+fn field_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        .collect()
+}
+
+/// Split `payload` into GELF chunks of at most `chunk_size` bytes, or
+/// return it whole if it fits into one datagram
+fn chunks(payload: &[u8], chunk_size: usize, id: u64) -> io::Result<Vec<Vec<u8>>> {
+    if payload.len() <= chunk_size {
+        return Ok(vec![payload.to_vec()]);
+    }
+    let body = chunk_size - CHUNK_HEADER_LEN;
+    let count = payload.len().div_ceil(body);
+    if count > MAX_CHUNKS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "GELF message of {} bytes needs {count} chunks, at most {MAX_CHUNKS} are allowed",
+                payload.len()
+            ),
+        ));
+    }
+    Ok(payload
+        .chunks(body)
+        .enumerate()
+        .map(|(seq, part)| {
+            let mut chunk = Vec::with_capacity(CHUNK_HEADER_LEN + part.len());
+            chunk.extend_from_slice(&CHUNK_MAGIC);
+            chunk.extend_from_slice(&id.to_be_bytes());
+            // both fit in a byte, count is at most 128
+            #[allow(clippy::cast_possible_truncation)]
+            chunk.extend_from_slice(&[seq as u8, count as u8]);
+            chunk.extend_from_slice(part);
+            chunk
+        })
+        .collect())
+}
+
+/// Fields added to the GELF message of each line, taken from the item that
+/// was sent, see `GelfSink::with_tags`
+pub trait GelfTags<T>: Send {
+    fn tags(&self, item: &T) -> Vec<(String, String)>;
+}
+
+impl<T> GelfTags<T> for () {
+    fn tags(&self, _: &T) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+impl<T, F> GelfTags<T> for F
+where
+    F: Fn(&T) -> Vec<(String, String)> + Send,
+{
+    fn tags(&self, item: &T) -> Vec<(String, String)> {
+        self(item)
+    }
+}
+
+/// Sends each line as a GELF message over UDP, zlib compressed if set with
+/// `with_compression` and chunked when it doesn't fit into one datagram.
+/// Messages needing more than 128 chunks fail the write.
+pub struct GelfSink<F = ()> {
+    socket: UdpSocket,
+    formatter: GelfFormatter,
+    chunk_size: usize,
+    compression: bool,
+    rng: fastrand::Rng,
+    tags: F,
+}
+
+impl GelfSink {
+    /// # Errors
+    ///
+    /// Will error if `addr` can't be resolved or a socket can't be bound
+    pub async fn connect(
+        addr: impl ToSocketAddrs,
+        formatter: GelfFormatter,
+    ) -> Result<Self, StdoutChannelError> {
+        let addr = lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(addr).await?;
+        Ok(Self {
+            socket,
+            formatter,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compression: false,
+            rng: fastrand::Rng::new(),
+            tags: (),
+        })
+    }
+}
+
+impl<F> GelfSink<F> {
+    /// Largest datagram sent, `DEFAULT_CHUNK_SIZE` unless set, at least 13
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(CHUNK_HEADER_LEN + 1);
+        self
+    }
+
+    /// Draw the message ids of chunked messages from an RNG seeded with
+    /// `seed` so runs are reproducible
+    #[must_use]
+    pub fn with_rng(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// Compress messages with zlib before chunking them, Graylog detects
+    /// it. Compressed messages take fewer chunks but cost CPU time.
+    #[must_use]
+    pub fn with_compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Add the fields `tags` returns for the item of each line, e.g. a
+    /// request id or user parsed from it, see `GelfFormatter::format_tagged`
+    #[must_use]
+    pub fn with_tags<G>(self, tags: G) -> GelfSink<G> {
+        GelfSink {
+            socket: self.socket,
+            formatter: self.formatter,
+            chunk_size: self.chunk_size,
+            compression: self.compression,
+            rng: self.rng,
+            tags,
+        }
+    }
+
+    async fn send(
+        &mut self,
+        bytes: &[u8],
+        stream: Stream,
+        tags: Vec<(String, String)>,
+    ) -> Result<(), StdoutChannelError> {
+        let mut payload = self.formatter.format_tagged(bytes, stream, &tags);
+        if self.compression {
+            payload = zlib::compress(&payload);
+        }
+        for chunk in chunks(&payload, self.chunk_size, self.rng.u64(..))? {
+            self.socket.send(&chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, F> OutputSink<T> for GelfSink<F>
+where
+    F: GelfTags<T>,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let tags = self.tags.tags(line.item());
+        let (bytes, stream) = (line.bytes(), line.stream());
+        Box::pin(self.send(bytes, stream, tags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use tokio::net::UdpSocket;
+
+    use crate::{
+        sink::Stream, zlib, Clock, ManualClock, MockStdout, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::{chunks, GelfFormatter, GelfSink};
+
+    #[tokio::test]
+    async fn test_gelf_sink() -> Result<(), StdoutChannelError> {
+        let formatter = GelfFormatter::new("web-1")
+            .with_field("service name", "api")
+            .with_clock(Clock::Manual(ManualClock::new(
+                UNIX_EPOCH + Duration::from_millis(1_704_164_645_250),
+            )));
+        assert_eq!(
+            String::from_utf8(formatter.format(b"warning: slow \"query\"\n", Stream::Stdout))
+                .unwrap(),
+            r#"{"version":"1.1","host":"web-1","short_message":"warning: slow \"query\"","level":4,"timestamp":1704164645.250000,"_stream":"stdout","_servicename":"api"}"#
+        );
+        assert!(chunks(&[0; 2000], 14, 1).is_err());
+
+        let server = UdpSocket::bind("127.0.0.1:0").await?;
+        let sink = GelfSink::connect(server.local_addr()?, GelfFormatter::new("web-1"))
+            .await?
+            .with_chunk_size(64)
+            .with_rng(7);
+        let chan = StdoutChannel::<String>::with_sinks(MockStdout::new(), sink);
+        let long = "x".repeat(100);
+        chan.send_err(long.clone());
+        chan.close().await?;
+
+        let mut payload = Vec::new();
+        let mut buf = [0; 64];
+        let mut count = None;
+        let mut seq = 0;
+        while count != Some(seq) {
+            let n = server.recv(&mut buf).await?;
+            assert_eq!(&buf[..2], [0x1e, 0x0f]);
+            assert_eq!(
+                buf[2..10],
+                fastrand::Rng::with_seed(7).u64(..).to_be_bytes()
+            );
+            assert_eq!(buf[10], seq);
+            count = Some(buf[11]);
+            payload.extend_from_slice(&buf[12..n]);
+            seq += 1;
+        }
+        let message: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(message["short_message"], long.as_str());
+        assert_eq!(message["level"], 3);
+        assert_eq!(message["_stream"], "stderr");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gelf_compression_and_tags() -> Result<(), StdoutChannelError> {
+        let formatter = || {
+            GelfFormatter::new("web-1").with_clock(Clock::Manual(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(1_704_164_645),
+            )))
+        };
+        let server = UdpSocket::bind("127.0.0.1:0").await?;
+        let sink = GelfSink::connect(server.local_addr()?, formatter())
+            .await?
+            .with_compression(true)
+            .with_tags(|line: &String| {
+                let user = line.split(':').next().unwrap_or_default();
+                vec![("user id".to_string(), user.to_string())]
+            });
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        chan.send("alice: logged in");
+        chan.close().await?;
+
+        let mut buf = [0; 1420];
+        let n = server.recv(&mut buf).await?;
+        let tags = [("userid".to_string(), "alice".to_string())];
+        let payload = formatter().format_tagged(b"alice: logged in", Stream::Stdout, &tags);
+        assert!(String::from_utf8_lossy(&payload).ends_with(r#","_userid":"alice"}"#));
+        assert_eq!(buf[..n], zlib::compress(&payload));
+        Ok(())
+    }
+}
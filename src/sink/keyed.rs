@@ -0,0 +1,317 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    sink::{file::FileSinkOptions, retention::RetentionPolicy, OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+struct OpenFile {
+    writer: BufWriter<File>,
+    last_used: u64,
+    written: u64,
+}
+
+/// Routes each line to `<dir>/<key>.log`, where the key is extracted from the
+/// item (e.g. a tenant or module name). Characters other than ASCII
+/// letters, digits, `-`, `_` and `.` are replaced with `_`, keys that end up
+/// the same like `a/b` and `a_b` share a file.
+///
+/// Files are opened on first use and at most `max_open_files` are kept open,
+/// the least recently used one is closed to make room. A file reopened after
+/// being closed is always appended to.
+///
+/// With `max_bytes` every key's file is rotated the same way, renamed to
+/// `<key>.log.1`, `<key>.log.2`, ... once it would grow past the limit, and
+/// `with_retention` then deletes the old rotated files of that key.
+pub struct KeyedFileSink<F> {
+    dir: PathBuf,
+    key_fn: F,
+    options: FileSinkOptions,
+    max_open_files: usize,
+    max_bytes: Option<u64>,
+    retention: Option<RetentionPolicy>,
+    open: HashMap<PathBuf, OpenFile>,
+    seen: HashSet<PathBuf>,
+    tick: u64,
+}
+
+impl<F> KeyedFileSink<F> {
+    /// # Errors
+    ///
+    /// Will error if the directory can't be created
+    pub async fn new(dir: impl AsRef<Path>, key_fn: F) -> Result<Self, StdoutChannelError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            key_fn,
+            options: FileSinkOptions::new(),
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            max_bytes: None,
+            retention: None,
+            open: HashMap::new(),
+            seen: HashSet::new(),
+            tick: 0,
+        })
+    }
+
+    #[must_use]
+    pub fn with_options(mut self, options: FileSinkOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[must_use]
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files.max(1);
+        self
+    }
+
+    /// Rotate a key's file before a write would take it past `max_bytes`, a
+    /// single longer line still gets written to a fresh file
+    #[must_use]
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Delete the rotated files of a key outside `retention` after each of
+    /// its rotations
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Path of the file lines with `key` are written to
+    #[must_use]
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        let name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = name.trim_start_matches('.');
+        let name = if name.is_empty() { "_" } else { name };
+        self.dir.join(format!("{name}.log"))
+    }
+
+    async fn write_keyed(&mut self, key: &str, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        self.tick += 1;
+        // distinct keys may share a file, e.g. `a/b` and `a_b`, so files are
+        // tracked by path
+        let path = self.path_for(key);
+        if !self.open.contains_key(&path) {
+            self.open_path(&path).await?;
+        }
+        let len = bytes.len() as u64;
+        let too_big = self.open.get(&path).is_some_and(|open| {
+            self.max_bytes
+                .is_some_and(|max| open.written > 0 && open.written + len > max)
+        });
+        if too_big {
+            self.rotate(&path).await?;
+            self.open_path(&path).await?;
+        }
+        if let Some(open) = self.open.get_mut(&path) {
+            open.last_used = self.tick;
+            open.writer.write_all(bytes).await?;
+            open.written += len;
+        }
+        Ok(())
+    }
+
+    async fn open_path(&mut self, path: &Path) -> Result<(), StdoutChannelError> {
+        if self.open.len() >= self.max_open_files {
+            self.evict().await?;
+        }
+        let append = self.options.is_append() || self.seen.contains(path);
+        let file = self.options.open_file(path, append).await?;
+        let written = file.metadata().await?.len();
+        self.seen.insert(path.to_path_buf());
+        self.open.insert(
+            path.to_path_buf(),
+            OpenFile {
+                writer: BufWriter::new(file),
+                last_used: 0,
+                written,
+            },
+        );
+        Ok(())
+    }
+
+    /// Rename the file at `path` to the next free number and apply the
+    /// retention to its rotated files
+    async fn rotate(&mut self, path: &Path) -> Result<(), StdoutChannelError> {
+        if let Some(mut open) = self.open.remove(path) {
+            open.writer.flush().await?;
+        }
+        let mut rotated = rotated_files(path).await?;
+        let number = rotated.last().map_or(1, |(number, _)| number + 1);
+        let mut name = OsString::from(path.as_os_str());
+        name.push(format!(".{number}"));
+        fs::rename(path, &name).await?;
+        rotated.push((number, name.into()));
+        if let Some(retention) = self.retention {
+            retention
+                .apply(rotated.into_iter().map(|(_, path)| path).collect())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn evict(&mut self) -> Result<(), StdoutChannelError> {
+        let oldest = self
+            .open
+            .iter()
+            .min_by_key(|(_, open)| open.last_used)
+            .map(|(path, _)| path.clone());
+        if let Some(mut open) = oldest.and_then(|path| self.open.remove(&path)) {
+            open.writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_all(&mut self) -> Result<(), StdoutChannelError> {
+        for open in self.open.values_mut() {
+            open.writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// The files rotated out of `path`, by increasing number
+async fn rotated_files(path: &Path) -> Result<Vec<(usize, PathBuf)>, StdoutChannelError> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{name}.");
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let number = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .filter(|suffix| suffix.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|suffix| suffix.parse().ok());
+        if let Some(number) = number {
+            files.push((number, entry.path()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+impl<T, F> OutputSink<T> for KeyedFileSink<F>
+where
+    F: Fn(&T) -> String + Send,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let key = (self.key_fn)(line.item());
+        let bytes = line.bytes();
+        Box::pin(async move { self.write_keyed(&key, bytes).await })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_all())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use crate::{
+        sink::{file::FileSink, retention::RetentionPolicy},
+        MockStdout, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::KeyedFileSink;
+
+    #[tokio::test]
+    async fn test_keyed_file_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let tenant = |line: &String| line.split(':').next().unwrap_or_default().to_string();
+        let sink = KeyedFileSink::new(&dir, tenant).await?.max_open_files(1);
+        assert_eq!(sink.path_for("../etc/passwd"), dir.join("_etc_passwd.log"));
+
+        let chan =
+            StdoutChannel::<String>::with_sinks(sink, FileSink::open(dir.join("err")).await?);
+        for line in ["acme: one", "globex: two", "acme: three", "globex: four"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+
+        assert_eq!(
+            fs::read_to_string(dir.join("acme.log")).await?,
+            "acme: one\nacme: three\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("globex.log")).await?,
+            "globex: two\nglobex: four\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rotation() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let tenant = |line: &String| line.split(':').next().unwrap_or_default().to_string();
+        let sink = KeyedFileSink::new(&dir, tenant)
+            .await?
+            .max_bytes(12)
+            .with_retention(RetentionPolicy::new().max_files(1));
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        for line in ["a: one", "b: one", "a: two", "a: three"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+
+        assert_eq!(fs::read_to_string(dir.join("a.log")).await?, "a: three\n");
+        assert_eq!(fs::read_to_string(dir.join("a.log.2")).await?, "a: two\n");
+        assert!(!fs::try_exists(dir.join("a.log.1")).await?);
+        assert_eq!(fs::read_to_string(dir.join("b.log")).await?, "b: one\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_colliding_keys() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let key = |line: &String| line.split(':').next().unwrap_or_default().to_string();
+        let sink = KeyedFileSink::new(&dir, key).await?;
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        for line in ["a/b: one", "a_b: two", "a/b: three"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+        assert_eq!(
+            fs::read_to_string(dir.join("a_b.log")).await?,
+            "a/b: one\na_b: two\na/b: three\n"
+        );
+        Ok(())
+    }
+}
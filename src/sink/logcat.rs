@@ -0,0 +1,122 @@
+use std::{
+    ffi::CString,
+    io,
+    os::raw::{c_char, c_int},
+};
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture, Stream},
+    Level, StdoutChannelError,
+};
+
+const ANDROID_LOG_DEBUG: c_int = 3;
+const ANDROID_LOG_INFO: c_int = 4;
+const ANDROID_LOG_WARN: c_int = 5;
+const ANDROID_LOG_ERROR: c_int = 6;
+
+/// Below the 4068 bytes logd keeps of an entry, tag and priority included
+const MAX_ENTRY_LEN: usize = 4000;
+
+#[link(name = "log")]
+extern "C" {
+    fn __android_log_write(prio: c_int, tag: *const c_char, text: *const c_char) -> c_int;
+}
+
+fn priority(level: Level) -> c_int {
+    match level {
+        Level::Error => ANDROID_LOG_ERROR,
+        Level::Warning => ANDROID_LOG_WARN,
+        Level::Info => ANDROID_LOG_INFO,
+        _ => ANDROID_LOG_DEBUG,
+    }
+}
+
+/// Split `line` into entries logd won't truncate, at char boundaries
+fn entries(line: &str) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = rest.len().min(MAX_ENTRY_LEN);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (entry, tail) = rest.split_at(end);
+        rest = tail;
+        Some(entry)
+    })
+}
+
+/// Writes lines to logcat under a tag, for Rust code in Android apps where
+/// stdout goes nowhere. The priority is error for stderr and info for
+/// stdout unless the line starts with a level like `warning: `. Lines
+/// longer than a log entry are split over several.
+pub struct LogcatSink {
+    tag: CString,
+}
+
+impl LogcatSink {
+    /// # Errors
+    ///
+    /// Will error if `tag` contains a NUL byte
+    pub fn new(tag: &str) -> Result<Self, StdoutChannelError> {
+        let tag = CString::new(tag).map_err(io::Error::from)?;
+        Ok(Self { tag })
+    }
+
+    fn write_line(&self, bytes: &[u8], stream: Stream) -> Result<(), StdoutChannelError> {
+        let line = String::from_utf8_lossy(bytes.strip_suffix(b"\n").unwrap_or(bytes));
+        let prio = priority(Level::of_line(&line, stream));
+        let line = line.replace('\0', "");
+        for entry in entries(&line) {
+            let text = CString::new(entry).map_err(io::Error::from)?;
+            // SAFETY: both strings are NUL-terminated and outlive the call
+            let result = unsafe { __android_log_write(prio, self.tag.as_ptr(), text.as_ptr()) };
+            if result < 0 {
+                return Err(io::Error::from_raw_os_error(-result).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for LogcatSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let result = self.write_line(line.bytes(), line.stream());
+        Box::pin(async { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sink::Stream, Level, StdoutChannel, StdoutChannelError};
+
+    use super::{entries, priority, LogcatSink, ANDROID_LOG_ERROR, ANDROID_LOG_WARN};
+
+    #[tokio::test]
+    async fn test_logcat_sink() -> Result<(), StdoutChannelError> {
+        assert_eq!(
+            priority(Level::of_line("failed", Stream::Stderr)),
+            ANDROID_LOG_ERROR
+        );
+        assert_eq!(
+            priority(Level::of_line("warning: disk low", Stream::Stdout)),
+            ANDROID_LOG_WARN
+        );
+        let long = "é".repeat(3000);
+        let parts: Vec<_> = entries(&long).collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts.concat(), long);
+        assert!(LogcatSink::new("bad\0tag").is_err());
+
+        let chan = StdoutChannel::<String>::with_sinks(
+            LogcatSink::new("stdout-channel")?,
+            LogcatSink::new("stdout-channel")?,
+        );
+        chan.send("info line");
+        chan.send_err("error line");
+        chan.close().await?;
+        Ok(())
+    }
+}
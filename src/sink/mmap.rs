@@ -0,0 +1,209 @@
+use memmap2::MmapMut;
+use std::{
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+use tokio::task::spawn_blocking;
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+const DEFAULT_PREALLOCATE: u64 = 8 * 1024 * 1024;
+const DEFAULT_SYNC_EVERY: u64 = 1024 * 1024;
+
+/// Appends lines to a memory mapped file.
+///
+/// The file is grown `preallocate` bytes at a time, writes are plain copies
+/// into the mapping and an asynchronous `msync` is started every `sync_every`
+/// bytes and on flush. On close the mapping is synced on a blocking thread
+/// (in place on drop) and the file truncated to the bytes actually written.
+///
+/// The file must not be truncated by another process while the sink is open.
+pub struct MmapFileSink {
+    path: PathBuf,
+    file: File,
+    map: Option<MmapMut>,
+    len: u64,
+    preallocate: u64,
+    sync_every: u64,
+    unsynced: u64,
+}
+
+impl MmapFileSink {
+    /// Create (or truncate) the file at `path`
+    /// # Errors
+    ///
+    /// Will error if the file can't be created or mapped
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, StdoutChannelError> {
+        Self::with_preallocate(path, DEFAULT_PREALLOCATE)
+    }
+
+    /// Create the file at `path`, growing it `preallocate` bytes at a time
+    /// # Errors
+    ///
+    /// Will error if the file can't be created or mapped
+    pub fn with_preallocate(
+        path: impl AsRef<Path>,
+        preallocate: u64,
+    ) -> Result<Self, StdoutChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        let mut sink = Self {
+            path,
+            file,
+            map: None,
+            len: 0,
+            preallocate: preallocate.max(4096),
+            sync_every: DEFAULT_SYNC_EVERY,
+            unsynced: 0,
+        };
+        sink.grow(0)?;
+        Ok(sink)
+    }
+
+    /// Start an asynchronous `msync` after this many bytes were written
+    #[must_use]
+    pub fn sync_every(mut self, bytes: u64) -> Self {
+        self.sync_every = bytes;
+        self
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes written so far
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn grow(&mut self, needed: u64) -> Result<(), StdoutChannelError> {
+        let capacity = self.map.as_ref().map_or(0, |m| m.len() as u64);
+        let size = capacity + self.preallocate.max(needed);
+        self.map = None;
+        self.file.set_len(size)?;
+        // SAFETY: the file was opened read/write by this sink, which is the
+        // only writer, and it isn't shrunk while mapped
+        self.map = Some(unsafe { MmapMut::map_mut(&self.file)? });
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        let n = bytes.len() as u64;
+        let capacity = self.map.as_ref().map_or(0, |m| m.len() as u64);
+        if self.len + n > capacity {
+            self.grow(n)?;
+        }
+        if let Some(map) = self.map.as_mut() {
+            let start = usize::try_from(self.len).unwrap_or(usize::MAX);
+            map[start..start + bytes.len()].copy_from_slice(bytes);
+            self.len += n;
+            self.unsynced += n;
+            if self.unsynced >= self.sync_every {
+                map.flush_async()?;
+                self.unsynced = 0;
+            }
+        }
+        Ok(())
+    }
+
+    fn sync_async(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(map) = self.map.as_ref() {
+            map.flush_async()?;
+        }
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Sync the mapping, unmap it and truncate the file to `len`, the
+    /// blocking part of closing the sink
+    fn unmap(map: MmapMut, file: &File, len: u64) -> Result<(), StdoutChannelError> {
+        map.flush()?;
+        drop(map);
+        file.set_len(len)?;
+        Ok(())
+    }
+
+    async fn finish_blocking(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(map) = self.map.take() {
+            let file = self.file.try_clone()?;
+            let len = self.len;
+            self.unsynced = 0;
+            spawn_blocking(move || Self::unmap(map, &file, len)).await??;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(map) = self.map.take() {
+            self.unsynced = 0;
+            Self::unmap(map, &self.file, self.len)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for MmapFileSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let result = self.write_bytes(line.bytes());
+        Box::pin(async { result })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        let result = self.sync_async();
+        Box::pin(async { result })
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.finish_blocking())
+    }
+}
+
+impl Drop for MmapFileSink {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use crate::{StdoutChannel, StdoutChannelError};
+
+    use super::MmapFileSink;
+
+    #[tokio::test]
+    async fn test_mmap_file_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let stdout = MmapFileSink::with_preallocate(dir.join("out"), 4096)?.sync_every(100);
+        let stderr = MmapFileSink::create(dir.join("err"))?;
+        let chan = StdoutChannel::<String>::with_sinks(stdout, stderr);
+        let long = "x".repeat(5000);
+        chan.send("first");
+        chan.send(long.clone());
+        chan.send("last");
+        chan.close().await?;
+
+        let expected = format!("first\n{long}\nlast\n");
+        assert_eq!(fs::read_to_string(dir.join("out")).await?, expected);
+        assert_eq!(fs::metadata(dir.join("err")).await?.len(), 0);
+        Ok(())
+    }
+}
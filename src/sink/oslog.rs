@@ -0,0 +1,193 @@
+#[cfg(target_os = "macos")]
+use std::{ffi::CString, os::raw::c_void};
+#[cfg(not(target_os = "macos"))]
+use tokio::io::{stderr, stdout, AsyncWriteExt, Stderr, Stdout};
+
+#[cfg(target_os = "macos")]
+use crate::Level;
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture, Stream},
+    StdoutChannelError,
+};
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    static __dso_handle: c_void;
+    fn _os_log_impl(
+        dso: *const c_void,
+        log: libc::os_log_t,
+        log_type: libc::os_log_type_t,
+        format: *const libc::c_char,
+        buf: *const u8,
+        size: u32,
+    );
+    fn os_release(object: *mut c_void);
+}
+
+/// Format of every message, where the `os_log` macro would put it so the
+/// log tools can read it from the binary
+#[cfg(target_os = "macos")]
+#[link_section = "__TEXT,__oslogstring,cstring_literals"]
+static FORMAT: [u8; 11] = *b"%{public}s\0";
+
+#[cfg(target_os = "macos")]
+fn log_type(level: Level) -> libc::os_log_type_t {
+    match level {
+        Level::Error => libc::OS_LOG_TYPE_ERROR,
+        Level::Info => libc::OS_LOG_TYPE_INFO,
+        Level::Debug => libc::OS_LOG_TYPE_DEBUG,
+        _ => libc::OS_LOG_TYPE_DEFAULT,
+    }
+}
+
+/// Sends lines to the unified log on macOS, shown by Console.app and
+/// `log stream --predicate 'subsystem == "..."'`. The log type is error for
+/// stderr and info for stdout unless the line starts with a level like
+/// `warning: `, warnings use the default type. Lines are logged as public,
+/// info and debug ones are only kept in memory unless configured otherwise.
+///
+/// On other platforms lines are written to the process stdout and stderr,
+/// so the same code runs everywhere.
+pub struct OsLogSink {
+    #[cfg(target_os = "macos")]
+    log: libc::os_log_t,
+    #[cfg(not(target_os = "macos"))]
+    stdout: Stdout,
+    #[cfg(not(target_os = "macos"))]
+    stderr: Stderr,
+}
+
+// SAFETY: os_log objects can be used from any thread
+#[cfg(target_os = "macos")]
+unsafe impl Send for OsLogSink {}
+
+impl OsLogSink {
+    /// Log under `subsystem`, usually a reverse DNS name like
+    /// `com.example.tool`, and `category`
+    /// # Errors
+    ///
+    /// Will error if either contains a NUL byte
+    #[cfg(target_os = "macos")]
+    pub fn new(subsystem: &str, category: &str) -> Result<Self, StdoutChannelError> {
+        let subsystem = CString::new(subsystem).map_err(std::io::Error::from)?;
+        let category = CString::new(category).map_err(std::io::Error::from)?;
+        // SAFETY: both strings are NUL-terminated and outlive the call
+        let log = unsafe { libc::os_log_create(subsystem.as_ptr(), category.as_ptr()) };
+        Ok(Self { log })
+    }
+
+    /// Log under `subsystem`, usually a reverse DNS name like
+    /// `com.example.tool`, and `category`
+    /// # Errors
+    ///
+    /// Will error if either contains a NUL byte
+    #[cfg(not(target_os = "macos"))]
+    pub fn new(subsystem: &str, category: &str) -> Result<Self, StdoutChannelError> {
+        if subsystem.contains('\0') || category.contains('\0') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "os_log subsystem and category can't contain NUL bytes",
+            )
+            .into());
+        }
+        Ok(Self {
+            stdout: stdout(),
+            stderr: stderr(),
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn write_line(&mut self, bytes: &[u8], stream: Stream) -> Result<(), StdoutChannelError> {
+        let line = String::from_utf8_lossy(bytes.strip_suffix(b"\n").unwrap_or(bytes));
+        let log_type = log_type(Level::of_line(&line, stream));
+        // cut at a NUL byte: the message is read as a C string
+        let line = CString::new(line.split('\0').next().unwrap_or_default())
+            .map_err(std::io::Error::from)?;
+        // SAFETY: `log` is valid for the life of the sink
+        if !unsafe { libc::os_log_type_enabled(self.log, log_type) } {
+            return Ok(());
+        }
+        // the argument buffer `os_log(log, "%{public}s", line)` builds: a
+        // summary byte flagging non-scalar arguments and one argument, then
+        // a public string descriptor and the 8 bytes of its pointer
+        let mut buf = [0u8; 12];
+        buf[..4].copy_from_slice(&[0x02, 0x01, 0x22, 0x08]);
+        buf[4..].copy_from_slice(&(line.as_ptr() as u64).to_ne_bytes());
+        // SAFETY: `buf` describes the one argument of `FORMAT`, a pointer to
+        // `line` that is alive for the call
+        unsafe {
+            _os_log_impl(
+                std::ptr::addr_of!(__dso_handle),
+                self.log,
+                log_type,
+                FORMAT.as_ptr().cast(),
+                buf.as_ptr(),
+                12,
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    async fn write_line(&mut self, bytes: &[u8], stream: Stream) -> Result<(), StdoutChannelError> {
+        match stream {
+            Stream::Stdout => self.stdout.write_all(bytes).await?,
+            Stream::Stderr => self.stderr.write_all(bytes).await?,
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        self.stdout.flush().await?;
+        self.stderr.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for OsLogSink {
+    fn drop(&mut self) {
+        // SAFETY: `log` was returned by `os_log_create`
+        unsafe { os_release(self.log) };
+    }
+}
+
+impl<T> OutputSink<T> for OsLogSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let (bytes, stream) = (line.bytes(), line.stream());
+        Box::pin(self.write_line(bytes, stream))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+}
+
+#[cfg(all(test, target_os = "macos"))]
+mod tests {
+    use crate::{sink::Stream, Level, StdoutChannel, StdoutChannelError};
+
+    use super::{log_type, OsLogSink};
+
+    #[tokio::test]
+    async fn test_os_log_sink() -> Result<(), StdoutChannelError> {
+        assert_eq!(
+            log_type(Level::of_line("failed", Stream::Stderr)),
+            libc::OS_LOG_TYPE_ERROR
+        );
+        assert_eq!(
+            log_type(Level::of_line("warning: disk low", Stream::Stdout)),
+            libc::OS_LOG_TYPE_DEFAULT
+        );
+        assert!(OsLogSink::new("com.example\0", "test").is_err());
+
+        let sink = || OsLogSink::new("com.github.stdout-channel", "test");
+        let chan = StdoutChannel::<String>::with_sinks(sink()?, sink()?);
+        chan.send("info line");
+        chan.send_err("error: error line");
+        chan.close().await?;
+        Ok(())
+    }
+}
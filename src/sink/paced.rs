@@ -0,0 +1,91 @@
+use crate::{
+    rate_limiter::RateLimiter,
+    sink::{OutputLine, OutputSink, SinkFuture},
+};
+
+/// Wraps a sink and takes one permit per byte from the limiter before each
+/// write, e.g. `RateLimiter::new(960, 1000)` paces output like a 9600 baud
+/// serial link.
+pub struct PacedSink<S> {
+    inner: S,
+    rate_limiter: RateLimiter,
+}
+
+impl<S> PacedSink<S> {
+    #[must_use]
+    pub fn new(inner: S, rate_limiter: RateLimiter) -> Self {
+        Self {
+            inner,
+            rate_limiter,
+        }
+    }
+
+    /// Pace `inner` to `bytes_per_sec`
+    #[must_use]
+    pub fn bytes_per_sec(inner: S, bytes_per_sec: usize) -> Self {
+        Self::new(inner, RateLimiter::new(bytes_per_sec, 1000))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<T, S> OutputSink<T> for PacedSink<S>
+where
+    S: OutputSink<T>,
+    T: Send + 'static,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        Box::pin(async move {
+            self.rate_limiter.acquire_n(line.bytes().len()).await;
+            self.inner.write(line).await
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        self.inner.close()
+    }
+
+    fn is_process_stream(&self) -> bool {
+        self.inner.is_process_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use tokio::fs;
+
+    use crate::{
+        rate_limiter::RateLimiter, sink::file::FileSink, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::PacedSink;
+
+    #[tokio::test]
+    async fn test_paced_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let stdout = PacedSink::new(
+            FileSink::open(dir.join("out")).await?,
+            RateLimiter::new(20, 100),
+        );
+        let chan =
+            StdoutChannel::<&str>::with_sinks(stdout, FileSink::open(dir.join("err")).await?);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            chan.send("123456789");
+        }
+        chan.close().await?;
+        // 30 bytes at 20 bytes per 100ms need a second window
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert_eq!(fs::read_to_string(dir.join("out")).await?.len(), 30);
+        Ok(())
+    }
+}
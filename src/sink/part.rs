@@ -0,0 +1,232 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    sink::{file::FileSinkOptions, retention::RetentionPolicy, OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+/// Name of the manifest a `PartFileSink` writes on close
+pub const PART_MANIFEST: &str = "index.txt";
+
+/// When a `PartFileSink` moves on to the next part
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartLimit {
+    /// At most this many lines per part
+    Lines(usize),
+    /// At most this many bytes per part, a single longer line gets a part of
+    /// its own
+    Bytes(u64),
+}
+
+struct Part {
+    name: String,
+    lines: usize,
+    bytes: u64,
+}
+
+/// Splits output into `part-0001.txt`, `part-0002.txt`, ... inside a
+/// directory, and on close writes an `index.txt` manifest listing each part
+/// with its line and byte counts (tab separated).
+pub struct PartFileSink {
+    dir: PathBuf,
+    limit: PartLimit,
+    options: FileSinkOptions,
+    retention: Option<RetentionPolicy>,
+    writer: Option<BufWriter<File>>,
+    parts: Vec<Part>,
+    next_part: usize,
+}
+
+impl PartFileSink {
+    /// # Errors
+    ///
+    /// Will error if the directory can't be created
+    pub async fn new(dir: impl AsRef<Path>, limit: PartLimit) -> Result<Self, StdoutChannelError> {
+        Self::with_options(dir, limit, FileSinkOptions::new()).await
+    }
+
+    /// Create the sink, opening each part with `options` (parts are always
+    /// truncated)
+    /// # Errors
+    ///
+    /// Will error if the directory can't be created
+    pub async fn with_options(
+        dir: impl AsRef<Path>,
+        limit: PartLimit,
+        options: FileSinkOptions,
+    ) -> Result<Self, StdoutChannelError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            limit,
+            options,
+            retention: None,
+            writer: None,
+            parts: Vec::new(),
+            next_part: 1,
+        })
+    }
+
+    /// Delete finished parts outside `retention` whenever a new part is
+    /// started, deleted parts are left out of the manifest
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Paths of the parts written so far and not removed by retention
+    #[must_use]
+    pub fn part_paths(&self) -> Vec<PathBuf> {
+        self.parts.iter().map(|p| self.dir.join(&p.name)).collect()
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        let len = bytes.len() as u64;
+        let full = match (self.parts.last(), self.limit) {
+            (None, _) => true,
+            (Some(part), PartLimit::Lines(lines)) => part.lines >= lines,
+            (Some(part), PartLimit::Bytes(max)) => part.bytes > 0 && part.bytes + len > max,
+        };
+        if full {
+            self.start_part().await?;
+        }
+        if let (Some(writer), Some(part)) = (self.writer.as_mut(), self.parts.last_mut()) {
+            writer.write_all(bytes).await?;
+            part.lines += 1;
+            part.bytes += len;
+        }
+        Ok(())
+    }
+
+    async fn start_part(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush().await?;
+        }
+        if let Some(retention) = self.retention {
+            let removed = retention.apply(self.part_paths()).await?;
+            let dir = &self.dir;
+            self.parts.retain(|p| !removed.contains(&dir.join(&p.name)));
+        }
+        let name = format!("part-{:04}.txt", self.next_part);
+        self.next_part += 1;
+        let file = self.options.open_file(&self.dir.join(&name), false).await?;
+        self.writer = Some(BufWriter::new(file));
+        self.parts.push(Part {
+            name,
+            lines: 0,
+            bytes: 0,
+        });
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn write_manifest(&mut self) -> Result<(), StdoutChannelError> {
+        self.flush_writer().await?;
+        let mut manifest = String::new();
+        for part in &self.parts {
+            let _ = writeln!(manifest, "{}\t{}\t{}", part.name, part.lines, part.bytes);
+        }
+        fs::write(self.dir.join(PART_MANIFEST), manifest).await?;
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for PartFileSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.write_manifest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use crate::{sink::retention::RetentionPolicy, StdoutChannel, StdoutChannelError};
+
+    use super::{PartFileSink, PartLimit, PART_MANIFEST};
+
+    #[tokio::test]
+    async fn test_part_file_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let base = tmp.path();
+        let lines_dir = base.join("lines");
+        let bytes_dir = base.join("bytes");
+
+        let by_lines = PartFileSink::new(&lines_dir, PartLimit::Lines(2)).await?;
+        let by_bytes = PartFileSink::new(&bytes_dir, PartLimit::Bytes(8)).await?;
+        let chan = StdoutChannel::<String>::with_sinks(by_lines, by_bytes);
+        for line in ["a", "b", "c", "d", "e"] {
+            chan.send(line);
+        }
+        for line in ["1234", "12", "12345678901", "x"] {
+            chan.send_err(line);
+        }
+        chan.close().await?;
+
+        assert_eq!(
+            fs::read_to_string(lines_dir.join(PART_MANIFEST)).await?,
+            "part-0001.txt\t2\t4\npart-0002.txt\t2\t4\npart-0003.txt\t1\t2\n"
+        );
+        assert_eq!(
+            fs::read_to_string(lines_dir.join("part-0003.txt")).await?,
+            "e\n"
+        );
+        assert_eq!(
+            fs::read_to_string(bytes_dir.join(PART_MANIFEST)).await?,
+            "part-0001.txt\t2\t8\npart-0002.txt\t1\t12\npart-0003.txt\t1\t2\n"
+        );
+        assert_eq!(
+            fs::read_to_string(bytes_dir.join("part-0001.txt")).await?,
+            "1234\n12\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_part_retention() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let sink = PartFileSink::new(&dir, PartLimit::Lines(1))
+            .await?
+            .with_retention(RetentionPolicy::new().max_files(1));
+        let chan = StdoutChannel::<String>::with_sinks(
+            sink,
+            PartFileSink::new(dir.join("err"), PartLimit::Lines(1)).await?,
+        );
+        for line in ["a", "b", "c", "d"] {
+            chan.send(line);
+        }
+        chan.close().await?;
+
+        assert_eq!(
+            fs::read_to_string(dir.join(PART_MANIFEST)).await?,
+            "part-0003.txt\t1\t2\npart-0004.txt\t1\t2\n"
+        );
+        assert!(!dir.join("part-0002.txt").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    convert::TryFrom,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    clock::{Clock, Timestamp},
+    sink::{file::FileSinkOptions, retention::RetentionPolicy, OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+/// Period covered by each file of a `TimePartitionedSink`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Partition {
+    /// `YYYY/MM/DD/HH/<file>`
+    Hourly,
+    /// `YYYY/MM/DD/<file>`
+    Daily,
+}
+
+impl Partition {
+    /// Number of directory levels below the sink's root
+    fn depth(self) -> usize {
+        match self {
+            Self::Hourly => 4,
+            Self::Daily => 3,
+        }
+    }
+
+    /// Directory, relative to the sink's root, for the period containing `time` (UTC)
+    #[must_use]
+    pub fn dir_for(self, time: SystemTime) -> PathBuf {
+        let [year, month, day, hour, ..] = utc_fields(time);
+        match self {
+            Self::Hourly => format!("{year:04}/{month:02}/{day:02}/{hour:02}").into(),
+            Self::Daily => format!("{year:04}/{month:02}/{day:02}").into(),
+        }
+    }
+}
+
+/// Year, month, day, hour, minute and second of `time` in UTC
+pub(crate) fn utc_fields(time: SystemTime) -> [i64; 6] {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => i64::try_from(d.as_secs()).unwrap_or(i64::MAX),
+        Err(e) => -i64::try_from(e.duration().as_secs()).unwrap_or(i64::MAX),
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs_of_day = secs.rem_euclid(86400);
+    [
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+    ]
+}
+
+// Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Files named `file_name` of every period under `root`, oldest first
+async fn period_files(
+    root: &Path,
+    partition: Partition,
+    file_name: &str,
+) -> Result<Vec<PathBuf>, StdoutChannelError> {
+    let mut dirs = vec![root.to_path_buf()];
+    for _ in 0..partition.depth() {
+        let mut children = Vec::new();
+        for dir in dirs {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let numbered = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()));
+                if numbered && entry.file_type().await?.is_dir() {
+                    children.push(entry.path());
+                }
+            }
+        }
+        dirs = children;
+    }
+    let mut files = Vec::new();
+    for dir in dirs {
+        let path = dir.join(file_name);
+        if fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            files.push(path);
+        }
+    }
+    // zero-padded periods sort chronologically
+    files.sort();
+    Ok(files)
+}
+
+/// Remove the directories of `file` left empty, up to `root`
+async fn remove_empty_dirs(root: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(period_dir) = dir.filter(|d| *d != root) {
+        // fails once a directory still holds other files
+        if fs::remove_dir(period_dir).await.is_err() {
+            break;
+        }
+        dir = period_dir.parent();
+    }
+}
+
+/// Writes to `<dir>/YYYY/MM/DD[/HH]/<file_name>`, creating the directories as
+/// needed and switching to a new file whenever a period boundary is crossed.
+/// Period files are always appended to, so restarting within a period or
+/// the clock stepping back into a past one doesn't truncate its log.
+pub struct TimePartitionedSink {
+    dir: PathBuf,
+    file_name: String,
+    partition: Partition,
+    options: FileSinkOptions,
+    clock: Clock,
+    retention: Option<RetentionPolicy>,
+    current: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl TimePartitionedSink {
+    #[must_use]
+    pub fn new(dir: impl AsRef<Path>, file_name: impl Into<String>, partition: Partition) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            file_name: file_name.into(),
+            partition,
+            options: FileSinkOptions::new(),
+            clock: Clock::System,
+            retention: None,
+            current: None,
+        }
+    }
+
+    /// Open period files with `options`, their append setting is ignored
+    #[must_use]
+    pub fn with_options(mut self, options: FileSinkOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Pick the period from `clock`, the system clock is used when it
+    /// doesn't tell wall-clock time
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Delete files of past periods outside `retention`, including ones
+    /// written by earlier runs, when the sink opens its first file and
+    /// whenever it switches to a new period. Period directories left empty
+    /// are removed.
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Path of the file currently written to, if any
+    #[must_use]
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    fn now(&self) -> SystemTime {
+        match self.clock.now() {
+            Timestamp::Utc(time) | Timestamp::Offset(time, _) => time,
+            Timestamp::Elapsed(_) | Timestamp::Ticks(_) => SystemTime::now(),
+        }
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        let dir = self.dir.join(self.partition.dir_for(self.now()));
+        let path = dir.join(&self.file_name);
+        if self.current_path() != Some(path.as_path()) {
+            if let Some((_, mut writer)) = self.current.take() {
+                writer.flush().await?;
+            }
+            fs::create_dir_all(&dir).await?;
+            let file = self.options.open_file(&path, true).await?;
+            if let Some(retention) = self.retention {
+                let mut files = period_files(&self.dir, self.partition, &self.file_name).await?;
+                files.retain(|p| *p != path);
+                for removed in retention.apply(files).await? {
+                    remove_empty_dirs(&self.dir, &removed).await;
+                }
+            }
+            self.current = Some((path, BufWriter::new(file)));
+        }
+        if let Some((_, writer)) = self.current.as_mut() {
+            writer.write_all(bytes).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some((_, writer)) = self.current.as_mut() {
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for TimePartitionedSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+    use tokio::fs;
+
+    use crate::{
+        clock::{Clock, ManualClock},
+        sink::{OutputLine, OutputSink, Stream},
+        RetentionPolicy, StdoutChannelError,
+    };
+
+    use super::{Partition, TimePartitionedSink};
+
+    #[test]
+    fn test_partition_dir() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_249_400);
+        assert_eq!(Partition::Hourly.dir_for(time), Path::new("2024/02/29/23"));
+        assert_eq!(Partition::Daily.dir_for(time), Path::new("2024/02/29"));
+        assert_eq!(
+            Partition::Daily.dir_for(UNIX_EPOCH),
+            Path::new("1970/01/01")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_time_partitioned_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        // five minutes before the end of the hour
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_709_250_900));
+        let mut sink = TimePartitionedSink::new(dir, "app.log", Partition::Hourly)
+            .with_clock(Clock::Manual(clock.clone()));
+        for (line, advance) in [("one\n", 299), ("two\n", 1), ("three\n", 0)] {
+            let line = OutputLine::new((), line.as_bytes(), Stream::Stdout);
+            OutputSink::<()>::write(&mut sink, line).await?;
+            clock.advance(Duration::from_secs(advance));
+        }
+        assert_eq!(
+            sink.current_path(),
+            Some(dir.join("2024/03/01/00/app.log").as_path())
+        );
+        OutputSink::<()>::close(&mut sink).await?;
+
+        // a restart within the period appends to its file
+        clock.advance(Duration::from_secs(1800));
+        let mut sink = TimePartitionedSink::new(dir, "app.log", Partition::Hourly)
+            .with_clock(Clock::Manual(clock));
+        let line = OutputLine::new((), b"four\n", Stream::Stdout);
+        OutputSink::<()>::write(&mut sink, line).await?;
+        OutputSink::<()>::close(&mut sink).await?;
+
+        assert_eq!(
+            fs::read_to_string(dir.join("2024/02/29/23/app.log")).await?,
+            "one\ntwo\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("2024/03/01/00/app.log")).await?,
+            "three\nfour\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partition_retention() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        // left by an earlier run
+        for period in ["2024/02/28/10", "2024/02/28/11"] {
+            fs::create_dir_all(dir.join(period)).await?;
+            fs::write(dir.join(period).join("app.log"), "old\n").await?;
+        }
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_709_249_400));
+        let mut sink = TimePartitionedSink::new(dir, "app.log", Partition::Hourly)
+            .with_retention(RetentionPolicy::new().max_files(1))
+            .with_clock(Clock::Manual(clock.clone()));
+        let line = || OutputLine::new((), b"new\n", Stream::Stdout);
+        OutputSink::<()>::write(&mut sink, line()).await?;
+        assert!(!dir.join("2024/02/28/10").exists());
+        assert!(dir.join("2024/02/28/11/app.log").exists());
+
+        clock.advance(Duration::from_secs(3600));
+        OutputSink::<()>::write(&mut sink, line()).await?;
+        OutputSink::<()>::close(&mut sink).await?;
+        assert!(!dir.join("2024/02/28").exists());
+        assert!(dir.join("2024/02/29/23/app.log").exists());
+        assert!(dir.join("2024/03/01/00/app.log").exists());
+        Ok(())
+    }
+}
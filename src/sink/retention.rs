@@ -0,0 +1,103 @@
+use std::{io::ErrorKind, path::PathBuf, time::Duration};
+use tokio::fs;
+
+use crate::StdoutChannelError;
+
+/// Which rotated files a sink deletes after switching to a new file.
+///
+/// Both limits may be combined, files older than `max_age` are removed first
+/// and then the oldest ones beyond `max_files`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    max_files: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max_files` rotated files
+    #[must_use]
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Delete rotated files last modified longer than `max_age` ago
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Delete the files of `files` (oldest first) that fall outside the
+    /// policy, returning the ones removed
+    /// # Errors
+    ///
+    /// Will error if a file's metadata can't be read or it can't be removed
+    pub async fn apply(&self, files: Vec<PathBuf>) -> Result<Vec<PathBuf>, StdoutChannelError> {
+        let mut kept = Vec::with_capacity(files.len());
+        let mut removed = Vec::new();
+        for path in files {
+            let expired = match self.max_age {
+                Some(max_age) => match fs::metadata(&path).await {
+                    Ok(metadata) => metadata.modified()?.elapsed().unwrap_or_default() > max_age,
+                    Err(e) if e.kind() == ErrorKind::NotFound => true,
+                    Err(e) => return Err(e.into()),
+                },
+                None => false,
+            };
+            if expired {
+                removed.push(path);
+            } else {
+                kept.push(path);
+            }
+        }
+        if let Some(max_files) = self.max_files {
+            let excess = kept.len().saturating_sub(max_files);
+            removed.extend(kept.drain(..excess));
+        }
+        for path in &removed {
+            match fs::remove_file(path).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::fs;
+
+    use crate::StdoutChannelError;
+
+    use super::RetentionPolicy;
+
+    #[tokio::test]
+    async fn test_retention_max_age() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let files = vec![dir.join("a.log"), dir.join("b.log")];
+        for path in &files {
+            fs::write(path, "x\n").await?;
+        }
+
+        let keep_all = RetentionPolicy::new().max_age(Duration::from_secs(3600));
+        assert!(keep_all.apply(files.clone()).await?.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let removed = RetentionPolicy::new()
+            .max_age(Duration::from_millis(10))
+            .apply(files.clone())
+            .await?;
+        assert_eq!(removed, files);
+        assert!(!files[0].exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use crate::{
+    sink::{
+        file::{default_app_file, FileSinkOptions},
+        partitioned::{utc_fields, Partition},
+        retention::RetentionPolicy,
+        OutputLine, OutputSink, SinkFuture,
+    },
+    StdoutChannelError,
+};
+
+/// How a `RotatingFileSink` names the files it rotates out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RotatedNaming {
+    /// `app.log.1`, `app.log.2`, ..., the highest number is the newest
+    #[default]
+    Numbered,
+    /// `app.log.2026-10-14T13-05-00`, the UTC time of the rotation
+    Timestamp,
+}
+
+/// Size past which `RotatingFileSink::default_for_app` rotates
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated files `RotatingFileSink::default_for_app` keeps
+pub const DEFAULT_MAX_FILES: usize = 7;
+
+type Now = Box<dyn Fn() -> SystemTime + Send>;
+
+/// Writes to a single file and renames it away once it would grow past
+/// `max_bytes` and/or when the hour or day changes, optionally deleting old
+/// rotated files according to a `RetentionPolicy`. The file is opened on the
+/// first write, appending to what a previous run left there unless the
+/// options say otherwise. Files a previous run rotated out with the same
+/// naming are picked up then too, so they count towards the retention and
+/// numbering continues after them.
+pub struct RotatingFileSink {
+    path: PathBuf,
+    options: FileSinkOptions,
+    max_bytes: Option<u64>,
+    every: Option<Partition>,
+    naming: RotatedNaming,
+    retention: Option<RetentionPolicy>,
+    now: Now,
+    writer: Option<BufWriter<File>>,
+    written: u64,
+    period: Option<PathBuf>,
+    rotated: Vec<PathBuf>,
+    next_number: usize,
+    scanned: bool,
+}
+
+impl RotatingFileSink {
+    #[must_use]
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            options: FileSinkOptions::new().append(true),
+            max_bytes: None,
+            every: None,
+            naming: RotatedNaming::Numbered,
+            retention: None,
+            now: Box::new(SystemTime::now),
+            writer: None,
+            written: 0,
+            period: None,
+            rotated: Vec::new(),
+            next_number: 1,
+            scanned: false,
+        }
+    }
+
+    /// Same file as `FileSink::default_for_app`, rotated daily and once it
+    /// would grow past `DEFAULT_MAX_BYTES`, keeping the last
+    /// `DEFAULT_MAX_FILES` rotated files
+    /// # Errors
+    ///
+    /// Will error if no home directory can be determined or the directory
+    /// can't be created
+    pub async fn default_for_app(app: &str) -> Result<Self, StdoutChannelError> {
+        let (path, options) = default_app_file(app).await?;
+        Ok(Self::new(path)
+            .with_options(options)
+            .max_bytes(DEFAULT_MAX_BYTES)
+            .every(Partition::Daily)
+            .with_retention(RetentionPolicy::new().max_files(DEFAULT_MAX_FILES)))
+    }
+
+    #[must_use]
+    pub fn with_options(mut self, options: FileSinkOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Rotate before a write would take the file past `max_bytes`, a single
+    /// longer line still gets written to a fresh file
+    #[must_use]
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Rotate on the first write of every new hour or day (UTC)
+    #[must_use]
+    pub fn every(mut self, every: Partition) -> Self {
+        self.every = Some(every);
+        self
+    }
+
+    #[must_use]
+    pub fn naming(mut self, naming: RotatedNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Delete rotated files outside `retention` after every rotation
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Use `now` instead of the system clock for time based rotation and
+    /// timestamps
+    #[must_use]
+    pub fn with_now(mut self, now: impl Fn() -> SystemTime + Send + 'static) -> Self {
+        self.now = Box::new(now);
+        self
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rotated files still on disk, oldest first, including the ones of
+    /// earlier runs once the first line has been written
+    #[must_use]
+    pub fn rotated(&self) -> &[PathBuf] {
+        &self.rotated
+    }
+
+    fn with_suffix(&self, suffix: &str) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(".");
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    /// Find the files earlier runs rotated out of `path`
+    async fn scan_rotated(&mut self) -> Result<(), StdoutChannelError> {
+        let Some(name) = self.path.file_name().and_then(OsStr::to_str) else {
+            return Ok(());
+        };
+        let prefix = format!("{name}.");
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut entries = match fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut found = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let key = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|suffix| rotated_key(self.naming, suffix));
+            if let Some((stamp, number)) = key {
+                if entry.file_type().await?.is_file() {
+                    found.push(((stamp.to_string(), number), entry.path()));
+                }
+            }
+        }
+        found.sort();
+        if let Some(((_, number), _)) = found.last() {
+            if self.naming == RotatedNaming::Numbered {
+                self.next_number = number + 1;
+            }
+        }
+        self.rotated = found.into_iter().map(|(_, path)| path).collect();
+        Ok(())
+    }
+
+    async fn rotated_path(&mut self, now: SystemTime) -> Result<PathBuf, StdoutChannelError> {
+        let stamp = match self.naming {
+            RotatedNaming::Numbered => None,
+            RotatedNaming::Timestamp => {
+                let [y, mo, d, h, mi, s] = utc_fields(now);
+                Some(format!("{y:04}-{mo:02}-{d:02}T{h:02}-{mi:02}-{s:02}"))
+            }
+        };
+        for attempt in 0.. {
+            let path = match &stamp {
+                None => {
+                    self.next_number += 1;
+                    self.with_suffix(&(self.next_number - 1).to_string())
+                }
+                Some(stamp) if attempt == 0 => self.with_suffix(stamp),
+                Some(stamp) => self.with_suffix(&format!("{stamp}.{attempt}")),
+            };
+            if !fs::try_exists(&path).await? {
+                return Ok(path);
+            }
+        }
+        unreachable!()
+    }
+
+    async fn rotate(&mut self, now: SystemTime) -> Result<(), StdoutChannelError> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush().await?;
+        }
+        let rotated = self.rotated_path(now).await?;
+        fs::rename(&self.path, &rotated).await?;
+        self.rotated.push(rotated);
+        if let Some(retention) = self.retention {
+            let removed = retention.apply(self.rotated.clone()).await?;
+            self.rotated.retain(|p| !removed.contains(p));
+        }
+        Ok(())
+    }
+
+    async fn open(&mut self, append: bool) -> Result<(), StdoutChannelError> {
+        let file = self.options.open_file(&self.path, append).await?;
+        self.written = if append {
+            file.metadata().await?.len()
+        } else {
+            0
+        };
+        self.writer = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        let now = (self.now)();
+        let period = self.every.map(|every| every.dir_for(now));
+        if !self.scanned {
+            self.scan_rotated().await?;
+            self.scanned = true;
+        }
+        if self.writer.is_none() {
+            self.period.clone_from(&period);
+            self.open(self.options.is_append()).await?;
+        }
+        let len = bytes.len() as u64;
+        let too_big = self
+            .max_bytes
+            .is_some_and(|max| self.written > 0 && self.written + len > max);
+        if too_big || period != self.period {
+            self.rotate(now).await?;
+            self.period = period;
+            self.open(false).await?;
+        }
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write_all(bytes).await?;
+            self.written += len;
+        }
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// The order of a file rotated out with `suffix`, `None` if `naming`
+/// doesn't produce such a suffix
+fn rotated_key(naming: RotatedNaming, suffix: &str) -> Option<(&str, usize)> {
+    let number = |s: &str| {
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    };
+    match naming {
+        RotatedNaming::Numbered => Some(("", number(suffix)?)),
+        RotatedNaming::Timestamp => {
+            let (stamp, attempt) = match suffix.split_at_checked(19)? {
+                (stamp, "") => (stamp, 0),
+                (stamp, attempt) => (stamp, number(attempt.strip_prefix('.')?)?),
+            };
+            let valid = stamp.bytes().enumerate().all(|(i, b)| match i {
+                4 | 7 | 13 | 16 => b == b'-',
+                10 => b == b'T',
+                _ => b.is_ascii_digit(),
+            });
+            valid.then_some((stamp, attempt))
+        }
+    }
+}
+
+impl<T> OutputSink<T> for RotatingFileSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, UNIX_EPOCH},
+    };
+    use tokio::fs;
+
+    use crate::{
+        sink::{partitioned::Partition, retention::RetentionPolicy},
+        MockStdout, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::{RotatedNaming, RotatingFileSink};
+
+    #[tokio::test]
+    async fn test_rotation() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let path = dir.join("app.log");
+
+        let sink = RotatingFileSink::new(&path)
+            .max_bytes(12)
+            .with_retention(RetentionPolicy::new().max_files(2));
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        for line in [
+            "one", "two", "three", "four", "five", "six", "seven", "eight",
+        ] {
+            chan.send(line);
+        }
+        chan.close().await?;
+        assert_eq!(fs::read_to_string(&path).await?, "seven\neight\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.3")).await?,
+            "five\nsix\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.2")).await?,
+            "three\nfour\n"
+        );
+        assert!(!fs::try_exists(dir.join("app.log.1")).await?);
+
+        // 2026-10-14T23:59:00Z, advanced by one hour for every line
+        let now = Arc::new(AtomicU64::new(1_792_022_340));
+        let clock = Arc::clone(&now);
+        let daily = dir.join("daily.log");
+        let sink = RotatingFileSink::new(&daily)
+            .every(Partition::Daily)
+            .naming(RotatedNaming::Timestamp)
+            .with_now(move || {
+                UNIX_EPOCH + Duration::from_secs(clock.fetch_add(3600, Ordering::SeqCst))
+            });
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        chan.send("monday");
+        chan.send("tuesday");
+        chan.close().await?;
+        assert_eq!(now.load(Ordering::SeqCst), 1_792_022_340 + 2 * 3600);
+        assert_eq!(fs::read_to_string(&daily).await?, "tuesday\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("daily.log.2026-10-15T00-59-00")).await?,
+            "monday\n"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_after_restart() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let path = dir.join("app.log");
+        fs::write(dir.join("app.log.1"), "oldest\n").await?;
+        fs::write(dir.join("app.log.2"), "older\n").await?;
+        fs::write(dir.join("app.log.bak"), "not rotated\n").await?;
+        fs::write(&path, "current\n").await?;
+
+        let sink = RotatingFileSink::new(&path)
+            .max_bytes(8)
+            .with_retention(RetentionPolicy::new().max_files(2));
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        chan.send("new");
+        chan.close().await?;
+        assert_eq!(fs::read_to_string(&path).await?, "new\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.3")).await?,
+            "current\n"
+        );
+        assert!(fs::try_exists(dir.join("app.log.2")).await?);
+        assert!(!fs::try_exists(dir.join("app.log.1")).await?);
+        assert!(fs::try_exists(dir.join("app.log.bak")).await?);
+        Ok(())
+    }
+}
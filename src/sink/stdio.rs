@@ -0,0 +1,89 @@
+use tokio::io::{stderr, stdout, AsyncWriteExt, Stderr, Stdout};
+
+use crate::{
+    sink::{OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+/// The process stdout as a sink, for combining with other sinks in
+/// `StdoutChannel::with_sinks`
+pub struct StdoutSink(Stdout);
+
+/// The process stderr as a sink
+pub struct StderrSink(Stderr);
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StdoutSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(stdout())
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        self.0.write_all(bytes).await?;
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        self.0.flush().await?;
+        Ok(())
+    }
+}
+
+impl Default for StderrSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StderrSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(stderr())
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        self.0.write_all(bytes).await?;
+        Ok(())
+    }
+
+    async fn flush_writer(&mut self) -> Result<(), StdoutChannelError> {
+        self.0.flush().await?;
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for StdoutSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+
+    fn is_process_stream(&self) -> bool {
+        true
+    }
+}
+
+impl<T> OutputSink<T> for StderrSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let bytes = line.bytes();
+        Box::pin(self.write_bytes(bytes))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writer())
+    }
+
+    fn is_process_stream(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,265 @@
+#[cfg(unix)]
+use std::path::Path;
+use std::{
+    fmt::Write,
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+#[cfg(unix)]
+use tokio::net::UnixDatagram;
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket};
+
+use crate::{
+    clock::{Clock, Timestamp, TimestampFormat},
+    sink::{OutputLine, OutputSink, SinkFuture, Stream},
+    Level, StdoutChannelError,
+};
+
+const NIL: &str = "-";
+
+/// Syslog facility codes used most by applications
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Facility {
+    #[default]
+    User = 1,
+    Daemon = 3,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Turns lines into RFC 5424 messages. The severity is that of
+/// `Level::syslog_severity`: error for stderr and info for stdout unless
+/// the line starts with a level like `warning: `.
+///
+/// Tags added with `with_structured_data` are written as STRUCTURED-DATA
+/// elements so parsers get them as fields, not as part of the message.
+pub struct SyslogFormatter {
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    proc_id: String,
+    msg_id: String,
+    structured_data: String,
+    clock: Clock,
+}
+
+impl SyslogFormatter {
+    #[must_use]
+    pub fn new(app_name: &str) -> Self {
+        Self {
+            facility: Facility::User,
+            hostname: NIL.into(),
+            app_name: header_field(app_name, 48),
+            proc_id: std::process::id().to_string(),
+            msg_id: NIL.into(),
+            structured_data: String::new(),
+            clock: Clock::System,
+        }
+    }
+
+    #[must_use]
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// `-` unless set
+    #[must_use]
+    pub fn with_hostname(mut self, hostname: &str) -> Self {
+        self.hostname = header_field(hostname, 255);
+        self
+    }
+
+    /// `-` unless set
+    #[must_use]
+    pub fn with_msg_id(mut self, msg_id: &str) -> Self {
+        self.msg_id = header_field(msg_id, 32);
+        self
+    }
+
+    /// Add an SD-ELEMENT `[sd_id name="value" ...]` to every message.
+    /// Private SD-IDs need an enterprise number, as in `meta@32473`.
+    #[must_use]
+    pub fn with_structured_data(mut self, sd_id: &str, params: &[(&str, &str)]) -> Self {
+        write!(self.structured_data, "[{}", sd_name(sd_id)).ok();
+        for (name, value) in params {
+            write!(self.structured_data, " {}=\"", sd_name(name)).ok();
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | ']') {
+                    self.structured_data.push('\\');
+                }
+                self.structured_data.push(c);
+            }
+            self.structured_data.push('"');
+        }
+        self.structured_data.push(']');
+        self
+    }
+
+    /// Read timestamps from `clock`, those that aren't wall-clock times are
+    /// written as `-`
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The message of one line, without its trailing newline
+    #[must_use]
+    pub fn format(&self, line: &[u8], stream: Stream) -> Vec<u8> {
+        let line = String::from_utf8_lossy(line.strip_suffix(b"\n").unwrap_or(line));
+        let pri = self.facility as u8 * 8 + Level::of_line(&line, stream).syslog_severity();
+        let timestamp = match self.clock.now() {
+            timestamp @ (Timestamp::Utc(_) | Timestamp::Offset(..)) => {
+                TimestampFormat::Pattern("%Y-%m-%dT%H:%M:%S.%f%z".into()).format(timestamp)
+            }
+            _ => NIL.into(),
+        };
+        let structured_data = if self.structured_data.is_empty() {
+            NIL
+        } else {
+            &self.structured_data
+        };
+        format!(
+            "<{pri}>1 {timestamp} {} {} {} {} {structured_data} {line}",
+            self.hostname, self.app_name, self.proc_id, self.msg_id
+        )
+        .into_bytes()
+    }
+}
+
+/// A header field of printable ASCII cut to `max` characters, `-` if empty
+fn header_field(value: &str, max: usize) -> String {
+    let value: String = value
+        .chars()
+        .filter(char::is_ascii_graphic)
+        .take(max)
+        .collect();
+    if value.is_empty() {
+        NIL.into()
+    } else {
+        value
+    }
+}
+
+/// An SD-ID or PARAM-NAME, printable ASCII but `=`, `]` and `"`
+fn sd_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"'))
+        .take(32)
+        .collect()
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+/// Sends each line as an RFC 5424 message to a syslog daemon, over UDP or
+/// a local datagram socket like `/dev/log`
+pub struct SyslogSink {
+    transport: Transport,
+    formatter: SyslogFormatter,
+}
+
+impl SyslogSink {
+    /// # Errors
+    ///
+    /// Will error if `addr` can't be resolved or a socket can't be bound
+    pub async fn udp(
+        addr: impl ToSocketAddrs,
+        formatter: SyslogFormatter,
+    ) -> Result<Self, StdoutChannelError> {
+        let addr = lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(addr).await?;
+        Ok(Self {
+            transport: Transport::Udp(socket),
+            formatter,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Will error if nothing listens on `path`
+    #[cfg(unix)]
+    pub fn unix(
+        path: impl AsRef<Path>,
+        formatter: SyslogFormatter,
+    ) -> Result<Self, StdoutChannelError> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self {
+            transport: Transport::Unix(socket),
+            formatter,
+        })
+    }
+
+    async fn send(&self, bytes: &[u8], stream: Stream) -> Result<(), StdoutChannelError> {
+        let message = self.formatter.format(bytes, stream);
+        match &self.transport {
+            Transport::Udp(socket) => socket.send(&message).await?,
+            #[cfg(unix)]
+            Transport::Unix(socket) => socket.send(&message).await?,
+        };
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for SyslogSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let (bytes, stream) = (line.bytes(), line.stream());
+        Box::pin(self.send(bytes, stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use tokio::net::UdpSocket;
+
+    use crate::{Clock, ManualClock, MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::{Facility, SyslogFormatter, SyslogSink};
+
+    #[tokio::test]
+    async fn test_syslog_sink() -> Result<(), StdoutChannelError> {
+        let server = UdpSocket::bind("127.0.0.1:0").await?;
+        let formatter = SyslogFormatter::new("my app")
+            .with_facility(Facility::Local0)
+            .with_hostname("web-1")
+            .with_structured_data("meta@32473", &[("region", "eu \"west\""), ("shard", "3")])
+            .with_clock(Clock::Manual(ManualClock::new(
+                UNIX_EPOCH + Duration::from_millis(1_704_164_645_250),
+            )));
+        let sink = SyslogSink::udp(server.local_addr()?, formatter).await?;
+        let chan = StdoutChannel::<String>::with_sinks(sink, MockStdout::new());
+        chan.send("warning: disk low".to_string());
+        chan.close().await?;
+
+        let mut buf = [0; 512];
+        let n = server.recv(&mut buf).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..n]),
+            format!(
+                r#"<132>1 2024-01-02T03:04:05.250000Z web-1 myapp {} - [meta@32473 region="eu \"west\"" shard="3"] warning: disk low"#,
+                std::process::id()
+            )
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::sink::{OutputLine, OutputSink, SinkFuture};
+
+/// Writes every line to a primary sink and a mirror, like `tee`: show the
+/// output on the terminal and keep a transcript in a file or `MockStdout`.
+///
+/// Both sinks are written even if one fails, the first error is returned.
+/// Nest tees to mirror to more sinks.
+pub struct TeeSink<P, M> {
+    primary: P,
+    mirror: M,
+}
+
+impl<P, M> TeeSink<P, M> {
+    #[must_use]
+    pub fn new(primary: P, mirror: M) -> Self {
+        Self { primary, mirror }
+    }
+
+    pub fn into_inner(self) -> (P, M) {
+        (self.primary, self.mirror)
+    }
+}
+
+impl<T, P, M> OutputSink<T> for TeeSink<P, M>
+where
+    P: OutputSink<T>,
+    M: OutputSink<T>,
+    T: Clone + Send + 'static,
+{
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let copy = OutputLine::new(line.item().clone(), line.bytes(), line.stream());
+        Box::pin(async move {
+            let primary = self.primary.write(line).await;
+            let mirror = self.mirror.write(copy).await;
+            primary.and(mirror)
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            let primary = self.primary.flush().await;
+            let mirror = self.mirror.flush().await;
+            primary.and(mirror)
+        })
+    }
+
+    fn close(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            let primary = self.primary.close().await;
+            let mirror = self.mirror.close().await;
+            primary.and(mirror)
+        })
+    }
+
+    fn is_process_stream(&self) -> bool {
+        self.primary.is_process_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        sink::fault::{FaultPlan, FaultSink},
+        Fault, MockStdout, StdoutChannel, StdoutChannelError,
+    };
+
+    use super::TeeSink;
+
+    #[tokio::test]
+    async fn test_tee_sink() -> Result<(), StdoutChannelError> {
+        let terminal = MockStdout::<String>::new();
+        let transcript = MockStdout::<String>::new();
+        let faults = FaultPlan::new();
+        faults.push(Fault::pass());
+        faults.push(Fault::fail());
+        let tee = TeeSink::new(FaultSink::new(terminal.clone(), faults), transcript.clone());
+        let chan = StdoutChannel::with_sinks(tee, MockStdout::new());
+        chan.send("shown twice");
+        chan.send("lost on the terminal");
+        assert!(chan.close().await.is_err());
+
+        assert_eq!(terminal.snapshot(), ["shown twice"]);
+        assert_eq!(
+            transcript.snapshot(),
+            ["shown twice", "lost on the terminal"]
+        );
+        Ok(())
+    }
+}
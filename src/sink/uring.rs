@@ -0,0 +1,227 @@
+use io_uring::{opcode, types, IoUring};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Write},
+    os::unix::io::{AsRawFd, OwnedFd},
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    sink::{file::FileSinkOptions, OutputLine, OutputSink, SinkFuture},
+    StdoutChannelError,
+};
+
+const BATCH_SIZE: usize = 64 * 1024;
+
+enum Command {
+    Write(Vec<u8>),
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+enum Backend {
+    Uring { ring: Box<IoUring>, seekable: bool },
+    Std,
+}
+
+struct Writer {
+    file: File,
+    backend: Backend,
+}
+
+impl Writer {
+    fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        let (ring, seekable) = match &mut self.backend {
+            Backend::Uring { ring, seekable } => (ring, *seekable),
+            Backend::Std => return self.file.write_all(buf),
+        };
+        // -1 uses and advances the file position, streams require 0
+        let offset = if seekable { u64::MAX } else { 0 };
+        while !buf.is_empty() {
+            let len = u32::try_from(buf.len()).unwrap_or(u32::MAX);
+            let entry = opcode::Write::new(types::Fd(self.file.as_raw_fd()), buf.as_ptr(), len)
+                .offset(offset)
+                .build();
+            // SAFETY: `buf` stays alive and unmodified until the completion
+            // below has been reaped
+            unsafe { ring.submission().push(&entry) }
+                .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+            ring.submit_and_wait(1)?;
+            let result = ring
+                .completion()
+                .next()
+                .ok_or_else(|| io::Error::other("missing io_uring completion"))?
+                .result();
+            match usize::try_from(result) {
+                Err(_) if result == -libc::EINTR => {}
+                Err(_) => return Err(io::Error::from_raw_os_error(-result)),
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    fn run(mut self, commands: &mpsc::Receiver<Command>) {
+        let mut error = None;
+        for command in commands {
+            match command {
+                Command::Write(buf) => {
+                    if error.is_none() {
+                        error = self.write_all(&buf).err();
+                    }
+                }
+                Command::Flush(reply) => {
+                    let _ = reply.send(error.take().map_or(Ok(()), Err));
+                }
+            }
+        }
+    }
+}
+
+/// Writes to a file or socket from a dedicated thread using `io_uring`.
+///
+/// Lines are batched into 64 KiB buffers before being handed to the writer
+/// thread. When `io_uring` is unavailable (old kernel, blocked by seccomp)
+/// the thread falls back to plain `write` calls. Write errors are reported
+/// by the next `flush` or `close`.
+pub struct UringSink {
+    commands: mpsc::Sender<Command>,
+    buf: Vec<u8>,
+    uring: bool,
+}
+
+impl UringSink {
+    /// Write to the file at `path`, opened with the default `FileSinkOptions`
+    /// # Errors
+    ///
+    /// Will error if the file can't be opened
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, StdoutChannelError> {
+        Self::open_with(path, FileSinkOptions::new()).await
+    }
+
+    /// # Errors
+    ///
+    /// Will error if the file can't be opened
+    pub async fn open_with(
+        path: impl AsRef<Path>,
+        options: FileSinkOptions,
+    ) -> Result<Self, StdoutChannelError> {
+        let file = options
+            .open_file(path.as_ref(), options.is_append())
+            .await?;
+        Self::new(file.into_std().await)
+    }
+
+    /// Write to an already open descriptor, e.g. a `File` or `TcpStream`
+    /// # Errors
+    ///
+    /// Will error if the writer thread can't be spawned
+    pub fn new(fd: impl Into<OwnedFd>) -> Result<Self, StdoutChannelError> {
+        let file = File::from(fd.into());
+        // SAFETY: `lseek` only queries the position of a descriptor we own
+        let seekable = unsafe { libc::lseek(file.as_raw_fd(), 0, libc::SEEK_CUR) } >= 0;
+        let backend = match IoUring::new(8) {
+            Ok(ring) => Backend::Uring {
+                ring: Box::new(ring),
+                seekable,
+            },
+            Err(_) => Backend::Std,
+        };
+        let uring = matches!(backend, Backend::Uring { .. });
+        let (commands, receiver) = mpsc::channel();
+        let writer = Writer { file, backend };
+        thread::Builder::new()
+            .name("stdout-channel-uring".into())
+            .spawn(move || writer.run(&receiver))?;
+        Ok(Self {
+            commands,
+            buf: Vec::with_capacity(BATCH_SIZE),
+            uring,
+        })
+    }
+
+    /// Whether writes go through `io_uring` rather than the fallback
+    #[must_use]
+    pub fn is_uring(&self) -> bool {
+        self.uring
+    }
+
+    fn send(&self, command: Command) -> Result<(), StdoutChannelError> {
+        self.commands
+            .send(command)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe).into())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), StdoutChannelError> {
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() >= BATCH_SIZE {
+            let buf = std::mem::replace(&mut self.buf, Vec::with_capacity(BATCH_SIZE));
+            self.send(Command::Write(buf))?;
+        }
+        Ok(())
+    }
+
+    async fn flush_writes(&mut self) -> Result<(), StdoutChannelError> {
+        if !self.buf.is_empty() {
+            let buf = std::mem::take(&mut self.buf);
+            self.send(Command::Write(buf))?;
+        }
+        let (reply, done) = oneshot::channel();
+        self.send(Command::Flush(reply))?;
+        done.await
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))??;
+        Ok(())
+    }
+}
+
+impl<T> OutputSink<T> for UringSink {
+    fn write<'a>(&'a mut self, line: OutputLine<'a, T>) -> SinkFuture<'a> {
+        let result = self.write_bytes(line.bytes());
+        Box::pin(async { result })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(self.flush_writes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+    use tokio::fs;
+
+    use crate::{StdoutChannel, StdoutChannelError};
+
+    use super::UringSink;
+
+    #[tokio::test]
+    async fn test_uring_sink() -> Result<(), StdoutChannelError> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let (socket, mut peer) = UnixStream::pair()?;
+        let reader = std::thread::spawn(move || {
+            let mut received = String::new();
+            std::io::Read::read_to_string(&mut peer, &mut received).map(|_| received)
+        });
+
+        let stdout = UringSink::open(dir.join("out")).await?;
+        let chan = StdoutChannel::<String>::with_sinks(stdout, UringSink::new(socket)?);
+        let long = "x".repeat(70_000);
+        chan.send("first");
+        chan.send(long.clone());
+        chan.send("last");
+        chan.send_err("to the socket");
+        chan.close().await?;
+
+        assert_eq!(
+            fs::read_to_string(dir.join("out")).await?,
+            format!("first\n{long}\nlast\n")
+        );
+        assert_eq!(reader.join().unwrap()?, "to the socket\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::{level::Level, sink::Stream, sync::Mutex, StdoutChannel};
+
+#[derive(Default)]
+struct Counts {
+    warnings: usize,
+    errors: usize,
+    instances: Vec<(Level, String)>,
+}
+
+/// Warnings and errors sent with `warn` and `error`, shared by all clones of
+/// a channel
+#[derive(Default)]
+pub(crate) struct Summary {
+    keep: usize,
+    counts: Mutex<Counts>,
+}
+
+impl Summary {
+    fn record(&self, level: Level, item: &impl Display) {
+        let mut counts = self.counts.lock();
+        let seen = counts.warnings + counts.errors;
+        match level {
+            Level::Error => counts.errors += 1,
+            _ => counts.warnings += 1,
+        }
+        if seen < self.keep {
+            counts.instances.push((level, item.to_string()));
+        }
+    }
+}
+
+impl<T> StdoutChannel<T> {
+    /// Keep the first `n` warnings and errors to be listed again by
+    /// `print_summary`, by default only their number is kept
+    #[must_use]
+    pub fn with_summary_instances(mut self, n: usize) -> Self {
+        self.summary = Summary {
+            keep: n,
+            counts: Mutex::default(),
+        }
+        .into();
+        self
+    }
+
+    #[must_use]
+    pub fn warnings(&self) -> usize {
+        self.summary.counts.lock().warnings
+    }
+
+    #[must_use]
+    pub fn errors(&self) -> usize {
+        self.summary.counts.lock().errors
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + 'static,
+{
+    /// Send a warning to stderr, counted for `print_summary`
+    pub fn warn(&self, item: impl Into<T>) {
+        self.send_level(Level::Warning, item);
+    }
+
+    /// Send an error to stderr, counted for `print_summary`
+    pub fn error(&self, item: impl Into<T>) {
+        self.send_level(Level::Error, item);
+    }
+
+    /// Send errors and warnings to stderr, counted for `print_summary`,
+    /// and info and debug messages to stdout. Messages of a level above the
+    /// verbosity (see `Level::min_verbosity` and `set_verbosity`) aren't
+    /// written but counted as filtered.
+    pub fn send_level(&self, level: Level, item: impl Into<T>) {
+        let stream = match level {
+            Level::Error | Level::Warning => Stream::Stderr,
+            _ => Stream::Stdout,
+        };
+        if self.verbosity() < level.min_verbosity() {
+            self.stats.filtered(stream);
+            return;
+        }
+        let item = item.into();
+        match stream {
+            Stream::Stderr => {
+                self.summary.record(level, &item);
+                self.send_err(item);
+            }
+            Stream::Stdout => self.send(item),
+        }
+    }
+}
+
+impl<T> StdoutChannel<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// List the kept warnings and errors followed by e.g. `2 warnings and 1
+    /// error emitted` on stderr, nothing is sent if there were none
+    pub fn print_summary(&self) {
+        let instances = std::mem::take(&mut self.summary.counts.lock().instances);
+        for (level, item) in instances {
+            self.send_err(format!("{level}: {item}"));
+        }
+        let (warnings, errors) = (self.warnings(), self.errors());
+        let plural = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+        let line = match (warnings, errors) {
+            (0, 0) => return,
+            (w, 0) => plural(w, "warning"),
+            (0, e) => plural(e, "error"),
+            (w, e) => format!("{} and {}", plural(w, "warning"), plural(e, "error")),
+        };
+        self.send_err(format!("{line} emitted"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Level, MockStdout, StdoutChannel, StdoutChannelError};
+
+    #[tokio::test]
+    async fn test_send_level() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+        chan.send_level(Level::Info, "info");
+        chan.send_level(Level::Debug, "hidden");
+        chan.set_verbosity(0);
+        chan.send_level(Level::Info, "quiet");
+        chan.send_level(Level::Warning, "warning");
+        chan.clone().set_verbosity(2);
+        assert_eq!(chan.verbosity(), 2);
+        chan.send_level(Level::Debug, "debug");
+        let stats = chan.close_with_stats().await?;
+
+        assert_eq!(stdout.snapshot(), ["info", "debug"]);
+        assert_eq!(stderr.snapshot(), ["warning"]);
+        assert_eq!(chan.warnings(), 1);
+        assert_eq!(stats.stdout.lines_filtered, 2);
+        assert_eq!(
+            stats.stdout.to_string(),
+            "2 lines, 0 bytes written, 2 filtered"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_print_summary() -> Result<(), StdoutChannelError> {
+        let stderr = MockStdout::<String>::new();
+        let chan = StdoutChannel::with_mock_stdout(MockStdout::new(), stderr.clone())
+            .with_summary_instances(2);
+        chan.print_summary();
+        chan.warn("unused variable `x`");
+        chan.error("mismatched types");
+        chan.warn("unused import");
+        assert_eq!((chan.warnings(), chan.errors()), (2, 1));
+        chan.print_summary();
+        chan.close().await?;
+
+        assert_eq!(
+            stderr.snapshot(),
+            [
+                "unused variable `x`",
+                "mismatched types",
+                "unused import",
+                "warning: unused variable `x`",
+                "error: mismatched types",
+                "2 warnings and 1 error emitted",
+            ]
+        );
+        Ok(())
+    }
+}
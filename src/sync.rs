@@ -0,0 +1,52 @@
+//! Blocking mutex used for internal state that is never held across an
+//! `.await`, `parking_lot::Mutex` with the `parking_lot` feature and a
+//! poison-ignoring wrapper around `std::sync::Mutex` otherwise.
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking_lot::Mutex;
+
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use std_mutex::Mutex;
+
+#[cfg(not(feature = "parking_lot"))]
+mod std_mutex {
+    use std::sync::{MutexGuard, PoisonError};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T: ?Sized>(std::sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(std::sync::Mutex::new(value))
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
+        /// A panic while the lock was held doesn't leave the protected state
+        /// inconsistent anywhere in this crate, so poisoning is ignored
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Mutex;
+
+    #[test]
+    fn test_lock_after_panic() {
+        let mutex = Arc::new(Mutex::new(1));
+        let cloned = Arc::clone(&mutex);
+        let result = std::thread::spawn(move || {
+            let _guard = cloned.lock();
+            panic!("poison");
+        })
+        .join();
+        assert!(result.is_err());
+        *mutex.lock() += 1;
+        assert_eq!(*mutex.lock(), 2);
+    }
+}
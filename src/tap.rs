@@ -0,0 +1,130 @@
+use std::fmt::Display;
+
+use crate::StdoutChannel;
+
+/// Writes a TAP version 14 stream through a `StdoutChannel`.
+///
+/// Test points go to stdout, and anything sent with `send_err` is turned into
+/// `# ` diagnostic comments within the same stream so TAP consumers keep it
+/// attached to the surrounding test points.
+pub struct TapWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    chan: StdoutChannel<T>,
+    count: usize,
+    planned: bool,
+}
+
+impl<T> TapWriter<T>
+where
+    T: Display + Send + From<String> + 'static,
+{
+    /// Start a TAP stream, emitting the version line
+    #[must_use]
+    pub fn new(chan: &StdoutChannel<T>) -> Self {
+        chan.send("TAP version 14".to_string());
+        Self {
+            chan: chan.clone(),
+            count: 0,
+            planned: false,
+        }
+    }
+
+    /// Emit the plan up front; otherwise it is written by `finish`
+    pub fn plan(&mut self, tests: usize) {
+        self.planned = true;
+        self.chan.send(format!("1..{tests}"));
+    }
+
+    /// Number of test points written so far
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn ok(&mut self, description: &str) {
+        let point = self.point("ok", description);
+        self.chan.send(point);
+    }
+
+    pub fn not_ok(&mut self, description: &str) {
+        let point = self.point("not ok", description);
+        self.chan.send(point);
+    }
+
+    pub fn skip(&mut self, description: &str, reason: &str) {
+        let point = self.point("ok", description);
+        self.chan.send(format!("{point} # SKIP {reason}"));
+    }
+
+    pub fn todo(&mut self, description: &str, reason: &str) {
+        let point = self.point("not ok", description);
+        self.chan.send(format!("{point} # TODO {reason}"));
+    }
+
+    /// Write a diagnostic, each line becomes a `# ` comment
+    pub fn send_err(&self, item: impl Display) {
+        for line in item.to_string().lines() {
+            self.chan.send(format!("# {line}"));
+        }
+    }
+
+    /// Abort the whole run
+    pub fn bail_out(self, reason: &str) {
+        self.chan.send(format!("Bail out! {reason}"));
+    }
+
+    /// End the stream, writing a trailing plan if none was given
+    pub fn finish(self) {
+        if !self.planned {
+            self.chan.send(format!("1..{}", self.count));
+        }
+    }
+
+    fn point(&mut self, status: &str, description: &str) -> String {
+        self.count += 1;
+        let description = description.replace('\\', "\\\\").replace('#', "\\#");
+        format!("{status} {} - {description}", self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockStdout, StdoutChannel, StdoutChannelError};
+
+    use super::TapWriter;
+
+    #[tokio::test]
+    async fn test_tap() -> Result<(), StdoutChannelError> {
+        let stdout = MockStdout::<String>::new();
+        let stderr = MockStdout::new();
+        let chan = StdoutChannel::with_mock_stdout(stdout.clone(), stderr.clone());
+
+        let mut tap = TapWriter::new(&chan);
+        tap.ok("parses config");
+        tap.not_ok("handles #hashtags");
+        tap.send_err("expected: 1\ngot: 2");
+        tap.skip("network", "offline");
+        tap.todo("windows paths", "not implemented");
+        assert_eq!(tap.count(), 4);
+        tap.finish();
+        chan.close().await?;
+
+        assert_eq!(
+            stdout.lock().await.as_slice(),
+            [
+                "TAP version 14",
+                "ok 1 - parses config",
+                "not ok 2 - handles \\#hashtags",
+                "# expected: 1",
+                "# got: 2",
+                "ok 3 - network # SKIP offline",
+                "not ok 4 - windows paths # TODO not implemented",
+                "1..4",
+            ]
+        );
+        assert!(stderr.lock().await.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! A small zlib (RFC 1950) encoder for payloads of a few kilobytes, like
+//! GELF messages: one fixed Huffman deflate block with LZ77 matches found
+//! through a hash chain.
+
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates looked at for every position
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 14;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes bits least significant first, as deflate packs them
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    len: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, len: u32) {
+        self.bits |= value << self.len;
+        self.len += len;
+        while self.len >= 8 {
+            self.out.push(self.bits.to_le_bytes()[0]);
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which deflate packs most significant bit first
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.bits.to_le_bytes()[0]);
+        }
+        self.out
+    }
+
+    fn literal(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn matched(&mut self, len: usize, distance: usize) {
+        // both are at most 258 and 32768, they fit into a u16
+        #[allow(clippy::cast_possible_truncation)]
+        let (len, distance) = (len as u16, distance as u16);
+        let i = LENGTH_BASE.partition_point(|&base| base <= len) - 1;
+        // the index of at most 29 codes fits into a u16
+        #[allow(clippy::cast_possible_truncation)]
+        self.literal(257 + i as u16);
+        self.write(u32::from(len - LENGTH_BASE[i]), LENGTH_EXTRA[i].into());
+        let i = DISTANCE_BASE.partition_point(|&base| base <= distance) - 1;
+        // same for the 30 distance codes
+        #[allow(clippy::cast_possible_truncation)]
+        self.write_code(i as u32, 5);
+        self.write(
+            u32::from(distance - DISTANCE_BASE[i]),
+            DISTANCE_EXTRA[i].into(),
+        );
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    let key = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Add the 3 bytes at `pos` to the hash chains
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash(&data[pos..]);
+        prev[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+    // sums of 5552 bytes can't overflow a u32 before the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    b << 16 | a
+}
+
+/// The zlib stream of `data`
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // CMF: deflate with a 32 KiB window, FLG: fastest level, check bits
+    bits.write(0x78, 8);
+    bits.write(0x01, 8);
+    // the only block, with fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut best_len, mut best_distance) = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || pos - candidate > WINDOW {
+                    break;
+                }
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    (best_len, best_distance) = (len, pos - candidate);
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+            }
+        }
+        if best_len >= MIN_MATCH {
+            bits.matched(best_len, best_distance);
+            for p in pos..pos + best_len {
+                insert(data, p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            bits.literal(data[pos].into());
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+    bits.literal(256);
+
+    let mut out = bits.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, compress};
+
+    #[test]
+    fn test_compress() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(
+            compress(b""),
+            [0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]
+        );
+        // the repeats are a single match, as zlib.decompress confirms
+        assert_eq!(
+            compress(b"hello hello hello hello"),
+            [
+                0x78, 0x01, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc0, 0x20, 0x01, 0x68, 0x03, 0x08,
+                0xb1
+            ]
+        );
+    }
+}